@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+use std::time::Duration;
+use benimator::State;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use liege::animations::animation::{AnimationDefinition, AnimationMap, AnimationMode, Frame, LiegeAnimation, LiegeSprite, Size, SpriteSheet};
+use liege::components::AnimatedComponent;
+
+const ENTITY_COUNT: usize = 5_000;
+const FRAME_TIME: Duration = Duration::from_nanos(16_666_667);
+
+// How animations used to be held: every entity owned a full copy of its animation
+struct ClonedAnimatedComponent {
+    liege_animation: LiegeAnimation,
+    animation_state: State,
+}
+
+fn creature_animations() -> HashMap<String, LiegeAnimation> {
+    let sprite = LiegeSprite {
+        frame: Frame { x: 0, y: 0, w: 9, h: 9 },
+        rotated: false,
+        trimmed: false,
+        sprite_source_size: Frame { x: 0, y: 0, w: 9, h: 9 },
+        source_size: Size { w: 9, h: 9 },
+        duration: None,
+    };
+
+    let mut animations = HashMap::new();
+    for (name, frame_count) in [("idle_right", 16), ("walk_right", 4), ("die", 26)] {
+        let prefix = format!("goblin_{}_", name);
+        let frames = (1..=frame_count).map(|i| (format!("{}{}.png", prefix, i), sprite)).collect();
+        let definition = AnimationDefinition {
+            name: name.to_string(),
+            frame_prefix: Some(prefix),
+            frame_count,
+            fps: Some(8.),
+            mode: Some(AnimationMode::Loop),
+            ..Default::default()
+        };
+        let animation = definition.build("goblin", &SpriteSheet::new(frames, Vec::new())).unwrap();
+        animations.insert(format!("goblin_{}", name), animation);
+    }
+    animations
+}
+
+fn bench_animation_switch(c: &mut Criterion) {
+    let animations = creature_animations();
+    let animation_map = AnimationMap::new(animations.clone());
+    let labels = ["goblin_idle_right", "goblin_walk_right", "goblin_die"];
+    let ids: Vec<_> = labels.iter().map(|label| animation_map.id(label).unwrap()).collect();
+
+    let mut group = c.benchmark_group("switch_animation");
+    group.bench_function(BenchmarkId::new("cloned", ENTITY_COUNT), |b| {
+        let mut components: Vec<_> = (0..ENTITY_COUNT)
+            .map(|_| ClonedAnimatedComponent { liege_animation: animations[labels[0]].clone(), animation_state: State::new() })
+            .collect();
+        let mut next = 0;
+        b.iter(|| {
+            next = (next + 1) % labels.len();
+            for component in components.iter_mut() {
+                component.liege_animation = animations[labels[next]].clone();
+                component.animation_state = State::new();
+            }
+        });
+    });
+    group.bench_function(BenchmarkId::new("shared", ENTITY_COUNT), |b| {
+        let mut components: Vec<_> = (0..ENTITY_COUNT).map(|_| AnimatedComponent::new(ids[0])).collect();
+        let mut next = 0;
+        b.iter(|| {
+            next = (next + 1) % ids.len();
+            for component in components.iter_mut() {
+                component.play(ids[next]);
+            }
+        });
+    });
+    group.finish();
+}
+
+// One frame's worth of animation work: advance every entity, then collect what to draw
+fn bench_update_and_render(c: &mut Criterion) {
+    let animations = creature_animations();
+    let animation_map = AnimationMap::new(animations.clone());
+    let walk = animation_map.id("goblin_walk_right").unwrap();
+
+    let mut group = c.benchmark_group("update_and_render");
+    group.bench_function(BenchmarkId::new("cloned", ENTITY_COUNT), |b| {
+        let mut components: Vec<_> = (0..ENTITY_COUNT)
+            .map(|_| ClonedAnimatedComponent { liege_animation: animations["goblin_walk_right"].clone(), animation_state: State::new() })
+            .collect();
+        b.iter(|| {
+            let mut render_data = Vec::with_capacity(ENTITY_COUNT);
+            for component in components.iter_mut() {
+                component.animation_state.update(&component.liege_animation.animation, FRAME_TIME);
+                render_data.push((component.liege_animation.clone(), component.animation_state.clone()));
+            }
+            black_box(render_data);
+        });
+    });
+    group.bench_function(BenchmarkId::new("shared", ENTITY_COUNT), |b| {
+        let mut components: Vec<_> = (0..ENTITY_COUNT).map(|_| AnimatedComponent::new(walk)).collect();
+        b.iter(|| {
+            let mut render_data = Vec::with_capacity(ENTITY_COUNT);
+            for component in components.iter_mut() {
+                component.update(&animation_map, FRAME_TIME);
+                render_data.push(*component.current_frame(&animation_map));
+            }
+            black_box(render_data);
+        });
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_animation_switch, bench_update_and_render);
+criterion_main!(benches);
@@ -0,0 +1,72 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use macroquad::math::Vec2;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use liege::flow_field::{FlowField, FlowFieldCache};
+use liege::map::{GameMap, MapGenConfig};
+use liege::pathfinding::find_path;
+use liege::MapInformation;
+
+const AGENT_COUNT: usize = 1_000;
+const MAP_SIZE: i32 = 200;
+
+struct Scenario {
+    game_map: GameMap,
+    map_info: MapInformation,
+    goal: (i32, i32),
+    agents: Vec<(i32, i32)>,
+}
+
+fn setup() -> Scenario {
+    let mut game_map = GameMap::new(MAP_SIZE, MAP_SIZE, MapGenConfig::default());
+    game_map.generate_terrain();
+    let map_info = MapInformation { width: MAP_SIZE, height: MAP_SIZE, tile_size: 8., tile_scale: Vec2::new(2.5, 2.5) };
+
+    // Everyone heads for the middle of the map, from anywhere they could reach it from
+    let goal = game_map.nearest_passable(MAP_SIZE / 2, MAP_SIZE / 2).unwrap();
+    let field = FlowField::new(&game_map, goal);
+    let mut rng = StdRng::seed_from_u64(1);
+    let mut agents = Vec::with_capacity(AGENT_COUNT);
+    while agents.len() < AGENT_COUNT {
+        let tile = (rng.gen_range(0..MAP_SIZE), rng.gen_range(0..MAP_SIZE));
+        if game_map.is_passable(tile.0, tile.1) && field.cost(tile.0, tile.1).is_some() {
+            agents.push(tile);
+        }
+    }
+    Scenario { game_map, map_info, goal, agents }
+}
+
+fn bench_build_flow_field(c: &mut Criterion) {
+    let Scenario { game_map, goal, .. } = setup();
+    c.bench_function("build_flow_field/200x200", |b| b.iter(|| FlowField::new(black_box(&game_map), goal)));
+}
+
+fn bench_agents(c: &mut Criterion) {
+    let Scenario { game_map, map_info, goal, agents } = setup();
+    let destination = map_info.tile_center(goal.0, goal.1);
+
+    let mut group = c.benchmark_group("route_agents");
+    group.sample_size(10);
+    // Every agent finding its own way there
+    group.bench_function(BenchmarkId::new("a_star", AGENT_COUNT), |b| {
+        b.iter(|| {
+            for &start in &agents {
+                black_box(find_path(&game_map, start, goal));
+            }
+        })
+    });
+    // One field for everyone, built the first time and then only looked up, as it is every tick in game
+    group.bench_function(BenchmarkId::new("flow_field", AGENT_COUNT), |b| {
+        let mut cache = FlowFieldCache::default();
+        b.iter(|| {
+            let field = cache.get(&game_map, goal);
+            for &(x, y) in &agents {
+                black_box(field.waypoint(&map_info, map_info.tile_center(x, y), destination));
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_build_flow_field, bench_agents);
+criterion_main!(benches);
@@ -1,37 +1,30 @@
-use std::collections::HashMap;
-use std::fs;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Mutex;
+use std::time::Duration;
+use std::{fmt, fs};
 use benimator::{Animation, State};
 use macroquad::math::Vec2;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
+use crate::animations::aseprite::{FrameTag, RawSpriteSheet, TagDirection};
+use crate::assets::TextureId;
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum AnimationStates {
-    IdleRight,
-    IdleLeft,
+    Idle,
+    Walk,
+    Attack,
     Die,
-    AttackRight,
-    AttackLeft,
-    AttackUpRight,
-    AttackUpLeft,
-    WalkRight,
-    WalkLeft,
-    WalkUpRight,
-    WalkUpLeft
 }
 
 impl AnimationStates {
-    pub fn to_str(&self) -> &str {
+    pub const ALL: [AnimationStates; 4] = [AnimationStates::Idle, AnimationStates::Walk, AnimationStates::Attack, AnimationStates::Die];
+
+    pub fn to_str(self) -> &'static str {
         match self {
-            AnimationStates::IdleRight => "_idle_right",
-            AnimationStates::IdleLeft => "_idle_left",
+            AnimationStates::Idle => "_idle",
+            AnimationStates::Walk => "_walk",
+            AnimationStates::Attack => "_attack",
             AnimationStates::Die => "_die",
-            AnimationStates::AttackRight => "_attack_right",
-            AnimationStates::AttackLeft => "_attack_left",
-            AnimationStates::AttackUpRight => "_attack_up_right",
-            AnimationStates::AttackUpLeft => "_attack_up_left",
-            AnimationStates::WalkRight => "_walk_right",
-            AnimationStates::WalkLeft => "_walk_left",
-            AnimationStates::WalkUpRight => "_walk_up_right",
-            AnimationStates::WalkUpLeft => "_walk_up_left"
         }
     }
 
@@ -40,12 +33,76 @@ impl AnimationStates {
     }
 }
 
+// A cheap, copyable handle to an animation stored in the AnimationMap
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct AnimationId(usize);
+
+// Every loaded animation, stored once. Components hold an AnimationId rather than their own copy of the frames.
+#[derive(Default)]
 pub struct AnimationMap {
-    pub animations: HashMap<String, LiegeAnimation>
+    animations: Vec<LiegeAnimation>,
+    labels: Vec<String>,
+    ids: HashMap<String, AnimationId>,
 }
 
-pub struct UIAnimationMap<'a> {
-    pub animations: HashMap<String, LiegeUIAnimation<'a>>
+impl AnimationMap {
+    pub fn new(animations: HashMap<String, LiegeAnimation>) -> Self {
+        let mut animation_map = Self::default();
+        for (label, animation) in animations {
+            animation_map.insert(label, animation);
+        }
+        animation_map
+    }
+
+    // Add an animation under a label. Replacing an existing label keeps its id, so anything already pointing at it
+    // picks up the new animation.
+    pub fn insert(&mut self, label: String, animation: LiegeAnimation) -> AnimationId {
+        if let Some(&id) = self.ids.get(&label) {
+            self.animations[id.0] = animation;
+            return id;
+        }
+
+        let id = AnimationId(self.animations.len());
+        self.animations.push(animation);
+        self.labels.push(label.clone());
+        self.ids.insert(label, id);
+        id
+    }
+
+    // Stop the label from being looked up. The animation itself stays where it is, so ids already handed out for it
+    // don't end up pointing at a different animation.
+    pub fn remove(&mut self, label: &str) -> Option<AnimationId> {
+        self.ids.remove(label)
+    }
+
+    pub fn id(&self, label: &str) -> Option<AnimationId> {
+        self.ids.get(label).copied()
+    }
+
+    // Every label that can currently be looked up, in no particular order
+    pub fn labels(&self) -> impl Iterator<Item=&str> {
+        self.ids.keys().map(String::as_str)
+    }
+
+    pub fn get(&self, id: AnimationId) -> &LiegeAnimation {
+        &self.animations[id.0]
+    }
+
+    pub fn label(&self, id: AnimationId) -> &str {
+        &self.labels[id.0]
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+}
+
+pub struct UIAnimationMap {
+    pub animations: HashMap<String, LiegeUIAnimation>
 }
 
 #[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
@@ -71,44 +128,373 @@ pub struct LiegeSprite {
     pub sprite_source_size: Frame,
     #[serde(rename = "sourceSize")]
     pub source_size: Size,
+    // How long this frame is shown for, in milliseconds. Only present in Aseprite exports.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<u32>,
 }
 
-#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+#[serde(from = "RawSpriteSheet")]
 pub struct SpriteSheet {
-    pub(crate) frames: HashMap<String, LiegeSprite>
+    pub frames: HashMap<String, LiegeSprite>,
+    // Frame names in the order they appear in the sheet, which is what frame tags index into
+    pub frame_names: Vec<String>,
+    pub frame_tags: Vec<FrameTag>,
+}
+
+impl SpriteSheet {
+    pub fn new(frames: Vec<(String, LiegeSprite)>, frame_tags: Vec<FrameTag>) -> Self {
+        let frame_names = frames.iter().map(|(name, _)| name.clone()).collect();
+        Self {
+            frames: frames.into_iter().collect(),
+            frame_names,
+            frame_tags,
+        }
+    }
+
+    pub fn frame_tag(&self, name: &str) -> Option<&FrameTag> {
+        self.frame_tags.iter().find(|tag| tag.name == name)
+    }
 }
 
 #[derive(Clone, PartialEq, Debug)]
 pub struct LiegeAnimation {
-    pub(crate) frames: Vec<LiegeSprite>,
-    pub(crate) animation: Animation,
+    pub frames: Vec<LiegeSprite>,
+    pub animation: Animation,
+    pub events: Vec<FrameEvent>,
+    // How long the briefest frame is shown for, so playback can be stepped a frame at a time
+    pub shortest_frame: Duration,
+}
+
+impl LiegeAnimation {
+    pub fn events_on_frame(&self, frame: usize) -> impl Iterator<Item = &'static str> + '_ {
+        self.events.iter().filter(move |event| event.frame == frame).map(|event| event.name)
+    }
+}
+
+// Finite repeats are played by laying the frames out that many times over, so the count is kept within reason
+const MAX_REPEAT: u32 = 100;
+
+// Raised by the animated component on the tick a non-looping animation plays out its last frame
+pub const ANIMATION_END_EVENT: &str = "end";
+
+// A named event fired when playback reaches a frame, e.g. the frame an attack connects on. `frame` is the
+// zero-based position of the frame within the animation.
+#[derive(Serialize, Clone, PartialEq, Debug)]
+pub struct FrameEvent {
+    pub frame: usize,
+    pub name: &'static str,
+}
+
+#[derive(Deserialize)]
+struct RawFrameEvent {
+    frame: usize,
+    name: String,
+}
+
+// Event names are read as owned strings and interned, as the definition file they come from doesn't live for good
+impl<'de> Deserialize<'de> for FrameEvent {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = RawFrameEvent::deserialize(deserializer)?;
+        Ok(FrameEvent { frame: raw.frame, name: intern_event_name(&raw.name) })
+    }
+}
+
+// Every event name ever loaded. Names are interned so raising an event is a copy rather than an allocation, and as
+// there are only ever a handful of them, they're kept for good.
+static EVENT_NAMES: Mutex<BTreeSet<&'static str>> = Mutex::new(BTreeSet::new());
+
+pub fn intern_event_name(name: &str) -> &'static str {
+    let mut names = EVENT_NAMES.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    match names.get(name) {
+        Some(interned) => interned,
+        None => {
+            let interned: &'static str = Box::leak(name.to_string().into_boxed_str());
+            names.insert(interned);
+            interned
+        }
+    }
 }
 
 #[derive(Clone)]
-pub struct LiegeUIAnimation<'a> {
-    pub(crate) frames: Vec<LiegeSprite>,
-    pub(crate) animation: Animation,
-    pub(crate) texture_handle: &'a str,
-    pub(crate) position: Vec2,
-    pub(crate) state: State,
+pub struct LiegeUIAnimation {
+    pub frames: Vec<LiegeSprite>,
+    pub animation: Animation,
+    pub texture: TextureId,
+    pub position: Vec2,
+    pub state: State,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum AnimationMode {
+    #[default]
+    Loop,
+    Once,
+    PingPong,
+}
+
+// A single animation for a creature. Frames either come from an Aseprite frame tag, are listed by name in `frames`,
+// or are the frames named `{frame_prefix}1.png` through `{frame_prefix}{frame_count}.png` in the creature's sprite
+// sheet. Without an `fps`, each frame is shown for its own duration from the sprite sheet.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Default)]
+pub struct AnimationDefinition {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub frames: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frame_prefix: Option<String>,
+    #[serde(default)]
+    pub frame_count: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fps: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<AnimationMode>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<FrameEvent>,
+}
+
+// Per-creature animation definition file, e.g. resources/characters/rogue/rogue_animations.json. `sprite_sheet` is
+// the id of a sprite sheet in the asset manifest. Every frame tag in the sprite sheet becomes an animation named
+// after the tag; `animations` adds to or overrides those.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct CreatureAnimations {
+    pub creature: String,
+    pub sprite_sheet: String,
+    #[serde(default)]
+    pub animations: Vec<AnimationDefinition>,
+}
+
+#[derive(Debug)]
+pub enum AnimationLoadError {
+    Io { path: String, source: std::io::Error },
+    Parse { path: String, source: serde_json::Error },
+    MissingFrames { animation: String, frames: Vec<String> },
+    InvalidDefinition { animation: String, reason: String },
 }
 
-pub(crate) fn read_json_file(file_path: &str) -> Result<String, std::io::Error> {
+impl fmt::Display for AnimationLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AnimationLoadError::Io { path, source } => write!(f, "failed to read {}: {}", path, source),
+            AnimationLoadError::Parse { path, source } => write!(f, "failed to parse {}: {}", path, source),
+            AnimationLoadError::MissingFrames { animation, frames } => {
+                write!(f, "animation '{}' references missing frame(s) '{}'", animation, frames.join("', '"))
+            }
+            AnimationLoadError::InvalidDefinition { animation, reason } => {
+                write!(f, "animation '{}' is invalid: {}", animation, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AnimationLoadError {}
+
+impl AnimationDefinition {
+    // Names of the sprite sheet frames this animation plays, in playback order
+    pub fn frame_names(&self, creature: &str, sprite_sheet: &SpriteSheet) -> Result<Vec<String>, AnimationLoadError> {
+        let label = format!("{}_{}", creature, self.name);
+        match (&self.tag, &self.frame_prefix) {
+            (Some(tag_name), _) => match sprite_sheet.frame_tag(tag_name) {
+                Some(tag) => tag_frame_names(&label, tag, sprite_sheet),
+                None => Err(AnimationLoadError::InvalidDefinition { animation: label, reason: format!("sprite sheet has no frame tag '{}'", tag_name) }),
+            },
+            (None, _) if !self.frames.is_empty() => Ok(self.frames.clone()),
+            (None, Some(prefix)) => {
+                if self.frame_count == 0 {
+                    return Err(AnimationLoadError::InvalidDefinition { animation: label, reason: "frame_count must be at least 1".to_string() });
+                }
+                Ok((1..=self.frame_count).map(|i| format!("{}{}.png", prefix, i)).collect())
+            }
+            (None, None) => Err(AnimationLoadError::InvalidDefinition { animation: label, reason: "one of tag, frames or frame_prefix must be set".to_string() }),
+        }
+    }
+
+    pub fn build(&self, creature: &str, sprite_sheet: &SpriteSheet) -> Result<LiegeAnimation, AnimationLoadError> {
+        let label = format!("{}_{}", creature, self.name);
+        let frame_names = self.frame_names(creature, sprite_sheet)?;
+
+        let mut frames = Vec::with_capacity(frame_names.len());
+        let mut missing = Vec::new();
+        for frame_name in frame_names {
+            match sprite_sheet.frames.get(&frame_name) {
+                Some(sprite) => frames.push(*sprite),
+                None => missing.push(frame_name),
+            }
+        }
+        if !missing.is_empty() {
+            return Err(AnimationLoadError::MissingFrames { animation: label, frames: missing });
+        }
+
+        // Tag animations pick up their playback mode and repeat count from Aseprite unless the definition overrides them
+        let (mode, repeat) = match (self.mode, self.tag.as_deref().and_then(|tag| sprite_sheet.frame_tag(tag))) {
+            (Some(mode), _) => (mode, None),
+            (None, Some(tag)) => tag_playback(tag),
+            (None, None) => (AnimationMode::default(), None),
+        };
+
+        if let Some(event) = self.events.iter().find(|event| event.frame >= frames.len()) {
+            return Err(AnimationLoadError::InvalidDefinition {
+                animation: label,
+                reason: format!("event '{}' is on frame {}, but the animation has {} frames", event.name, event.frame, frames.len()),
+            });
+        }
+
+        let mut animation = build_animation(label, frames, self.fps, mode, repeat)?;
+        animation.events = self.events.clone();
+        Ok(animation)
+    }
+}
+
+impl CreatureAnimations {
+    // Build every animation for this creature, keyed by its full label (e.g. "rogue_walk_left")
+    pub fn build(&self, sprite_sheet: &SpriteSheet) -> Result<HashMap<String, LiegeAnimation>, AnimationLoadError> {
+        let (animations_map, errors) = self.build_each(sprite_sheet);
+        match errors.into_iter().next() {
+            Some(error) => Err(error),
+            None => Ok(animations_map),
+        }
+    }
+
+    // Build as many of this creature's animations as possible, carrying on past any that fail. Returns the ones that
+    // built along with why each of the others didn't.
+    pub fn build_each(&self, sprite_sheet: &SpriteSheet) -> (HashMap<String, LiegeAnimation>, Vec<AnimationLoadError>) {
+        let mut animations_map = HashMap::new();
+        let mut errors = Vec::new();
+        let mut build = |definition: &AnimationDefinition| match definition.build(&self.creature, sprite_sheet) {
+            Ok(animation) => {
+                animations_map.insert(format!("{}_{}", self.creature, definition.name), animation);
+            }
+            Err(error) => errors.push(error),
+        };
+        for tag in &sprite_sheet.frame_tags {
+            build(&AnimationDefinition { name: tag.name.clone(), tag: Some(tag.name.clone()), ..Default::default() });
+        }
+        // Definitions come after the tags, so they replace a tag animation with the same name
+        self.animations.iter().for_each(build);
+
+        (animations_map, errors)
+    }
+}
+
+fn tag_frame_names(label: &str, tag: &FrameTag, sprite_sheet: &SpriteSheet) -> Result<Vec<String>, AnimationLoadError> {
+    if tag.from > tag.to || tag.to >= sprite_sheet.frame_names.len() {
+        return Err(AnimationLoadError::InvalidDefinition {
+            animation: label.to_string(),
+            reason: format!("frame tag '{}' covers frames {}..={}, but the sheet has {} frames", tag.name, tag.from, tag.to, sprite_sheet.frame_names.len()),
+        });
+    }
+
+    let mut names = sprite_sheet.frame_names[tag.from..=tag.to].to_vec();
+    if matches!(tag.direction, TagDirection::Reverse | TagDirection::PingpongReverse) {
+        names.reverse();
+    }
+    Ok(names)
+}
+
+// How a frame tag plays, and how many times before it stops (None to play forever). Aseprite leaves the repeat count
+// out, or writes 0, for tags that loop forever.
+fn tag_playback(tag: &FrameTag) -> (AnimationMode, Option<u32>) {
+    let mode = match tag.direction {
+        TagDirection::Pingpong | TagDirection::PingpongReverse => AnimationMode::PingPong,
+        TagDirection::Forward | TagDirection::Reverse => AnimationMode::Loop,
+    };
+    (mode, tag.repeat.filter(|&count| count > 0))
+}
+
+// Frame indices in the order they play, for an animation that plays `repeat` times and then stops. A ping pong plays
+// there and back each time, finishing on its first frame.
+fn repeated_sequence(frame_count: usize, mode: AnimationMode, repeat: u32) -> Vec<usize> {
+    let pass: Vec<usize> = match mode {
+        AnimationMode::PingPong if frame_count > 1 => (1..frame_count).chain((0..frame_count - 1).rev()).collect(),
+        _ => (0..frame_count).collect(),
+    };
+    let mut sequence = Vec::with_capacity(pass.len() * repeat as usize + 1);
+    if mode == AnimationMode::PingPong && frame_count > 1 {
+        sequence.push(0);
+    }
+    for _ in 0..repeat {
+        sequence.extend(&pass);
+    }
+    sequence
+}
+
+// Turn a list of frames into a playable animation, either at a uniform frame rate, or using each frame's own duration.
+// A repeat count plays the animation that many times and then stops.
+fn build_animation(label: String, frames: Vec<LiegeSprite>, fps: Option<f64>, mode: AnimationMode, repeat: Option<u32>) -> Result<LiegeAnimation, AnimationLoadError> {
+    let durations: Vec<Duration> = match fps {
+        Some(fps) if fps.is_finite() && fps > 0. => vec![Duration::from_secs(1).div_f64(fps); frames.len()],
+        Some(fps) => return Err(AnimationLoadError::InvalidDefinition { animation: label, reason: format!("fps must be positive, got {}", fps) }),
+        None => {
+            let mut durations = Vec::with_capacity(frames.len());
+            for (index, sprite) in frames.iter().enumerate() {
+                match sprite.duration {
+                    Some(millis) if millis > 0 => durations.push(Duration::from_millis(millis as u64)),
+                    _ => return Err(AnimationLoadError::InvalidDefinition { animation: label, reason: format!("no fps given and frame {} has no duration", index) }),
+                }
+            }
+            durations
+        }
+    };
+    let timed_frames = |indices: Vec<usize>| indices.into_iter().map(|index| benimator::Frame::new(index, durations[index])).collect::<Vec<_>>();
+
+    let animation = match (mode, repeat) {
+        (AnimationMode::Once, _) => Animation::from_frames(timed_frames((0..frames.len()).collect())).once(),
+        (AnimationMode::Loop, None) => Animation::from_frames(timed_frames((0..frames.len()).collect())).repeat(),
+        (AnimationMode::PingPong, None) => Animation::from_frames(timed_frames((0..frames.len()).collect())).ping_pong(),
+        (_, Some(count)) if count > MAX_REPEAT => {
+            return Err(AnimationLoadError::InvalidDefinition { animation: label, reason: format!("repeats {} times, more than the most allowed ({})", count, MAX_REPEAT) });
+        }
+        (_, Some(count)) => Animation::from_frames(timed_frames(repeated_sequence(frames.len(), mode, count))).once(),
+    };
+
+    let shortest_frame = durations.iter().copied().min().unwrap_or_default();
+    Ok(LiegeAnimation { frames, animation, events: Vec::new(), shortest_frame })
+}
+
+pub fn read_json_file(file_path: &str) -> Result<String, std::io::Error> {
     fs::read_to_string(file_path)
 }
 
-pub(crate) fn deserialize_json(json_str: &str) -> Result<SpriteSheet, serde_json::Error> {
+pub fn deserialize_json(json_str: &str) -> Result<SpriteSheet, serde_json::Error> {
     serde_json::from_str(json_str)
 }
 
+pub fn load_creature_definition(file_path: &str) -> Result<CreatureAnimations, AnimationLoadError> {
+    let file_content = read_json_file(file_path)
+        .map_err(|source| AnimationLoadError::Io { path: file_path.to_string(), source })?;
+    serde_json::from_str(&file_content)
+        .map_err(|source| AnimationLoadError::Parse { path: file_path.to_string(), source })
+}
+
+pub fn load_sprite_sheet(file_path: &str) -> Result<SpriteSheet, AnimationLoadError> {
+    let file_content = read_json_file(file_path)
+        .map_err(|source| AnimationLoadError::Io { path: file_path.to_string(), source })?;
+    deserialize_json(&file_content)
+        .map_err(|source| AnimationLoadError::Parse { path: file_path.to_string(), source })
+}
+
 
 #[cfg(test)]
 mod tests {
+    use std::io::Write;
+    use benimator::FrameRate;
     use super::*;
 
     #[test]
     fn test_read_and_deserialize() {
-        let file_content = match read_json_file("resources/characters/rogue/rogue.json") {
+        // The real sprite sheets aren't distributed, so read a cut down copy of the rogue's
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(br#"{"frames": {
+            "rogue_attack_left_1.png": {"frame": {"x": 26, "y": 0, "w": 9, "h": 9}, "rotated": false, "trimmed": false,
+                "spriteSourceSize": {"x": 0, "y": 0, "w": 9, "h": 9}, "sourceSize": {"w": 9, "h": 9}},
+            "rogue_attack_left_2.png": {"frame": {"x": 35, "y": 0, "w": 9, "h": 9}, "rotated": false, "trimmed": false,
+                "spriteSourceSize": {"x": 0, "y": 0, "w": 9, "h": 9}, "sourceSize": {"w": 9, "h": 9}}
+        }}"#).unwrap();
+
+        let file_content = match read_json_file(&file.path().to_string_lossy()) {
             Ok(content) => content,
             Err(e) => panic!("Failed to read file: {}", e),
         };
@@ -122,4 +508,287 @@ mod tests {
         let sprite = &deserialized_content.frames["rogue_attack_left_1.png"];
         assert_eq!(sprite.frame.x, 26);
     }
-}
\ No newline at end of file
+
+    fn test_sprite(x: i32) -> LiegeSprite {
+        LiegeSprite {
+            frame: Frame { x, y: 0, w: 9, h: 9 },
+            rotated: false,
+            trimmed: false,
+            sprite_source_size: Frame { x: 0, y: 0, w: 9, h: 9 },
+            source_size: Size { w: 9, h: 9 },
+            duration: None,
+        }
+    }
+
+    fn test_sprite_sheet(prefix: &str, count: i32) -> SpriteSheet {
+        let frames = (1..=count).map(|i| (format!("{}{}.png", prefix, i), test_sprite(i * 9))).collect();
+        SpriteSheet::new(frames, Vec::new())
+    }
+
+    #[test]
+    fn test_deserialize_creature_definition() {
+        let definition: CreatureAnimations = serde_json::from_str(r#"{
+            "creature": "goblin",
+            "sprite_sheet": "goblin",
+            "animations": [
+                { "name": "walk_left", "frame_prefix": "goblin_walk_left_", "frame_count": 4, "fps": 8.0, "mode": "ping-pong" },
+                { "name": "idle_left", "frame_prefix": "goblin_idle_left_", "frame_count": 16, "fps": 5.0 }
+            ]
+        }"#).unwrap();
+
+        assert_eq!(definition.creature, "goblin");
+        assert_eq!(definition.animations[0].mode, Some(AnimationMode::PingPong));
+        assert_eq!(definition.animations[1].mode, None);
+    }
+
+    #[test]
+    fn test_build_creature_animations() {
+        let definition = CreatureAnimations {
+            creature: "goblin".to_string(),
+            sprite_sheet: String::new(),
+            animations: vec![AnimationDefinition {
+                name: "walk_right".to_string(),
+                frame_prefix: Some("goblin_walk_right_".to_string()),
+                frame_count: 4,
+                fps: Some(8.),
+                mode: Some(AnimationMode::PingPong),
+                ..Default::default()
+            }],
+        };
+
+        let animations = definition.build(&test_sprite_sheet("goblin_walk_right_", 4)).unwrap();
+        let walk_right = &animations["goblin_walk_right"];
+        assert_eq!(walk_right.frames.len(), 4);
+        assert_eq!(walk_right.frames[0].frame.x, 9);
+        assert_eq!(walk_right.frames[3].frame.x, 36);
+        assert_eq!(walk_right.animation, Animation::from_indices(0..4, FrameRate::from_fps(8.)).ping_pong());
+    }
+
+    #[test]
+    fn test_build_reports_missing_frame() {
+        let definition = AnimationDefinition {
+            name: "die".to_string(),
+            frame_prefix: Some("goblin_die_".to_string()),
+            frame_count: 6,
+            fps: Some(8.),
+            mode: Some(AnimationMode::Once),
+            ..Default::default()
+        };
+
+        match definition.build("goblin", &test_sprite_sheet("goblin_die_", 4)) {
+            Err(AnimationLoadError::MissingFrames { animation, frames }) => {
+                assert_eq!(animation, "goblin_die");
+                assert_eq!(frames, vec!["goblin_die_5.png", "goblin_die_6.png"]);
+            }
+            other => panic!("Expected a missing frame error, got {:?}", other.map(|a| a.frames.len())),
+        }
+    }
+
+    const ASEPRITE_ARRAY_SHEET: &str = r##"{
+        "frames": [
+            { "filename": "rogue 0.aseprite", "frame": { "x": 0, "y": 0, "w": 9, "h": 9 }, "rotated": false, "trimmed": false,
+              "spriteSourceSize": { "x": 0, "y": 0, "w": 9, "h": 9 }, "sourceSize": { "w": 9, "h": 9 }, "duration": 100 },
+            { "filename": "rogue 1.aseprite", "frame": { "x": 9, "y": 0, "w": 9, "h": 9 }, "rotated": false, "trimmed": false,
+              "spriteSourceSize": { "x": 0, "y": 0, "w": 9, "h": 9 }, "sourceSize": { "w": 9, "h": 9 }, "duration": 250 },
+            { "filename": "rogue 2.aseprite", "frame": { "x": 18, "y": 0, "w": 9, "h": 9 }, "rotated": false, "trimmed": false,
+              "spriteSourceSize": { "x": 0, "y": 0, "w": 9, "h": 9 }, "sourceSize": { "w": 9, "h": 9 }, "duration": 50 }
+        ],
+        "meta": {
+            "app": "https://www.aseprite.org/",
+            "frameTags": [
+                { "name": "walk_right", "from": 0, "to": 1, "direction": "forward", "color": "#000000ff" },
+                { "name": "die", "from": 1, "to": 2, "direction": "reverse", "repeat": "1", "color": "#000000ff" }
+            ]
+        }
+    }"##;
+
+    #[test]
+    fn test_deserialize_aseprite_array() {
+        let sprite_sheet = deserialize_json(ASEPRITE_ARRAY_SHEET).unwrap();
+
+        assert_eq!(sprite_sheet.frame_names, vec!["rogue 0.aseprite", "rogue 1.aseprite", "rogue 2.aseprite"]);
+        assert_eq!(sprite_sheet.frames["rogue 1.aseprite"].frame.x, 9);
+        assert_eq!(sprite_sheet.frames["rogue 1.aseprite"].duration, Some(250));
+        assert_eq!(sprite_sheet.frame_tags.len(), 2);
+        assert_eq!(sprite_sheet.frame_tags[1].direction, TagDirection::Reverse);
+        assert_eq!(sprite_sheet.frame_tags[1].repeat, Some(1));
+    }
+
+    #[test]
+    fn test_deserialize_aseprite_hash_keeps_frame_order() {
+        let sprite_sheet = deserialize_json(r#"{
+            "frames": {
+                "goblin 2.aseprite": { "frame": { "x": 18, "y": 0, "w": 9, "h": 9 }, "rotated": false, "trimmed": false,
+                    "spriteSourceSize": { "x": 0, "y": 0, "w": 9, "h": 9 }, "sourceSize": { "w": 9, "h": 9 }, "duration": 100 },
+                "goblin 0.aseprite": { "frame": { "x": 0, "y": 0, "w": 9, "h": 9 }, "rotated": false, "trimmed": false,
+                    "spriteSourceSize": { "x": 0, "y": 0, "w": 9, "h": 9 }, "sourceSize": { "w": 9, "h": 9 }, "duration": 100 }
+            },
+            "meta": { "frameTags": [ { "name": "idle", "from": 0, "to": 1, "direction": "pingpong" } ] }
+        }"#).unwrap();
+
+        assert_eq!(sprite_sheet.frame_names, vec!["goblin 2.aseprite", "goblin 0.aseprite"]);
+
+        let animations = CreatureAnimations { creature: "goblin".to_string(), sprite_sheet: String::new(), animations: Vec::new() }
+            .build(&sprite_sheet)
+            .unwrap();
+        let idle = &animations["goblin_idle"];
+        assert_eq!(idle.frames[0].frame.x, 18);
+        assert_eq!(idle.frames[1].frame.x, 0);
+        assert_eq!(idle.animation, Animation::from_indices(0..2, FrameRate::from_fps(10.)).ping_pong());
+    }
+
+    #[test]
+    fn test_build_animations_from_frame_tags() {
+        let sprite_sheet = deserialize_json(ASEPRITE_ARRAY_SHEET).unwrap();
+        let definition = CreatureAnimations { creature: "rogue".to_string(), sprite_sheet: String::new(), animations: Vec::new() };
+        let animations = definition.build(&sprite_sheet).unwrap();
+
+        // Each frame keeps its own duration from the export
+        let walk_right = &animations["rogue_walk_right"];
+        assert_eq!(walk_right.animation, Animation::from_frames(vec![
+            benimator::Frame::new(0, Duration::from_millis(100)),
+            benimator::Frame::new(1, Duration::from_millis(250)),
+        ]).repeat());
+
+        // Reverse tags play backwards, and a repeat count of one plays the animation once
+        let die = &animations["rogue_die"];
+        assert_eq!(die.frames[0].frame.x, 18);
+        assert_eq!(die.frames[1].frame.x, 9);
+        assert_eq!(die.animation, Animation::from_frames(vec![
+            benimator::Frame::new(0, Duration::from_millis(50)),
+            benimator::Frame::new(1, Duration::from_millis(250)),
+        ]).once());
+    }
+
+    #[test]
+    fn test_build_frame_tags_with_repeat_counts() {
+        let mut sprite_sheet = deserialize_json(ASEPRITE_ARRAY_SHEET).unwrap();
+        sprite_sheet.frame_tags = vec![
+            FrameTag { name: "attack".to_string(), from: 0, to: 1, direction: TagDirection::Forward, repeat: Some(3) },
+            FrameTag { name: "bounce".to_string(), from: 0, to: 2, direction: TagDirection::Pingpong, repeat: Some(1) },
+            FrameTag { name: "idle".to_string(), from: 0, to: 1, direction: TagDirection::Pingpong, repeat: Some(0) },
+        ];
+        let definition = CreatureAnimations { creature: "rogue".to_string(), sprite_sheet: String::new(), animations: Vec::new() };
+        let animations = definition.build(&sprite_sheet).unwrap();
+        let timed = |indices: &[usize]| {
+            let durations = [100, 250, 50];
+            Animation::from_frames(indices.iter().map(|&index| benimator::Frame::new(index, Duration::from_millis(durations[index]))))
+        };
+
+        // A forward tag plays its frames the given number of times, and a ping pong goes there and back, then stops
+        assert_eq!(animations["rogue_attack"].animation, timed(&[0, 1, 0, 1, 0, 1]).once());
+        assert_eq!(animations["rogue_bounce"].animation, timed(&[0, 1, 2, 1, 0]).once());
+        // A repeat count of zero loops forever
+        assert_eq!(animations["rogue_idle"].animation, timed(&[0, 1]).ping_pong());
+
+        sprite_sheet.frame_tags[0].repeat = Some(MAX_REPEAT + 1);
+        assert!(matches!(definition.build(&sprite_sheet), Err(AnimationLoadError::InvalidDefinition { .. })));
+    }
+
+    #[test]
+    fn test_definition_overrides_frame_tag() {
+        let sprite_sheet = deserialize_json(ASEPRITE_ARRAY_SHEET).unwrap();
+        let definition = CreatureAnimations {
+            creature: "rogue".to_string(),
+            sprite_sheet: String::new(),
+            animations: vec![AnimationDefinition {
+                name: "walk_left".to_string(),
+                tag: Some("walk_right".to_string()),
+                fps: Some(4.),
+                mode: Some(AnimationMode::PingPong),
+                ..Default::default()
+            }],
+        };
+        let animations = definition.build(&sprite_sheet).unwrap();
+
+        assert!(animations.contains_key("rogue_walk_right"));
+        assert_eq!(animations["rogue_walk_left"].animation, Animation::from_indices(0..2, FrameRate::from_fps(4.)).ping_pong());
+    }
+
+    #[test]
+    fn test_build_requires_fps_or_durations() {
+        let definition = AnimationDefinition {
+            name: "idle_right".to_string(),
+            frame_prefix: Some("rogue_idle_right_".to_string()),
+            frame_count: 2,
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            definition.build("rogue", &test_sprite_sheet("rogue_idle_right_", 2)),
+            Err(AnimationLoadError::InvalidDefinition { .. })
+        ));
+    }
+
+    #[test]
+    fn test_build_attaches_frame_events() {
+        let mut definition = AnimationDefinition {
+            name: "attack_right".to_string(),
+            frame_prefix: Some("goblin_attack_right_".to_string()),
+            frame_count: 4,
+            fps: Some(8.),
+            events: vec![FrameEvent { frame: 2, name: "hit" }],
+            ..Default::default()
+        };
+        let sprite_sheet = test_sprite_sheet("goblin_attack_right_", 4);
+
+        let animation = definition.build("goblin", &sprite_sheet).unwrap();
+        assert_eq!(animation.events_on_frame(2).collect::<Vec<_>>(), vec!["hit"]);
+        assert_eq!(animation.events_on_frame(1).count(), 0);
+
+        // Events past the last frame would never fire
+        definition.events[0].frame = 4;
+        assert!(matches!(definition.build("goblin", &sprite_sheet), Err(AnimationLoadError::InvalidDefinition { .. })));
+    }
+
+    #[test]
+    fn test_animation_map_reuses_ids_for_replaced_labels() {
+        let mut animation_map = AnimationMap::default();
+        let walk = AnimationDefinition {
+            name: "walk_right".to_string(),
+            frame_prefix: Some("goblin_walk_right_".to_string()),
+            frame_count: 2,
+            fps: Some(8.),
+            ..Default::default()
+        };
+        let sprite_sheet = test_sprite_sheet("goblin_walk_right_", 4);
+
+        let id = animation_map.insert("goblin_walk_right".to_string(), walk.build("goblin", &sprite_sheet).unwrap());
+        assert_eq!(animation_map.id("goblin_walk_right"), Some(id));
+        assert_eq!(animation_map.label(id), "goblin_walk_right");
+        assert_eq!(animation_map.get(id).frames.len(), 2);
+
+        let longer_walk = AnimationDefinition { frame_count: 4, ..walk };
+        let replaced = animation_map.insert("goblin_walk_right".to_string(), longer_walk.build("goblin", &sprite_sheet).unwrap());
+        assert_eq!(replaced, id);
+        assert_eq!(animation_map.len(), 1);
+        assert_eq!(animation_map.get(id).frames.len(), 4);
+    }
+
+    #[test]
+    fn test_animation_map_remove_keeps_other_ids() {
+        let sprite_sheet = test_sprite_sheet("goblin_walk_right_", 2);
+        let walk = AnimationDefinition {
+            name: "walk_right".to_string(),
+            frame_prefix: Some("goblin_walk_right_".to_string()),
+            frame_count: 2,
+            fps: Some(8.),
+            ..Default::default()
+        };
+        let mut animation_map = AnimationMap::default();
+        let walk_id = animation_map.insert("goblin_walk_right".to_string(), walk.build("goblin", &sprite_sheet).unwrap());
+        let idle_id = animation_map.insert("goblin_idle_right".to_string(), walk.build("goblin", &sprite_sheet).unwrap());
+
+        assert_eq!(animation_map.remove("goblin_walk_right"), Some(walk_id));
+        assert_eq!(animation_map.remove("goblin_walk_right"), None);
+        assert_eq!(animation_map.id("goblin_walk_right"), None);
+        assert_eq!(animation_map.id("goblin_idle_right"), Some(idle_id));
+        assert_eq!(animation_map.labels().collect::<Vec<_>>(), vec!["goblin_idle_right"]);
+        assert_eq!(animation_map.len(), 1);
+
+        // Adding the label back doesn't reuse the removed id's slot for something else
+        let readded = animation_map.insert("goblin_walk_right".to_string(), walk.build("goblin", &sprite_sheet).unwrap());
+        assert_ne!(readded, idle_id);
+        assert_eq!(animation_map.label(readded), "goblin_walk_right");
+    }
+}
@@ -0,0 +1,109 @@
+use std::fmt;
+use serde::de::{MapAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use crate::animations::animation::{LiegeSprite, SpriteSheet};
+
+// Aseprite tag playback direction, as written to `meta.frameTags[].direction`
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TagDirection {
+    #[default]
+    Forward,
+    Reverse,
+    Pingpong,
+    PingpongReverse,
+}
+
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+pub struct FrameTag {
+    pub name: String,
+    pub from: usize,
+    pub to: usize,
+    #[serde(default)]
+    pub direction: TagDirection,
+    // Aseprite writes the repeat count as a string ("1" means play once), and omits it for infinite loops
+    #[serde(default, deserialize_with = "deserialize_repeat")]
+    pub repeat: Option<u32>,
+}
+
+// Sprite sheet JSON as exported by Aseprite or TexturePacker. `frames` may be a hash keyed by frame name
+// (TexturePacker, Aseprite "Hash") or an array of frames carrying a `filename` (Aseprite "Array").
+#[derive(Deserialize)]
+pub(crate) struct RawSpriteSheet {
+    frames: RawFrames,
+    #[serde(default)]
+    meta: RawMeta,
+}
+
+#[derive(Deserialize, Default)]
+struct RawMeta {
+    #[serde(default, rename = "frameTags")]
+    frame_tags: Vec<FrameTag>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawFrames {
+    Array(Vec<NamedSprite>),
+    Hash(OrderedSprites),
+}
+
+#[derive(Deserialize)]
+struct NamedSprite {
+    filename: String,
+    #[serde(flatten)]
+    sprite: LiegeSprite,
+}
+
+// Frame tags refer to frames by position, so the hash format has to keep the order the frames appear in the file
+struct OrderedSprites(Vec<(String, LiegeSprite)>);
+
+impl<'de> Deserialize<'de> for OrderedSprites {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct OrderedSpritesVisitor;
+
+        impl<'de> Visitor<'de> for OrderedSpritesVisitor {
+            type Value = OrderedSprites;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a map of frame names to sprites")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut sprites = Vec::with_capacity(map.size_hint().unwrap_or(0));
+                while let Some(entry) = map.next_entry::<String, LiegeSprite>()? {
+                    sprites.push(entry);
+                }
+                Ok(OrderedSprites(sprites))
+            }
+        }
+
+        deserializer.deserialize_map(OrderedSpritesVisitor)
+    }
+}
+
+impl From<RawSpriteSheet> for SpriteSheet {
+    fn from(raw: RawSpriteSheet) -> Self {
+        let frames = match raw.frames {
+            RawFrames::Array(frames) => frames.into_iter().map(|f| (f.filename, f.sprite)).collect(),
+            RawFrames::Hash(OrderedSprites(frames)) => frames,
+        };
+
+        SpriteSheet::new(frames, raw.meta.frame_tags)
+    }
+}
+
+fn deserialize_repeat<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u32>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Repeat {
+        Number(u32),
+        Text(String),
+    }
+
+    match Option::<Repeat>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(Repeat::Number(count)) => Ok(Some(count)),
+        Some(Repeat::Text(text)) => text.parse().map(Some).map_err(serde::de::Error::custom),
+    }
+}
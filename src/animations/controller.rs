@@ -0,0 +1,327 @@
+use std::f32::consts::FRAC_PI_4;
+use macroquad::math::Vec2;
+use crate::animations::animation::{AnimationId, AnimationMap, AnimationStates};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Facing {
+    Right,
+    DownRight,
+    Down,
+    DownLeft,
+    Left,
+    UpLeft,
+    Up,
+    UpRight,
+}
+
+impl Facing {
+    pub const ALL: [Facing; 8] = [
+        Facing::Right, Facing::DownRight, Facing::Down, Facing::DownLeft,
+        Facing::Left, Facing::UpLeft, Facing::Up, Facing::UpRight,
+    ];
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Facing::Right => "_right",
+            Facing::DownRight => "_down_right",
+            Facing::Down => "_down",
+            Facing::DownLeft => "_down_left",
+            Facing::Left => "_left",
+            Facing::UpLeft => "_up_left",
+            Facing::Up => "_up",
+            Facing::UpRight => "_up_right",
+        }
+    }
+
+    // Pick the nearest of the eight facings for a direction of travel (y grows downwards). Standing still has no
+    // facing, so the entity keeps whichever way it was facing before.
+    pub fn from_direction(direction: Vec2) -> Option<Facing> {
+        if direction.length_squared() <= f32::EPSILON {
+            return None;
+        }
+
+        let octant = (direction.y.atan2(direction.x) / FRAC_PI_4).round() as i32;
+        Some(match octant.rem_euclid(8) {
+            0 => Facing::Right,
+            1 => Facing::DownRight,
+            2 => Facing::Down,
+            3 => Facing::DownLeft,
+            4 => Facing::Left,
+            5 => Facing::UpLeft,
+            6 => Facing::Up,
+            _ => Facing::UpRight,
+        })
+    }
+
+    // The same facing reflected left to right
+    pub fn mirrored(self) -> Facing {
+        match self {
+            Facing::Right => Facing::Left,
+            Facing::DownRight => Facing::DownLeft,
+            Facing::DownLeft => Facing::DownRight,
+            Facing::Left => Facing::Right,
+            Facing::UpLeft => Facing::UpRight,
+            Facing::UpRight => Facing::UpLeft,
+            Facing::Down => Facing::Down,
+            Facing::Up => Facing::Up,
+        }
+    }
+
+    // Facings to try, best first, when a creature has no art for this one. Diagonals fall back to the side they
+    // lean towards, and straight up or down fall back to the diagonal and then the plain right art.
+    fn fallbacks(self) -> &'static [Facing] {
+        match self {
+            Facing::Right => &[Facing::Right],
+            Facing::DownRight => &[Facing::DownRight, Facing::Right, Facing::Down],
+            Facing::Down => &[Facing::Down, Facing::DownRight, Facing::Right],
+            Facing::DownLeft => &[Facing::DownLeft, Facing::Left, Facing::Down],
+            Facing::Left => &[Facing::Left],
+            Facing::UpLeft => &[Facing::UpLeft, Facing::Left, Facing::Up],
+            Facing::Up => &[Facing::Up, Facing::UpRight, Facing::Right],
+            Facing::UpRight => &[Facing::UpRight, Facing::Right, Facing::Up],
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OnAnimationEnd {
+    // Looping animations never end
+    Repeat,
+    // Stay on the last frame
+    Hold,
+    // Go back to whatever state was playing (or requested) before this one
+    ReturnToPrevious,
+}
+
+// How a state behaves in the controller. A state that is not interruptible plays out until it ends, unless a
+// state with a higher priority is requested.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StateRule {
+    pub priority: u8,
+    pub interruptible: bool,
+    pub on_end: OnAnimationEnd,
+}
+
+impl AnimationStates {
+    pub fn rule(&self) -> StateRule {
+        match self {
+            AnimationStates::Idle => StateRule { priority: 0, interruptible: true, on_end: OnAnimationEnd::Repeat },
+            AnimationStates::Walk => StateRule { priority: 1, interruptible: true, on_end: OnAnimationEnd::Repeat },
+            AnimationStates::Attack => StateRule { priority: 2, interruptible: false, on_end: OnAnimationEnd::ReturnToPrevious },
+            AnimationStates::Die => StateRule { priority: 3, interruptible: false, on_end: OnAnimationEnd::Hold },
+        }
+    }
+}
+
+// Decides which animation an entity plays. Systems only say what they want (walk, attack, die...), and the
+// controller applies the transition rules. The facing is kept in step with the entity's FacingComponent.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AnimationController {
+    state: AnimationStates,
+    // The state to go back to when a ReturnToPrevious state ends
+    previous: AnimationStates,
+    facing: Facing,
+    // Set whenever the state or facing changes, so the animation only has to be looked up again when needed
+    changed: bool,
+}
+
+impl AnimationController {
+    pub fn new(state: AnimationStates, facing: Facing) -> Self {
+        Self { state, previous: state, facing, changed: true }
+    }
+
+    pub fn state(&self) -> AnimationStates {
+        self.state
+    }
+
+    // Turning would restart the animation, so a state that can't be interrupted keeps the facing it started with until
+    // it's done
+    pub fn face(&mut self, facing: Facing) {
+        if !self.state.rule().interruptible {
+            return;
+        }
+        if self.facing != facing {
+            self.facing = facing;
+            self.changed = true;
+        }
+    }
+
+    pub fn request(&mut self, requested: AnimationStates) {
+        if requested == self.state {
+            return;
+        }
+
+        let current = self.state.rule();
+        if !current.interruptible && requested.rule().priority <= current.priority {
+            // Can't switch yet, but remember the latest request so that we pick it up once this state is done
+            if current.on_end == OnAnimationEnd::ReturnToPrevious {
+                self.previous = requested;
+            }
+            return;
+        }
+
+        if requested.rule().on_end == OnAnimationEnd::ReturnToPrevious {
+            self.previous = self.state;
+        }
+        self.state = requested;
+        self.changed = true;
+    }
+
+    // Called when the current animation plays out its last frame
+    pub fn animation_ended(&mut self) {
+        if self.state.rule().on_end == OnAnimationEnd::ReturnToPrevious {
+            self.state = self.previous;
+            self.changed = true;
+        }
+    }
+
+    // Look the animation up again even though nothing changed, e.g. after the animations have been reloaded
+    pub fn mark_changed(&mut self) {
+        self.changed = true;
+    }
+
+    // Whether the state or facing changed since the last call
+    pub fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+
+    // Find the animation to play for the current state and facing, and whether it has to be mirrored. When the
+    // creature has no art for the exact facing, the nearest facing is used, or the opposite side drawn flipped.
+    pub fn resolve_animation(&self, animation_map: &AnimationMap, creature_type: &str) -> Option<(AnimationId, bool)> {
+        let base = self.state.get_creature_animation(creature_type);
+        for facing in self.facing.fallbacks() {
+            if let Some(id) = animation_map.id(&format!("{}{}", base, facing.to_str())) {
+                return Some((id, false));
+            }
+            if facing.mirrored() != *facing {
+                if let Some(id) = animation_map.id(&format!("{}{}", base, facing.mirrored().to_str())) {
+                    return Some((id, true));
+                }
+            }
+        }
+
+        // Some states, like dying, look the same whichever way the creature faces
+        animation_map.id(&base).map(|id| (id, false))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::Duration;
+    use benimator::{Animation, FrameRate};
+    use crate::animations::animation::LiegeAnimation;
+    use super::*;
+
+    fn animation_map(labels: &[&str]) -> AnimationMap {
+        let animation = LiegeAnimation {
+            frames: Vec::new(),
+            animation: Animation::from_indices(0..1, FrameRate::from_fps(1.)),
+            events: Vec::new(),
+            shortest_frame: Duration::from_secs(1),
+        };
+        AnimationMap::new(labels.iter().map(|label| (label.to_string(), animation.clone())).collect::<HashMap<_, _>>())
+    }
+
+    fn resolve<'a>(animation_map: &'a AnimationMap, controller: &AnimationController) -> Option<(&'a str, bool)> {
+        controller
+            .resolve_animation(animation_map, "rogue")
+            .map(|(id, flip_x)| (animation_map.label(id), flip_x))
+    }
+
+    #[test]
+    fn test_walk_returns_to_idle_keeping_facing() {
+        let animation_map = animation_map(&["rogue_idle_right", "rogue_idle_left", "rogue_walk_up_left"]);
+        let mut controller = AnimationController::new(AnimationStates::Idle, Facing::Right);
+        controller.request(AnimationStates::Walk);
+        controller.face(Facing::UpLeft);
+        assert_eq!(resolve(&animation_map, &controller), Some(("rogue_walk_up_left", false)));
+
+        controller.request(AnimationStates::Idle);
+        assert_eq!(controller.state(), AnimationStates::Idle);
+        // There is no idle art facing up, so the nearest side is used
+        assert_eq!(resolve(&animation_map, &controller), Some(("rogue_idle_left", false)));
+    }
+
+    #[test]
+    fn test_missing_left_art_is_mirrored() {
+        let animation_map = animation_map(&["rogue_walk_right", "rogue_walk_up_right", "rogue_walk_down"]);
+        let mut controller = AnimationController::new(AnimationStates::Walk, Facing::Left);
+        assert_eq!(resolve(&animation_map, &controller), Some(("rogue_walk_right", true)));
+
+        controller.face(Facing::UpLeft);
+        assert_eq!(resolve(&animation_map, &controller), Some(("rogue_walk_up_right", true)));
+
+        // A left diagonal prefers mirrored side art over the straight down art
+        controller.face(Facing::DownLeft);
+        assert_eq!(resolve(&animation_map, &controller), Some(("rogue_walk_right", true)));
+
+        controller.face(Facing::Down);
+        assert_eq!(resolve(&animation_map, &controller), Some(("rogue_walk_down", false)));
+
+        // Straight up has no art of its own, so falls back to the nearest diagonal
+        controller.face(Facing::Up);
+        assert_eq!(resolve(&animation_map, &controller), Some(("rogue_walk_up_right", false)));
+    }
+
+    #[test]
+    fn test_state_without_facing() {
+        let animation_map = animation_map(&["rogue_die"]);
+        let controller = AnimationController::new(AnimationStates::Die, Facing::UpLeft);
+        assert_eq!(resolve(&animation_map, &controller), Some(("rogue_die", false)));
+
+        let controller = AnimationController::new(AnimationStates::Walk, Facing::UpLeft);
+        assert_eq!(resolve(&animation_map, &controller), None);
+    }
+
+    #[test]
+    fn test_attack_is_not_interrupted_and_returns_to_previous() {
+        let mut controller = AnimationController::new(AnimationStates::Walk, Facing::Right);
+        controller.request(AnimationStates::Attack);
+        assert_eq!(controller.state(), AnimationStates::Attack);
+
+        // Movement keeps asking for idle during the attack, which has to wait until the attack is over
+        controller.take_changed();
+        controller.request(AnimationStates::Idle);
+        assert_eq!(controller.state(), AnimationStates::Attack);
+        assert!(!controller.take_changed());
+
+        controller.animation_ended();
+        assert!(controller.take_changed());
+        assert_eq!(controller.state(), AnimationStates::Idle);
+    }
+
+    #[test]
+    fn test_attack_returns_to_state_before_it_when_nothing_else_was_requested() {
+        let mut controller = AnimationController::new(AnimationStates::Walk, Facing::Right);
+        controller.request(AnimationStates::Attack);
+        controller.animation_ended();
+        assert_eq!(controller.state(), AnimationStates::Walk);
+    }
+
+    #[test]
+    fn test_die_interrupts_everything_and_is_final() {
+        let mut controller = AnimationController::new(AnimationStates::Walk, Facing::Left);
+        controller.request(AnimationStates::Attack);
+        controller.request(AnimationStates::Die);
+        assert_eq!(controller.state(), AnimationStates::Die);
+
+        controller.request(AnimationStates::Walk);
+        controller.request(AnimationStates::Attack);
+        controller.animation_ended();
+        assert_eq!(controller.state(), AnimationStates::Die);
+    }
+
+    #[test]
+    fn test_facing_from_direction() {
+        assert_eq!(Facing::from_direction(Vec2::new(1., 0.1)), Some(Facing::Right));
+        assert_eq!(Facing::from_direction(Vec2::new(1., 1.)), Some(Facing::DownRight));
+        assert_eq!(Facing::from_direction(Vec2::new(0.1, 1.)), Some(Facing::Down));
+        assert_eq!(Facing::from_direction(Vec2::new(-1., 0.9)), Some(Facing::DownLeft));
+        assert_eq!(Facing::from_direction(Vec2::new(-1., -0.1)), Some(Facing::Left));
+        assert_eq!(Facing::from_direction(Vec2::new(-0.5, -0.6)), Some(Facing::UpLeft));
+        assert_eq!(Facing::from_direction(Vec2::new(0., -1.)), Some(Facing::Up));
+        assert_eq!(Facing::from_direction(Vec2::new(0.7, -0.7)), Some(Facing::UpRight));
+        assert_eq!(Facing::from_direction(Vec2::ZERO), None);
+    }
+}
@@ -1,47 +1,29 @@
 use std::collections::HashMap;
-use benimator::{Animation, FrameRate, State};
+use benimator::State;
 use macroquad::math::Vec2;
-use crate::animations::animation::{LiegeAnimation, LiegeUIAnimation};
-
-pub fn load_cursor_animations<'a>() -> HashMap<String, LiegeUIAnimation<'a>> {
-    // Load and deserialize our spritesheet information
-    let file_content = match crate::animations::animation::read_json_file("resources/ui/cursor/cursor.json") {
-        Ok(content) => content,
-        Err(e) => panic!("Failed to read file: {}", e),
-    };
-
-    let sprite_sheet = match crate::animations::animation::deserialize_json(&file_content) {
-        Ok(content) => content,
-        Err(e) => panic!("Failed to deserialize: {}", e)
-    };
+use crate::animations::animation::{AnimationLoadError, AnimationMap, LiegeUIAnimation};
+use crate::assets::TextureId;
 
+// The cursor's animations are defined in resources/ui/cursor/cursor_animations.json like any creature's, and are
+// copied out of the animation map here so each click can play its own instance
+pub fn load_cursor_animations(animation_map: &AnimationMap, texture: TextureId) -> Result<HashMap<String, LiegeUIAnimation>, AnimationLoadError> {
     let mut animations_map: HashMap<String, LiegeUIAnimation> = HashMap::new();
 
-    let idle = LiegeUIAnimation{
-        frames: vec![
-            sprite_sheet.frames["cursor_default.png"],
-        ],
-        animation: Animation::from_indices(0..0, FrameRate::from_fps(5.)),
-        texture_handle: "resources/ui/cursor/cursor.png",
-        position: Vec2::ZERO,
-        state: State::new(),
-    };
-
-    let click = LiegeUIAnimation{
-        frames: vec![
-            sprite_sheet.frames["cursor_click_default_1.png"],
-            sprite_sheet.frames["cursor_click_default_2.png"],
-            sprite_sheet.frames["cursor_click_default_3.png"],
-            sprite_sheet.frames["cursor_click_default_4.png"],
-        ],
-        animation: Animation::from_indices(0..3, FrameRate::from_fps(12.)).once(),
-        texture_handle: "resources/ui/cursor/cursor.png",
-        position: Vec2::ZERO,
-        state: State::new(),
-    };
+    for label in ["cursor_idle", "cursor_click"] {
+        let id = animation_map.id(label).ok_or_else(|| AnimationLoadError::InvalidDefinition {
+            animation: label.to_string(),
+            reason: "no animation with this name has been loaded".to_string(),
+        })?;
+        let liege_animation = animation_map.get(id);
 
-    animations_map.insert("cursor_click".to_string(), click);
-    animations_map.insert("cursor_idle".to_string(), idle);
+        animations_map.insert(label.to_string(), LiegeUIAnimation{
+            frames: liege_animation.frames.clone(),
+            animation: liege_animation.animation.clone(),
+            texture,
+            position: Vec2::ZERO,
+            state: State::new(),
+        });
+    }
 
-    animations_map
-}
\ No newline at end of file
+    Ok(animations_map)
+}
@@ -1,4 +1,4 @@
 pub mod animation;
-pub(crate) mod rogue;
-pub(crate) mod goblin;
-pub(crate) mod cursor;
+pub mod aseprite;
+pub mod controller;
+pub mod cursor;
@@ -0,0 +1,389 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use macroquad::prelude::{FilterMode, Image, Rect, Texture2D};
+use serde::{Deserialize, Serialize};
+use crate::animations::animation::{load_creature_definition, load_sprite_sheet, AnimationLoadError, CreatureAnimations, LiegeAnimation, SpriteSheet};
+use crate::atlas::{pack_atlas, remap_sprite_sheet, TextureAtlas, ATLAS_PAGE_SIZE};
+use crate::placeholder::{placeholder_colour, placeholder_edge_image, placeholder_sprite_image, placeholder_sprite_sheet, placeholder_tile_image, EDGE_TILESET_SUFFIX};
+
+pub const ASSET_MANIFEST_PATH: &str = "resources/manifest.json";
+
+// Every asset the game loads, keyed by a logical id. The rest of the game only refers to assets by id, so files can
+// be moved or renamed by editing the manifest.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Default)]
+pub struct AssetManifest {
+    // Texture id to image path
+    #[serde(default)]
+    pub textures: BTreeMap<String, String>,
+    // Sprite sheet id to sprite sheet JSON path
+    #[serde(default)]
+    pub sprite_sheets: BTreeMap<String, String>,
+    // Creature id to animation definition path. Definitions name their sprite sheet by id.
+    #[serde(default)]
+    pub animations: BTreeMap<String, String>,
+}
+
+#[derive(Debug)]
+pub enum AssetError {
+    Manifest { source: AnimationLoadError },
+    Texture { id: String, path: String, reason: String },
+    SpriteSheet { id: String, source: AnimationLoadError },
+    Animations { id: String, source: AnimationLoadError },
+    UnknownSpriteSheet { animations: String, sprite_sheet: String },
+}
+
+impl fmt::Display for AssetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssetError::Manifest { source } => write!(f, "manifest: {}", source),
+            AssetError::Texture { id, path, reason } => write!(f, "texture '{}': failed to load {}: {}", id, path, reason),
+            AssetError::SpriteSheet { id, source } => write!(f, "sprite sheet '{}': {}", id, source),
+            AssetError::Animations { id, source } => write!(f, "animations '{}': {}", id, source),
+            AssetError::UnknownSpriteSheet { animations, sprite_sheet } => {
+                write!(f, "animations '{}': sprite sheet '{}' is not in the manifest", animations, sprite_sheet)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AssetError {}
+
+// Everything that went wrong while loading, so that all broken assets can be fixed in one go
+#[derive(Debug)]
+pub struct AssetReport {
+    pub errors: Vec<AssetError>,
+}
+
+impl fmt::Display for AssetReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} asset(s) failed to load:", self.errors.len())?;
+        for error in &self.errors {
+            write!(f, "\n  - {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for AssetReport {}
+
+// Assets read from disk, before anything is uploaded to the GPU. Every texture is packed into the atlas, and the
+// sprite sheets' frames are already moved to where their texture ended up in it.
+pub struct LoadedAssets {
+    pub atlas: TextureAtlas,
+    pub sprite_sheets: HashMap<String, SpriteSheet>,
+    pub animations: HashMap<String, LiegeAnimation>,
+    // Assets whose files don't exist, and were replaced with generated placeholders
+    pub placeholders: Vec<String>,
+}
+
+pub fn load_manifest(file_path: &str) -> Result<AssetManifest, AssetReport> {
+    let report = |source| AssetReport { errors: vec![AssetError::Manifest { source }] };
+    let file_content = fs::read_to_string(file_path)
+        .map_err(|source| report(AnimationLoadError::Io { path: file_path.to_string(), source }))?;
+    serde_json::from_str(&file_content)
+        .map_err(|source| report(AnimationLoadError::Parse { path: file_path.to_string(), source }))
+}
+
+pub fn load_image(file_path: &str) -> Result<Image, String> {
+    let bytes = fs::read(file_path).map_err(|e| e.to_string())?;
+    Image::from_file_with_format(&bytes, None).map_err(|e| e.to_string())
+}
+
+// Load every asset in the manifest. Rather than stopping at the first problem, every asset is tried, and all of
+// the failures are returned together. Textures and sprite sheets that don't exist at all are replaced with
+// placeholders rather than treated as errors, as the art isn't distributed with the game.
+pub fn load_assets(manifest: &AssetManifest) -> Result<LoadedAssets, AssetReport> {
+    let (assets, errors) = load_available_assets(manifest);
+    if errors.is_empty() {
+        Ok(assets)
+    } else {
+        Err(AssetReport { errors })
+    }
+}
+
+// Like load_assets, but keeps whatever did load alongside the failures, e.g. to check it over anyway
+pub fn load_available_assets(manifest: &AssetManifest) -> (LoadedAssets, Vec<AssetError>) {
+    let mut errors = Vec::new();
+    let mut placeholders = Vec::new();
+
+    // Animation definitions come first, so that placeholder sprite sheets know which frames they need
+    let mut definitions: Vec<(&String, CreatureAnimations)> = Vec::new();
+    for (id, path) in &manifest.animations {
+        match load_creature_definition(path) {
+            Ok(definition) => definitions.push((id, definition)),
+            Err(source) => errors.push(AssetError::Animations { id: id.clone(), source }),
+        }
+    }
+
+    let mut sprite_sheets = HashMap::new();
+    for (id, path) in &manifest.sprite_sheets {
+        if !Path::new(path).exists() {
+            let users: Vec<_> = definitions.iter().map(|(_, definition)| definition).filter(|definition| &definition.sprite_sheet == id).collect();
+            sprite_sheets.insert(id.clone(), placeholder_sprite_sheet(&users));
+            placeholders.push(format!("sprite sheet '{}' ({})", id, path));
+            continue;
+        }
+
+        match load_sprite_sheet(path) {
+            Ok(sprite_sheet) => {
+                sprite_sheets.insert(id.clone(), sprite_sheet);
+            }
+            Err(source) => errors.push(AssetError::SpriteSheet { id: id.clone(), source }),
+        }
+    }
+
+    // Sprite sheet textures get a box for each frame in the sheet with the same id. Edge tile sets get edges in the
+    // colour of the tile set they're the edges of. Anything else is a tile set.
+    let mut images = BTreeMap::new();
+    for (id, path) in &manifest.textures {
+        if !Path::new(path).exists() {
+            let image = match sprite_sheets.get(id) {
+                Some(sprite_sheet) => placeholder_sprite_image(sprite_sheet, placeholder_colour(id)),
+                None => match id.strip_suffix(EDGE_TILESET_SUFFIX) {
+                    Some(tile_set) => placeholder_edge_image(placeholder_colour(tile_set)),
+                    None => placeholder_tile_image(placeholder_colour(id)),
+                },
+            };
+            images.insert(id.clone(), image);
+            placeholders.push(format!("texture '{}' ({})", id, path));
+            continue;
+        }
+
+        match load_image(path) {
+            Ok(image) => {
+                images.insert(id.clone(), image);
+            }
+            Err(reason) => errors.push(AssetError::Texture { id: id.clone(), path: path.clone(), reason }),
+        }
+    }
+
+    // A sprite sheet's frames are in the texture with the same id
+    let atlas = pack_atlas(&images, ATLAS_PAGE_SIZE);
+    for (id, sprite_sheet) in sprite_sheets.iter_mut() {
+        if let Some(placement) = atlas.placements.get(id) {
+            remap_sprite_sheet(sprite_sheet, placement);
+        }
+    }
+
+    let mut animations = HashMap::new();
+    for (id, definition) in definitions {
+        match sprite_sheets.get(&definition.sprite_sheet) {
+            Some(sprite_sheet) => {
+                let (built, failures) = definition.build_each(sprite_sheet);
+                animations.extend(built);
+                errors.extend(failures.into_iter().map(|source| AssetError::Animations { id: id.clone(), source }));
+            }
+            // A sheet that is listed but failed to load has already been reported
+            None if manifest.sprite_sheets.contains_key(&definition.sprite_sheet) => {}
+            None => errors.push(AssetError::UnknownSpriteSheet { animations: id.clone(), sprite_sheet: definition.sprite_sheet }),
+        }
+    }
+
+    (LoadedAssets { atlas, sprite_sheets, animations, placeholders }, errors)
+}
+
+// A cheap, copyable handle to a texture stored in the TextureMap
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TextureId(usize);
+
+// For building components in tests, without any textures loaded
+#[cfg(test)]
+impl TextureId {
+    pub(crate) fn new(index: usize) -> Self {
+        TextureId(index)
+    }
+}
+
+// The atlas pages on the GPU, and where each texture is on them. Textures sharing a page share a Texture2D, so
+// sprites from different sheets can be drawn in the same batch.
+#[derive(Default)]
+pub struct TextureMap {
+    pages: Vec<Texture2D>,
+    textures: Vec<(usize, Rect)>,
+    ids: HashMap<String, TextureId>,
+}
+
+impl TextureMap {
+    // Upload the atlas to the GPU. Needs a macroquad context, so can only be called once the game is running.
+    pub fn from_atlas(atlas: &TextureAtlas) -> Self {
+        let mut texture_map = Self::default();
+        texture_map.replace_atlas(atlas);
+        texture_map
+    }
+
+    // Swap in a newly packed atlas. Textures that were already loaded keep their ids, so anything pointing at them
+    // draws the new version.
+    pub fn replace_atlas(&mut self, atlas: &TextureAtlas) {
+        self.pages = atlas.pages.iter()
+            .map(|page| {
+                let texture = Texture2D::from_image(page);
+                texture.set_filter(FilterMode::Nearest);
+                texture
+            })
+            .collect();
+
+        // Textures that have gone from the manifest draw nothing, rather than whatever is now where they used to be
+        for (id, texture_id) in &self.ids {
+            if !atlas.placements.contains_key(id) {
+                self.textures[texture_id.0] = (0, Rect::new(0., 0., 0., 0.));
+            }
+        }
+
+        let placements: BTreeMap<_, _> = atlas.placements.iter().collect();
+        for (id, placement) in placements {
+            match self.ids.get(id) {
+                Some(texture_id) => self.textures[texture_id.0] = (placement.page, placement.rect()),
+                None => {
+                    let texture_id = TextureId(self.textures.len());
+                    self.textures.push((placement.page, placement.rect()));
+                    self.ids.insert(id.clone(), texture_id);
+                }
+            }
+        }
+    }
+
+    pub fn id(&self, id: &str) -> Option<TextureId> {
+        self.ids.get(id).copied()
+    }
+
+    // The atlas page the texture is on. Sprite frames are already in page coordinates.
+    pub fn get(&self, id: TextureId) -> &Texture2D {
+        &self.pages[self.textures[id.0].0]
+    }
+
+    // Where the texture is on its page, for drawing parts of textures that have no sprite sheet (e.g. tiles)
+    pub fn region(&self, id: TextureId) -> Rect {
+        self.textures[id.0].1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use macroquad::color::RED;
+    use crate::map::{Biome, Feature};
+    use crate::resource_nodes::ResourceKind;
+    use super::*;
+
+    fn write(dir: &Path, name: &str, content: &str) -> String {
+        let path = dir.join(name);
+        fs::write(&path, content).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    const SPRITE_SHEET: &str = r#"{"frames": {"rogue_idle_right_1.png": {
+        "frame": {"x": 0, "y": 0, "w": 9, "h": 9}, "rotated": false, "trimmed": false,
+        "spriteSourceSize": {"x": 0, "y": 0, "w": 9, "h": 9}, "sourceSize": {"w": 9, "h": 9}}}}"#;
+
+    #[test]
+    fn test_load_assets() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let image_path = dir.join("rogue.png").to_string_lossy().into_owned();
+        Image::gen_image_color(4, 2, RED).export_png(&image_path);
+        let sheet_path = write(dir, "rogue.json", SPRITE_SHEET);
+        let definition_path = write(dir, "rogue_animations.json", r#"{"creature": "rogue", "sprite_sheet": "rogue_sheet",
+            "animations": [{"name": "idle_right", "frame_prefix": "rogue_idle_right_", "frame_count": 1, "fps": 5.0}]}"#);
+
+        let manifest = AssetManifest {
+            textures: BTreeMap::from([("rogue".to_string(), image_path)]),
+            sprite_sheets: BTreeMap::from([("rogue_sheet".to_string(), sheet_path)]),
+            animations: BTreeMap::from([("rogue".to_string(), definition_path)]),
+        };
+        let assets = load_assets(&manifest).unwrap();
+
+        assert_eq!((assets.atlas.placements["rogue"].w, assets.atlas.placements["rogue"].h), (4, 2));
+        assert!(assets.sprite_sheets.contains_key("rogue_sheet"));
+        assert!(assets.animations.contains_key("rogue_idle_right"));
+    }
+
+    #[test]
+    fn test_every_broken_asset_is_reported() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let not_an_image = write(dir, "goblin.png", "not a png");
+        let broken_sheet = write(dir, "goblin.json", "{\"frames\": ");
+        let goblin_animations = write(dir, "goblin_animations.json", r#"{"creature": "goblin", "sprite_sheet": "goblin_sheet"}"#);
+        let rogue_animations = write(dir, "rogue_animations.json", r#"{"creature": "rogue", "sprite_sheet": "rogue_sheet"}"#);
+
+        let manifest = AssetManifest {
+            textures: BTreeMap::from([
+                ("goblin".to_string(), not_an_image),
+                ("rogue".to_string(), dir.join("missing.png").to_string_lossy().into_owned()),
+            ]),
+            sprite_sheets: BTreeMap::from([("goblin_sheet".to_string(), broken_sheet)]),
+            animations: BTreeMap::from([
+                ("goblin".to_string(), goblin_animations),
+                ("rogue".to_string(), rogue_animations),
+            ]),
+        };
+        let report = load_assets(&manifest).err().unwrap();
+
+        // The goblin animations are not reported on their own, as their sprite sheet is already broken. The missing
+        // rogue texture isn't an error, it is replaced with a placeholder.
+        assert_eq!(report.errors.len(), 3, "{}", report);
+        assert!(matches!(&report.errors[0], AssetError::SpriteSheet { source: AnimationLoadError::Parse { .. }, .. }));
+        assert!(matches!(&report.errors[1], AssetError::Texture { id, .. } if id == "goblin"));
+        assert!(matches!(&report.errors[2], AssetError::UnknownSpriteSheet { sprite_sheet, .. } if sprite_sheet == "rogue_sheet"));
+        assert!(report.to_string().starts_with("3 asset(s) failed to load:"));
+    }
+
+    #[test]
+    fn test_missing_art_is_replaced_with_placeholders() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let definition_path = write(dir, "rogue_animations.json", r#"{"creature": "rogue", "sprite_sheet": "rogue",
+            "animations": [{"name": "walk_right", "frame_prefix": "rogue_walk_right_", "frame_count": 4, "fps": 8.0}]}"#);
+
+        let manifest = AssetManifest {
+            textures: BTreeMap::from([
+                ("rogue".to_string(), dir.join("rogue.png").to_string_lossy().into_owned()),
+                ("plains".to_string(), dir.join("plains.png").to_string_lossy().into_owned()),
+            ]),
+            sprite_sheets: BTreeMap::from([("rogue".to_string(), dir.join("rogue.json").to_string_lossy().into_owned())]),
+            animations: BTreeMap::from([("rogue".to_string(), definition_path)]),
+        };
+        let assets = load_assets(&manifest).unwrap();
+
+        assert_eq!(assets.placeholders.len(), 3);
+        assert_eq!(assets.animations["rogue_walk_right"].frames.len(), 4);
+        // The rogue texture has a box for each of its four frames, and the plains are a tile set
+        let (rogue, plains) = (assets.atlas.placements["rogue"], assets.atlas.placements["plains"]);
+        assert_eq!((rogue.w, rogue.h), (36, 9));
+        assert_eq!((plains.w, plains.h), (128, 128));
+
+        // Both are packed onto one page, and the rogue's frames moved along with its texture
+        assert_eq!(rogue.page, plains.page);
+        let first_frame = assets.animations["rogue_walk_right"].frames[0].frame;
+        assert_eq!((first_frame.x, first_frame.y), (rogue.x as i32, rogue.y as i32));
+    }
+
+    #[test]
+    fn test_game_manifest_loads() {
+        // Runs with or without the art, as whatever is missing is replaced with a placeholder
+        let assets = load_manifest(ASSET_MANIFEST_PATH).and_then(|manifest| load_assets(&manifest)).unwrap();
+        for label in ["rogue_idle_right", "goblin_walk_up_left", "rogue_die", "cursor_click"] {
+            assert!(assets.animations.contains_key(label), "missing {}", label);
+        }
+        for biome in Biome::ALL {
+            assert!(assets.atlas.placements.contains_key(biome.texture_id()), "missing {:?} tiles", biome);
+            if let Some(edge_texture_id) = biome.edge_texture_id() {
+                assert!(assets.atlas.placements.contains_key(edge_texture_id), "missing {:?} edges", biome);
+            }
+        }
+        for feature in Feature::ALL {
+            assert!(assets.atlas.placements.contains_key(feature.texture_id()), "missing {:?} tiles", feature);
+        }
+        for kind in ResourceKind::ALL {
+            assert!(assets.atlas.placements.contains_key(kind.texture_id()), "missing {:?} texture", kind);
+        }
+    }
+
+    #[test]
+    fn test_missing_manifest() {
+        let report = load_manifest("resources/does_not_exist.json").err().unwrap();
+        assert!(matches!(&report.errors[..], [AssetError::Manifest { source: AnimationLoadError::Io { .. } }]));
+    }
+}
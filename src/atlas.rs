@@ -0,0 +1,226 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use macroquad::color::Color;
+use macroquad::math::Rect;
+use macroquad::prelude::Image;
+use crate::animations::animation::SpriteSheet;
+
+pub const ATLAS_PAGE_SIZE: u32 = 2048;
+// Empty space left around every packed image, so neighbours never bleed into each other
+const ATLAS_PADDING: u32 = 1;
+
+// Where a packed image ended up in the atlas
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AtlasPlacement {
+    pub page: usize,
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+}
+
+impl AtlasPlacement {
+    pub fn rect(&self) -> Rect {
+        Rect::new(self.x as f32, self.y as f32, self.w as f32, self.h as f32)
+    }
+}
+
+// Every texture packed into as few pages as possible, so that sprites from different sheets can be drawn from the
+// same texture and batched together
+pub struct TextureAtlas {
+    pub pages: Vec<Image>,
+    pub placements: HashMap<String, AtlasPlacement>,
+}
+
+// A page being filled a row ("shelf") at a time. Each shelf is as tall as the tallest image on it.
+struct Shelves {
+    width: u32,
+    height: u32,
+    shelf_x: u32,
+    shelf_y: u32,
+    shelf_height: u32,
+}
+
+impl Shelves {
+    fn new() -> Self {
+        Self { width: 0, height: 0, shelf_x: 0, shelf_y: 0, shelf_height: 0 }
+    }
+
+    fn place(&mut self, w: u32, h: u32, page_size: u32) -> Option<(u32, u32)> {
+        // Start a new shelf when this one is full, but only if the image then fits
+        let (x, y, shelf_height) = if self.shelf_x + w > page_size {
+            (0, self.shelf_y + self.shelf_height, 0)
+        } else {
+            (self.shelf_x, self.shelf_y, self.shelf_height)
+        };
+        if x + w > page_size || y + h > page_size {
+            return None;
+        }
+
+        let position = (x, y);
+        self.shelf_x = x + w;
+        self.shelf_y = y;
+        self.shelf_height = shelf_height.max(h);
+        self.width = self.width.max(self.shelf_x);
+        self.height = self.height.max(self.shelf_y + h);
+        Some(position)
+    }
+}
+
+// Shelf pack images into pages of at most `page_size` square, tallest first. An image too large for a page gets a
+// page of its own. Pages are trimmed down to the space actually used.
+pub fn pack_atlas(images: &BTreeMap<String, Image>, page_size: u32) -> TextureAtlas {
+    let mut order: Vec<_> = images.iter().collect();
+    order.sort_by(|(id_a, a), (id_b, b)| b.height.cmp(&a.height).then(b.width.cmp(&a.width)).then(id_a.cmp(id_b)));
+
+    let mut shelves: Vec<Shelves> = Vec::new();
+    let mut placements = HashMap::new();
+    for (id, image) in &order {
+        let (w, h) = (image.width as u32, image.height as u32);
+        let (padded_w, padded_h) = (w + ATLAS_PADDING * 2, h + ATLAS_PADDING * 2);
+
+        let mut placed = None;
+        for (page, page_shelves) in shelves.iter_mut().enumerate() {
+            if let Some(position) = page_shelves.place(padded_w, padded_h, page_size) {
+                placed = Some((page, position));
+                break;
+            }
+        }
+        let (page, (x, y)) = placed.unwrap_or_else(|| {
+            let mut page_shelves = Shelves::new();
+            let position = page_shelves.place(padded_w, padded_h, page_size.max(padded_w).max(padded_h)).unwrap();
+            shelves.push(page_shelves);
+            (shelves.len() - 1, position)
+        });
+
+        placements.insert(id.to_string(), AtlasPlacement { page, x: x + ATLAS_PADDING, y: y + ATLAS_PADDING, w, h });
+    }
+
+    let mut pages: Vec<Image> = shelves.iter()
+        .map(|page_shelves| Image::gen_image_color(page_shelves.width as u16, page_shelves.height as u16, Color::new(0., 0., 0., 0.)))
+        .collect();
+    for (id, image) in &order {
+        let placement = placements[id.as_str()];
+        blit(&mut pages[placement.page], image, placement.x, placement.y);
+    }
+
+    TextureAtlas { pages, placements }
+}
+
+fn blit(page: &mut Image, image: &Image, x: u32, y: u32) {
+    let row_bytes = image.width as usize * 4;
+    for row in 0..image.height as usize {
+        let source = row * row_bytes;
+        let destination = ((y as usize + row) * page.width as usize + x as usize) * 4;
+        page.bytes[destination..destination + row_bytes].copy_from_slice(&image.bytes[source..source + row_bytes]);
+    }
+}
+
+// Move every frame of a sprite sheet to where its texture was packed
+pub fn remap_sprite_sheet(sprite_sheet: &mut SpriteSheet, placement: &AtlasPlacement) {
+    for sprite in sprite_sheet.frames.values_mut() {
+        sprite.frame.x += placement.x as i32;
+        sprite.frame.y += placement.y as i32;
+    }
+}
+
+impl TextureAtlas {
+    // Write every page out as atlas_{page}.png, for checking what was packed where
+    pub fn dump(&self, directory: &str) -> std::io::Result<Vec<String>> {
+        fs::create_dir_all(directory)?;
+        let mut paths = Vec::with_capacity(self.pages.len());
+        for (index, page) in self.pages.iter().enumerate() {
+            let path = Path::new(directory).join(format!("atlas_{}.png", index)).to_string_lossy().into_owned();
+            // export_png flips the image upside down (it expects data read back from the GPU), so flip it first
+            flipped(page).export_png(&path);
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+}
+
+fn flipped(image: &Image) -> Image {
+    let row_bytes = image.width as usize * 4;
+    let mut flipped = image.clone();
+    for (row, bytes) in image.bytes.chunks_exact(row_bytes).rev().enumerate() {
+        flipped.bytes[row * row_bytes..(row + 1) * row_bytes].copy_from_slice(bytes);
+    }
+    flipped
+}
+
+#[cfg(test)]
+mod tests {
+    use macroquad::color::{BLUE, GREEN, RED};
+    use crate::animations::animation::{Frame, LiegeSprite, Size};
+    use super::*;
+
+    fn images() -> BTreeMap<String, Image> {
+        BTreeMap::from([
+            ("rogue".to_string(), Image::gen_image_color(40, 20, RED)),
+            ("goblin".to_string(), Image::gen_image_color(30, 30, GREEN)),
+            ("plains".to_string(), Image::gen_image_color(64, 8, BLUE)),
+        ])
+    }
+
+    fn overlaps(a: &AtlasPlacement, b: &AtlasPlacement) -> bool {
+        a.page == b.page && a.x < b.x + b.w && b.x < a.x + a.w && a.y < b.y + b.h && b.y < a.y + a.h
+    }
+
+    #[test]
+    fn test_pack_onto_one_page() {
+        let images = images();
+        let atlas = pack_atlas(&images, 128);
+
+        assert_eq!(atlas.pages.len(), 1);
+        let placements: Vec<_> = atlas.placements.values().collect();
+        for (i, a) in placements.iter().enumerate() {
+            for b in &placements[i + 1..] {
+                assert!(!overlaps(a, b), "{:?} overlaps {:?}", a, b);
+            }
+        }
+
+        // Every image is copied to where it was placed
+        for (id, image) in &images {
+            let placement = atlas.placements[id];
+            assert_eq!((placement.w, placement.h), (image.width as u32, image.height as u32));
+            let page = &atlas.pages[placement.page];
+            assert_eq!(page.get_pixel(placement.x, placement.y), image.get_pixel(0, 0));
+            assert_eq!(page.get_pixel(placement.x + placement.w - 1, placement.y + placement.h - 1), image.get_pixel(0, 0));
+        }
+    }
+
+    #[test]
+    fn test_pack_overflows_onto_more_pages() {
+        let atlas = pack_atlas(&images(), 64);
+
+        // The plains are 66 pixels wide with padding, so don't fit a page and get one of their own
+        assert_eq!(atlas.pages.len(), 2);
+        assert_eq!(atlas.placements["goblin"].page, atlas.placements["rogue"].page);
+        assert_eq!((atlas.pages[atlas.placements["plains"].page].width, atlas.pages[atlas.placements["plains"].page].height), (66, 10));
+    }
+
+    #[test]
+    fn test_remap_sprite_sheet() {
+        let sprite = LiegeSprite {
+            frame: Frame { x: 9, y: 0, w: 9, h: 9 },
+            rotated: false,
+            trimmed: false,
+            sprite_source_size: Frame { x: 0, y: 0, w: 9, h: 9 },
+            source_size: Size { w: 9, h: 9 },
+            duration: None,
+        };
+        let mut sprite_sheet = SpriteSheet::new(vec![("rogue_idle_right_2.png".to_string(), sprite)], Vec::new());
+        remap_sprite_sheet(&mut sprite_sheet, &AtlasPlacement { page: 0, x: 100, y: 50, w: 18, h: 9 });
+
+        assert_eq!(sprite_sheet.frames["rogue_idle_right_2.png"].frame, Frame { x: 109, y: 50, w: 9, h: 9 });
+    }
+
+    #[test]
+    fn test_flipped() {
+        let mut image = Image::gen_image_color(2, 2, RED);
+        image.set_pixel(0, 0, BLUE);
+        assert_eq!(flipped(&image).get_pixel(0, 1), image.get_pixel(0, 0));
+        assert_eq!(flipped(&image).get_pixel(0, 0), image.get_pixel(0, 1));
+    }
+}
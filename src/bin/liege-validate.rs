@@ -0,0 +1,17 @@
+use std::process::ExitCode;
+use liege::assets::ASSET_MANIFEST_PATH;
+use liege::validate::validate_assets;
+
+// Check every sprite sheet and animation definition in the asset manifest, e.g.
+//   cargo run --bin liege-validate [path/to/manifest.json]
+fn main() -> ExitCode {
+    let manifest_path = std::env::args().nth(1).unwrap_or_else(|| ASSET_MANIFEST_PATH.to_string());
+    let report = validate_assets(&manifest_path);
+    println!("{}", report);
+
+    if report.has_errors() {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
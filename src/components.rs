@@ -1,33 +1,144 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
 use benimator::{State};
 use macroquad::prelude::*;
-use crate::LiegeAnimation;
+use serde_json::Value;
+use crate::animations::animation::{AnimationId, AnimationMap, LiegeSprite, ANIMATION_END_EVENT};
+use crate::animations::controller::Facing;
+use crate::assets::TextureId;
+use crate::resource_nodes::ResourceKind;
+use crate::Faction;
 
 #[derive(Clone, Copy, Debug, PartialEq)]
-pub enum AnimationState {
-    Idle,
-    WalkRight,
-    WalkLeft,
-    WalkUpRight,
-    WalkUpLeft,
-}
-
-#[derive(Clone, Copy, Debug, PartialEq)]
-pub struct DrawableComponent<'a> {
-    pub(crate) position: Vec2,
-    pub texture_handle: &'a str,
+pub struct DrawableComponent {
+    pub position: Vec2,
+    pub texture: TextureId,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct MovementComponent {
     pub destination: Vec2,
     pub speed: f32,
+    // How far the entity moved this tick, zero while it stands still
+    pub velocity: Vec2,
+    pub mode: MovementMode,
+}
+
+// How an entity finds its way to its destination
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MovementMode {
+    // Along its own path, in its PathComponent
+    #[default]
+    Path,
+    // Along the flow field shared by everything heading for the same goal tile
+    FlowField { goal: (i32, i32) },
+}
+
+// World positions an entity walks through in order, one straight line at a time, to get to its destination
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PathComponent {
+    pub waypoints: Vec<Vec2>,
+    next: usize,
+}
+
+impl PathComponent {
+    pub fn new(waypoints: Vec<Vec2>) -> Self {
+        Self { waypoints, next: 0 }
+    }
+
+    // The waypoint being walked towards, or None once the path is finished
+    pub fn current(&self) -> Option<Vec2> {
+        self.waypoints.get(self.next).copied()
+    }
+
+    pub fn advance(&mut self) {
+        self.next = (self.next + 1).min(self.waypoints.len());
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.waypoints.len()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FacingComponent {
+    pub facing: Facing,
 }
 
 #[derive(Clone)]
 pub struct AnimatedComponent    {
-    pub animated_sprite_label: String,
-    pub liege_animation: LiegeAnimation,
+    pub animation: AnimationId,
     pub animation_state: State,
+    // Draw the animation mirrored left to right, for facings that reuse the art of the opposite side
+    pub flip_x: bool,
+    // Events raised by the most recent update, readable by any system that runs before the next one
+    pub events: Vec<&'static str>,
+    last_frame: Option<usize>,
+}
+
+impl AnimatedComponent {
+    pub fn new(animation: AnimationId) -> Self {
+        Self {
+            animation,
+            animation_state: State::new(),
+            flip_x: false,
+            events: Vec::new(),
+            last_frame: None,
+        }
+    }
+
+    // Switch to a different animation, starting it from its first frame
+    pub fn play(&mut self, animation: AnimationId) {
+        self.animation = animation;
+        self.animation_state = State::new();
+        self.events.clear();
+        self.last_frame = None;
+    }
+
+    pub fn current_frame<'a>(&self, animation_map: &'a AnimationMap) -> &'a LiegeSprite {
+        &animation_map.get(self.animation).frames[self.animation_state.frame_index()]
+    }
+
+    // Advance the animation, and queue up the events for every frame it moves onto, in the order they play, plus the
+    // end event if the animation finished during this update. Playback is stepped no more than one frame at a time, so
+    // a long delta that skips over frames (or around a loop) still raises their events.
+    pub fn update(&mut self, animation_map: &AnimationMap, delta: Duration) {
+        let liege_animation = animation_map.get(self.animation);
+        self.events.clear();
+
+        let was_ended = self.animation_state.is_ended();
+        let step = liege_animation.shortest_frame;
+        let mut remaining = delta;
+        loop {
+            let elapsed = if step.is_zero() { remaining } else { remaining.min(step) };
+            remaining -= elapsed;
+            self.animation_state.update(&liege_animation.animation, elapsed);
+
+            let frame = self.animation_state.frame_index();
+            if self.last_frame != Some(frame) {
+                self.events.extend(liege_animation.events_on_frame(frame));
+                self.last_frame = Some(frame);
+            }
+            if remaining.is_zero() || self.animation_state.is_ended() {
+                break;
+            }
+        }
+        if !was_ended && self.animation_state.is_ended() {
+            self.events.push(ANIMATION_END_EVENT);
+        }
+    }
+
+    pub fn has_event(&self, name: &str) -> bool {
+        self.events.contains(&name)
+    }
+
+    // Called after the animation being played has been replaced in the AnimationMap (e.g. by a hot reload).
+    // Playback carries on from the same frame, unless the new animation no longer has that many frames.
+    pub fn refresh(&mut self, animation_map: &AnimationMap) {
+        if self.animation_state.frame_index() >= animation_map.get(self.animation).frames.len() {
+            self.play(self.animation);
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -38,4 +149,235 @@ pub struct EntityKind<'a> {
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct SelectedComponent {
     pub selected: bool
-}
\ No newline at end of file
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FactionComponent {
+    pub faction: Faction,
+}
+
+// How far an entity can see, in tiles. Only what the player owns lifts the fog of war.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SightComponent {
+    pub radius: i32,
+}
+
+// The custom properties of the Tiled object that placed an entity, for whatever wants to read more of them
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ScenarioPropertiesComponent {
+    pub properties: BTreeMap<String, Value>,
+}
+
+// A building, covering `size` tiles from its top left tile
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BuildingComponent {
+    pub tile: (i32, i32),
+    pub size: (i32, i32),
+}
+
+impl BuildingComponent {
+    pub fn centre(&self) -> (i32, i32) {
+        (self.tile.0 + self.size.0 / 2, self.tile.1 + self.size.1 / 2)
+    }
+}
+
+// Marks a resource node that has been worked and is growing back, so full nodes aren't looked at every tick
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RegrowingComponent;
+
+// Something on the map that can be harvested, until there's nothing left in it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ResourceNodeComponent {
+    pub kind: ResourceKind,
+    pub tile: (i32, i32),
+    pub remaining: u32,
+    // Regrowth that hasn't added up to a whole unit yet
+    regrown: f32,
+}
+
+impl ResourceNodeComponent {
+    pub fn new(kind: ResourceKind, tile: (i32, i32)) -> Self {
+        Self { kind, tile, remaining: kind.capacity(), regrown: 0. }
+    }
+
+    // Take up to `amount` out of the node, returning how much was actually taken
+    pub fn work(&mut self, amount: u32) -> u32 {
+        let taken = amount.min(self.remaining);
+        self.remaining -= taken;
+        taken
+    }
+
+    pub fn is_depleted(&self) -> bool {
+        self.remaining == 0
+    }
+
+    // Whether a depleted node is gone for good, rather than growing back
+    pub fn is_exhausted(&self) -> bool {
+        self.is_depleted() && self.kind.regrowth().is_none()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.remaining >= self.kind.capacity()
+    }
+
+    // Grow back for `seconds`, if the node's kind grows back at all, up to its capacity
+    pub fn regrow(&mut self, seconds: f32) {
+        let Some(rate) = self.kind.regrowth() else { return };
+        if self.is_full() {
+            self.regrown = 0.;
+            return;
+        }
+        self.regrown += rate * seconds;
+        let whole = self.regrown.floor();
+        self.regrown -= whole;
+        self.remaining = (self.remaining + whole as u32).min(self.kind.capacity());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use benimator::{Animation, FrameRate};
+    use crate::animations::animation::{Frame, FrameEvent, LiegeAnimation, Size};
+    use super::*;
+
+    fn test_animation(frame_count: usize, events: Vec<FrameEvent>) -> (AnimationMap, AnimatedComponent) {
+        let sprite = LiegeSprite {
+            frame: Frame { x: 0, y: 0, w: 9, h: 9 },
+            rotated: false,
+            trimmed: false,
+            sprite_source_size: Frame { x: 0, y: 0, w: 9, h: 9 },
+            source_size: Size { w: 9, h: 9 },
+            duration: None,
+        };
+        let mut animation_map = AnimationMap::default();
+        let id = animation_map.insert("goblin_test".to_string(), LiegeAnimation {
+            frames: vec![sprite; frame_count],
+            animation: Animation::from_indices(0..frame_count, FrameRate::from_fps(10.)).once(),
+            events,
+            shortest_frame: Duration::from_millis(100),
+        });
+        (animation_map, AnimatedComponent::new(id))
+    }
+
+    #[test]
+    fn test_frame_events_fire_once_when_frame_is_reached() {
+        let (animation_map, mut animated) = test_animation(4, vec![FrameEvent { frame: 2, name: "hit" }]);
+
+        animated.update(&animation_map, Duration::from_millis(150));
+        assert_eq!(animated.animation_state.frame_index(), 1);
+        assert!(animated.events.is_empty());
+
+        animated.update(&animation_map, Duration::from_millis(100));
+        assert_eq!(animated.animation_state.frame_index(), 2);
+        assert!(animated.has_event("hit"));
+
+        // Staying on the same frame doesn't raise the event again
+        animated.update(&animation_map, Duration::from_millis(10));
+        assert!(!animated.has_event("hit"));
+    }
+
+    #[test]
+    fn test_skipped_frames_still_raise_their_events() {
+        let events = vec![FrameEvent { frame: 1, name: "wind_up" }, FrameEvent { frame: 2, name: "hit" }];
+        let (animation_map, mut animated) = test_animation(4, events);
+
+        // From the first frame straight to the last, passing the wind up and the hit on the way, in order
+        animated.update(&animation_map, Duration::from_millis(10));
+        animated.update(&animation_map, Duration::from_millis(300));
+        assert_eq!(animated.animation_state.frame_index(), 3);
+        assert_eq!(animated.events, vec!["wind_up", "hit"]);
+    }
+
+    #[test]
+    fn test_looping_past_the_end_raises_events_around_the_loop() {
+        let (mut animation_map, mut animated) = test_animation(3, Vec::new());
+        let mut looping = animation_map.get(animated.animation).clone();
+        looping.animation = Animation::from_indices(0..3, FrameRate::from_fps(10.)).repeat();
+        looping.events = vec![FrameEvent { frame: 0, name: "footstep" }, FrameEvent { frame: 2, name: "footstep_left" }];
+        animation_map.insert("goblin_test".to_string(), looping);
+
+        animated.update(&animation_map, Duration::from_millis(150));
+        assert_eq!(animated.animation_state.frame_index(), 1);
+        // Round through the last frame and back to the first
+        animated.update(&animation_map, Duration::from_millis(200));
+        assert_eq!(animated.animation_state.frame_index(), 0);
+        assert_eq!(animated.events, vec!["footstep_left", "footstep"]);
+    }
+
+    #[test]
+    fn test_end_event_fires_when_animation_finishes() {
+        let (animation_map, mut animated) = test_animation(2, Vec::new());
+
+        animated.update(&animation_map, Duration::from_millis(150));
+        assert!(!animated.has_event(ANIMATION_END_EVENT));
+
+        animated.update(&animation_map, Duration::from_millis(100));
+        assert!(animated.has_event(ANIMATION_END_EVENT));
+
+        animated.update(&animation_map, Duration::from_millis(100));
+        assert!(animated.events.is_empty());
+    }
+
+    #[test]
+    fn test_refresh_keeps_playing_unless_animation_got_shorter() {
+        let (mut animation_map, mut animated) = test_animation(4, Vec::new());
+        animated.update(&animation_map, Duration::from_millis(250));
+        assert_eq!(animated.animation_state.frame_index(), 2);
+
+        let (longer, _) = test_animation(6, Vec::new());
+        animation_map.insert("goblin_test".to_string(), longer.get(animated.animation).clone());
+        animated.refresh(&animation_map);
+        assert_eq!(animated.animation_state.frame_index(), 2);
+
+        let (shorter, _) = test_animation(2, Vec::new());
+        animation_map.insert("goblin_test".to_string(), shorter.get(animated.animation).clone());
+        animated.refresh(&animation_map);
+        assert_eq!(animated.animation_state.frame_index(), 0);
+    }
+
+    #[test]
+    fn test_first_frame_events_fire_on_first_update() {
+        let (animation_map, mut animated) = test_animation(2, vec![FrameEvent { frame: 0, name: "footstep" }]);
+
+        animated.update(&animation_map, Duration::from_millis(10));
+        assert!(animated.has_event("footstep"));
+    }
+
+    #[test]
+    fn test_path_component_walks_waypoints_in_order() {
+        let mut path = PathComponent::new(vec![Vec2::new(10., 0.), Vec2::new(20., 5.)]);
+        assert_eq!(path.current(), Some(Vec2::new(10., 0.)));
+
+        path.advance();
+        assert_eq!(path.current(), Some(Vec2::new(20., 5.)));
+        assert!(!path.is_finished());
+
+        path.advance();
+        path.advance();
+        assert_eq!(path.current(), None);
+        assert!(path.is_finished());
+        assert!(PathComponent::default().is_finished());
+    }
+
+    #[test]
+    fn test_resource_nodes_deplete_and_regrow() {
+        let mut gold = ResourceNodeComponent::new(ResourceKind::Gold, (0, 0));
+        assert_eq!(gold.work(150), 150);
+        // Only what's left can be taken
+        assert_eq!(gold.work(150), 50);
+        assert!(gold.is_depleted() && gold.is_exhausted());
+        gold.regrow(1000.);
+        assert_eq!(gold.remaining, 0);
+
+        let mut tree = ResourceNodeComponent::new(ResourceKind::Tree, (0, 0));
+        tree.work(ResourceKind::Tree.capacity());
+        assert!(tree.is_depleted() && !tree.is_exhausted());
+        // Half a unit a second, carried over between updates
+        tree.regrow(3.);
+        assert_eq!(tree.remaining, 1);
+        tree.regrow(1.);
+        assert_eq!(tree.remaining, 2);
+        tree.regrow(1000.);
+        assert_eq!(tree.remaining, ResourceKind::Tree.capacity());
+    }
+}
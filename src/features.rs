@@ -0,0 +1,404 @@
+use std::collections::{BinaryHeap, HashSet};
+use rand::rngs::StdRng;
+use rand::Rng;
+use crate::map::{Biome, Feature, GameMap, Tile};
+use crate::pathfinding::OpenTile;
+
+const SIDES: [(i32, i32); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+
+// Rivers start at least this far apart, in tiles
+const RIVER_SPACING: i32 = 20;
+// Rivers longer than this are cut short, in case one wanders forever over flat ground
+const MAX_RIVER_LENGTH: usize = 400;
+// A lake that fills this many tiles without spilling over is where its river ends
+const MAX_LAKE_SIZE: usize = 48;
+// Points of interest are at least this far from the castle site and each other, in tiles
+const SITE_SPACING: i32 = 25;
+// Each random pick of a river source or point of interest gets this many tries to find somewhere suitable
+const PLACEMENT_ATTEMPTS: u32 = 50;
+// What laying a road costs, compared to a grassland tile: following an existing road is cheap, so roads join up, and
+// bridges are expensive, so roads only cross rivers when going round is a long way
+const EXISTING_ROAD_COST: f32 = 0.2;
+const BRIDGE_COST: f32 = 8.;
+
+// Start rivers at random spots in the highlands, each flowing downhill over the elevation layer (one value per tile,
+// row by row) until it reaches the sea, another river or the edge of the map. A river that runs into a hollow fills
+// it with a lake, and carries on from wherever the lake spills over.
+pub(crate) fn add_rivers(game_map: &mut GameMap, elevation: &[f64], rng: &mut StdRng) {
+    let highlands = game_map.config.thresholds.hills;
+    let mut sources: Vec<(i32, i32)> = Vec::new();
+    for _ in 0..game_map.config.rivers * PLACEMENT_ATTEMPTS {
+        if sources.len() == game_map.config.rivers as usize {
+            break;
+        }
+        let source = (rng.gen_range(0..game_map.map_width), rng.gen_range(0..game_map.map_height));
+        let suitable = elevation[game_map.map_index(source.0, source.1) as usize] >= highlands
+            && game_map.tile(source.0, source.1).is_some_and(|tile| tile.is_passable() && tile.feature.is_none())
+            && sources.iter().all(|&other| distance(source, other) >= RIVER_SPACING);
+        if suitable {
+            sources.push(source);
+        }
+    }
+
+    for source in sources {
+        add_river(game_map, elevation, source);
+    }
+}
+
+fn add_river(game_map: &mut GameMap, elevation: &[f64], source: (i32, i32)) {
+    let map_width = game_map.map_width;
+    let height = |(x, y): (i32, i32)| elevation[(y * map_width + x) as usize];
+    // The river's own tiles, and its lakes', which it can't flow back into
+    let mut course = HashSet::new();
+    let mut current = source;
+
+    for _ in 0..MAX_RIVER_LENGTH {
+        game_map.set_feature(current.0, current.1, Some(Feature::River));
+        course.insert(current);
+        if on_edge(game_map, current) {
+            return;
+        }
+
+        let lowest = SIDES.iter()
+            .map(|&(dx, dy)| (current.0 + dx, current.1 + dy))
+            .filter(|side| !course.contains(side))
+            .min_by(|&a, &b| height(a).total_cmp(&height(b)));
+        match lowest {
+            Some(next) if height(next) <= height(current) => {
+                if joins_water(game_map, next) {
+                    return;
+                }
+                current = next;
+            }
+            // Nowhere lower to go
+            _ => match add_lake(game_map, elevation, current, &mut course) {
+                Some(outflow) => current = outflow,
+                None => return,
+            },
+        }
+    }
+}
+
+// Fill the hollow around `lowest` with a lake, lowest ground first, until it spills over the lowest point on its rim.
+// That's where its river flows out, or None if the river ends here: the lake reached the sea, another river or the
+// edge of the map, or grew as large as lakes get.
+fn add_lake(game_map: &mut GameMap, elevation: &[f64], lowest: (i32, i32), course: &mut HashSet<(i32, i32)>) -> Option<(i32, i32)> {
+    let lowest_index = game_map.map_index(lowest.0, lowest.1) as usize;
+    let mut level = elevation[lowest_index];
+    let mut open = BinaryHeap::from([OpenTile { estimate: level as f32, index: lowest_index }]);
+    let mut seen = HashSet::from([lowest]);
+    let mut size = 0;
+
+    while let Some(OpenTile { index, .. }) = open.pop() {
+        let (x, y) = game_map.map_coords(index as i32);
+        // Downhill from the water level, so the lake spills out here
+        if elevation[index] < level {
+            return Some((x, y));
+        }
+        if size >= MAX_LAKE_SIZE {
+            return None;
+        }
+        level = elevation[index];
+        game_map.set_feature(x, y, Some(Feature::Lake));
+        course.insert((x, y));
+        size += 1;
+        if on_edge(game_map, (x, y)) {
+            return None;
+        }
+
+        for (dx, dy) in SIDES {
+            let side = (x + dx, y + dy);
+            if course.contains(&side) || !seen.insert(side) {
+                continue;
+            }
+            if joins_water(game_map, side) {
+                return None;
+            }
+            let side_index = game_map.map_index(side.0, side.1) as usize;
+            open.push(OpenTile { estimate: elevation[side_index] as f32, index: side_index });
+        }
+    }
+    None
+}
+
+// Whether a river reaching x, y runs into the sea, a lake or another river
+fn joins_water(game_map: &GameMap, (x, y): (i32, i32)) -> bool {
+    game_map.tile(x, y).is_some_and(|tile| tile.biome == Biome::Water || matches!(tile.feature, Some(Feature::River | Feature::Lake)))
+}
+
+fn on_edge(game_map: &GameMap, (x, y): (i32, i32)) -> bool {
+    x == 0 || y == 0 || x == game_map.map_width - 1 || y == game_map.map_height - 1
+}
+
+// Pick a castle site on open grassland, as close to the middle of the map as there is, then points of interest
+// scattered around it on any land with nothing on it yet
+pub(crate) fn place_sites(game_map: &mut GameMap, rng: &mut StdRng) {
+    let (middle_x, middle_y) = (game_map.map_width / 2, game_map.map_height / 2);
+    let open_land = |tile: Tile| tile.is_passable() && tile.feature.is_none();
+    let castle_site = game_map.nearest_tile(middle_x, middle_y, |tile| open_land(tile) && tile.biome == Biome::Grassland)
+        .or_else(|| game_map.nearest_tile(middle_x, middle_y, open_land));
+    game_map.castle_site = castle_site;
+    game_map.points_of_interest = Vec::new();
+    let Some(castle_site) = castle_site else { return };
+
+    let wanted = game_map.config.points_of_interest as usize;
+    let mut points_of_interest: Vec<(i32, i32)> = Vec::new();
+    for _ in 0..game_map.config.points_of_interest * PLACEMENT_ATTEMPTS {
+        if points_of_interest.len() == wanted {
+            break;
+        }
+        let site = (rng.gen_range(0..game_map.map_width), rng.gen_range(0..game_map.map_height));
+        let suitable = game_map.tile(site.0, site.1).is_some_and(open_land)
+            && points_of_interest.iter().chain([&castle_site]).all(|&other| distance(site, other) >= SITE_SPACING);
+        if suitable {
+            points_of_interest.push(site);
+        }
+    }
+    game_map.points_of_interest = points_of_interest;
+}
+
+// Lay a road from the castle site to each point of interest, nearest first, so that later roads can branch off the
+// earlier ones. Where a road crosses a river it becomes a bridge. Points that can't be reached over land get no road.
+pub(crate) fn add_roads(game_map: &mut GameMap) {
+    let Some(castle_site) = game_map.castle_site else { return };
+    let mut destinations = game_map.points_of_interest.clone();
+    destinations.sort_by_key(|&site| distance(castle_site, site));
+
+    for destination in destinations {
+        let Some(route) = road_route(game_map, castle_site, destination) else { continue };
+        for (x, y) in route {
+            let tile = game_map.tile(x, y).unwrap();
+            let feature = match tile.feature {
+                Some(Feature::River | Feature::Bridge) => Feature::Bridge,
+                _ => Feature::Road,
+            };
+            game_map.set_feature(x, y, Some(feature));
+        }
+    }
+}
+
+// Cost of laying a road over a tile, or None if no road can go there
+fn road_cost(tile: Tile) -> Option<f32> {
+    match tile.feature {
+        Some(Feature::Road | Feature::Bridge) => Some(EXISTING_ROAD_COST),
+        Some(Feature::River) => Some(BRIDGE_COST),
+        Some(Feature::Lake) => None,
+        None => tile.biome.movement_cost(),
+    }
+}
+
+// The cheapest route for a road from start to goal, A* over the four sides of each tile so that roads are unbroken
+// lines of tiles, and cross rivers square on
+fn road_route(game_map: &GameMap, start: (i32, i32), goal: (i32, i32)) -> Option<Vec<(i32, i32)>> {
+    let tile_count = game_map.tile_count();
+    let mut cost_so_far = vec![f32::INFINITY; tile_count];
+    let mut came_from = vec![usize::MAX; tile_count];
+    let mut open = BinaryHeap::new();
+    let heuristic = |(x, y): (i32, i32)| ((x - goal.0).abs() + (y - goal.1).abs()) as f32 * EXISTING_ROAD_COST;
+
+    let start_index = game_map.map_index(start.0, start.1) as usize;
+    let goal_index = game_map.map_index(goal.0, goal.1) as usize;
+    cost_so_far[start_index] = 0.;
+    open.push(OpenTile { estimate: heuristic(start), index: start_index });
+
+    while let Some(OpenTile { estimate, index }) = open.pop() {
+        if index == goal_index {
+            let mut route = vec![goal];
+            let mut current = index;
+            while current != start_index {
+                current = came_from[current];
+                route.push(game_map.map_coords(current as i32));
+            }
+            route.reverse();
+            return Some(route);
+        }
+        let (x, y) = game_map.map_coords(index as i32);
+        // Already reached more cheaply
+        if estimate > cost_so_far[index] + heuristic((x, y)) {
+            continue;
+        }
+
+        for (dx, dy) in SIDES {
+            let Some(step_cost) = game_map.tile(x + dx, y + dy).and_then(road_cost) else { continue };
+            let next_index = game_map.map_index(x + dx, y + dy) as usize;
+            let next_cost = cost_so_far[index] + step_cost;
+            if next_cost < cost_so_far[next_index] {
+                cost_so_far[next_index] = next_cost;
+                came_from[next_index] = index;
+                open.push(OpenTile { estimate: next_cost + heuristic((x + dx, y + dy)), index: next_index });
+            }
+        }
+    }
+    None
+}
+
+// Manhattan distance between two tiles
+fn distance(a: (i32, i32), b: (i32, i32)) -> i32 {
+    (a.0 - b.0).abs() + (a.1 - b.1).abs()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use crate::map::MapGenConfig;
+    use crate::pathfinding::find_path;
+    use super::*;
+
+    fn generated_map(seed: u32) -> GameMap {
+        let mut game_map = GameMap::new(200, 200, MapGenConfig::with_seed(seed));
+        game_map.generate_terrain();
+        game_map
+    }
+
+    // Every tile with a river on it
+    fn river_tiles(game_map: &GameMap) -> Vec<(i32, i32)> {
+        game_map.tiles().filter(|(_, tile)| tile.feature == Some(Feature::River)).map(|(position, _)| position).collect()
+    }
+
+    #[test]
+    fn test_rivers_flow_downhill() {
+        // A slope down to the west, with a ridge up the middle
+        let mut game_map = GameMap::new(12, 5, MapGenConfig { rivers: 1, ..Default::default() });
+        game_map.fill(|_, _| Tile::new(Biome::Grassland, 0));
+        let elevation: Vec<f64> = (0..60).map(|index| (index % 12) as f64 * 0.05 + if index / 12 == 2 { 0. } else { 0.01 }).collect();
+        add_river(&mut game_map, &elevation, (10, 2));
+
+        // Straight down the valley to the edge of the map
+        let expected: Vec<_> = (0..=10).map(|x| (x, 2)).collect();
+        let mut river = river_tiles(&game_map);
+        river.sort();
+        assert_eq!(river, expected);
+        assert!(game_map.tiles().all(|(_, tile)| tile.feature != Some(Feature::Lake)));
+    }
+
+    fn lake_tiles(game_map: &GameMap) -> Vec<(i32, i32)> {
+        game_map.tiles().filter(|(_, tile)| tile.feature == Some(Feature::Lake)).map(|(position, _)| position).collect()
+    }
+
+    #[test]
+    fn test_lakes_spill_over() {
+        // A valley down the middle row, with a hollow in it, and high ground either side
+        let mut game_map = GameMap::new(9, 3, MapGenConfig::default());
+        game_map.fill(|_, _| Tile::new(Biome::Grassland, 0));
+        let valley = [0.5, 0.4, 0.1, 0.2, 0.3, 0.25, 0.2, 0.15, 0.1];
+        let elevation: Vec<f64> = (0..27).map(|index| if index / 9 == 1 { valley[index % 9] } else { 0.9 }).collect();
+        add_river(&mut game_map, &elevation, (1, 1));
+
+        // The hollow fills up to its rim, and the river carries on over it to the edge of the map
+        assert_eq!(lake_tiles(&game_map), vec![(2, 1), (3, 1), (4, 1)]);
+        let mut river = river_tiles(&game_map);
+        river.sort();
+        assert_eq!(river, vec![(1, 1), (5, 1), (6, 1), (7, 1), (8, 1)]);
+    }
+
+    #[test]
+    fn test_rivers_end_in_lakes() {
+        // A bowl, lowest in the middle, with more room in it than any lake fills
+        let mut game_map = GameMap::new(11, 11, MapGenConfig::default());
+        game_map.fill(|_, _| Tile::new(Biome::Grassland, 0));
+        let elevation: Vec<f64> = (0..121).map(|index| (((index % 11) - 5i32).pow(2) + ((index / 11) - 5i32).pow(2)) as f64 * 0.01).collect();
+        add_river(&mut game_map, &elevation, (5, 1));
+
+        let lake = lake_tiles(&game_map);
+        assert_eq!(lake.len(), MAX_LAKE_SIZE);
+        assert!(lake.contains(&(5, 5)));
+        assert!(river_tiles(&game_map).contains(&(5, 1)));
+        assert!(!game_map.is_passable(5, 5) && !game_map.is_passable(5, 1));
+    }
+
+    #[test]
+    fn test_rivers_stop_at_water() {
+        let mut game_map = GameMap::new(8, 3, MapGenConfig::default());
+        game_map.fill(|x, _| Tile::new(if x < 3 { Biome::Water } else { Biome::Grassland }, 0));
+        let elevation: Vec<f64> = (0..24).map(|index| (index % 8) as f64 * 0.1).collect();
+        add_river(&mut game_map, &elevation, (6, 1));
+
+        let mut river = river_tiles(&game_map);
+        river.sort();
+        assert_eq!(river, vec![(3, 1), (4, 1), (5, 1), (6, 1)]);
+    }
+
+    #[test]
+    fn test_roads_bridge_rivers() {
+        // A river across the middle, that the road has to cross to get from one side to the other
+        let mut game_map = GameMap::new(7, 7, MapGenConfig::default());
+        game_map.fill(|_, y| if y == 3 { Tile::new(Biome::Grassland, 0).with_feature(Feature::River) } else { Tile::new(Biome::Grassland, 0) });
+        game_map.castle_site = Some((3, 0));
+        game_map.points_of_interest = vec![(3, 6)];
+        add_roads(&mut game_map);
+
+        assert_eq!(game_map.tile(3, 3).unwrap().feature, Some(Feature::Bridge));
+        let road: Vec<_> = game_map.tiles().filter(|(_, tile)| tile.feature.is_some_and(|feature| feature != Feature::River)).collect();
+        assert_eq!(road.len(), 7);
+        // Bridges can be walked over, the rest of the river can't
+        assert!(game_map.is_passable(3, 3) && !game_map.is_passable(2, 3));
+        assert!(find_path(&game_map, (0, 0), (0, 6)).is_some());
+    }
+
+    #[test]
+    fn test_roads_go_round_lakes_and_join_up() {
+        let mut game_map = GameMap::new(9, 9, MapGenConfig::default());
+        game_map.fill(|x, y| {
+            let tile = Tile::new(Biome::Grassland, 0);
+            if (2..7).contains(&x) && y == 4 { tile.with_feature(Feature::Lake) } else { tile }
+        });
+        game_map.castle_site = Some((4, 0));
+        game_map.points_of_interest = vec![(4, 8), (0, 4)];
+        add_roads(&mut game_map);
+
+        // Round the lake, never on it
+        for (x, y) in (2..7).map(|x| (x, 4)) {
+            assert_eq!(game_map.tile(x, y).unwrap().feature, Some(Feature::Lake));
+        }
+        let road = |x, y| game_map.tile(x, y).unwrap().feature == Some(Feature::Road);
+        assert!(road(4, 8) && road(0, 4));
+        // The second road branches off the first rather than laying another beside it, so all it adds is one tile
+        let road_count = game_map.tiles().filter(|(_, tile)| tile.feature == Some(Feature::Road)).count();
+        assert_eq!(road_count, 16);
+    }
+
+    #[test]
+    fn test_generated_features() {
+        let game_map = generated_map(1001010101);
+        let count = |feature| game_map.tiles().filter(|(_, tile)| tile.feature == Some(feature)).count();
+        assert!(count(Feature::River) > 0);
+        assert!(count(Feature::Road) > 0);
+
+        // The castle is on open grassland, and every point of interest is connected to it by road
+        let castle_site = game_map.castle_site.unwrap();
+        assert_eq!(game_map.tile(castle_site.0, castle_site.1).unwrap().biome, Biome::Grassland);
+        assert!(!game_map.points_of_interest.is_empty());
+        for &site in &game_map.points_of_interest {
+            let mut stack = vec![castle_site];
+            let mut seen = HashSet::from([castle_site]);
+            while let Some((x, y)) = stack.pop() {
+                for (dx, dy) in SIDES {
+                    let on_road = game_map.tile(x + dx, y + dy).is_some_and(|tile| matches!(tile.feature, Some(Feature::Road | Feature::Bridge)));
+                    if on_road && seen.insert((x + dx, y + dy)) {
+                        stack.push((x + dx, y + dy));
+                    }
+                }
+            }
+            assert!(seen.contains(&site), "no road to {:?}", site);
+        }
+
+        // Rivers and lakes never sit on top of the sea
+        assert!(game_map.tiles().all(|(_, tile)| tile.biome != Biome::Water || tile.feature.is_none()));
+    }
+
+    #[test]
+    fn test_features_are_deterministic() {
+        let features = |game_map: &GameMap| game_map.tiles().map(|(_, tile)| tile.feature).collect::<Vec<_>>();
+        let (first, second) = (generated_map(42), generated_map(42));
+        assert_eq!(features(&first), features(&second));
+        assert_eq!(first.points_of_interest, second.points_of_interest);
+        assert_ne!(features(&first), features(&generated_map(43)));
+
+        // Nowhere for a castle on a map that's all water
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut sea = GameMap::new(10, 10, MapGenConfig::default());
+        sea.fill(|_, _| Tile::new(Biome::Water, 0));
+        place_sites(&mut sea, &mut rng);
+        assert_eq!((sea.castle_site, sea.points_of_interest.len()), (None, 0));
+    }
+}
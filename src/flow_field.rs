@@ -0,0 +1,253 @@
+use std::collections::{BinaryHeap, HashMap};
+use macroquad::math::Vec2;
+use crate::map::GameMap;
+use crate::pathfinding::{step_distance, OpenTile, NEIGHBOURS};
+use crate::MapInformation;
+
+// Flow fields are kept for this many goals at most, before the cache starts over
+const MAX_CACHED_FLOW_FIELDS: usize = 32;
+
+// The cheapest way to one goal tile from every tile on the map at once, so any number of entities heading for the
+// same place can share it instead of each finding their own path. Steps follow the same rules as find_path.
+pub struct FlowField {
+    goal: (i32, i32),
+    map_width: i32,
+    map_height: i32,
+    // Cost of the cheapest path from each tile to the goal, infinite where the goal can't be reached
+    costs: Vec<f32>,
+    // The neighbouring tile to step onto next, from each tile
+    next: Vec<Option<(i32, i32)>>,
+}
+
+impl FlowField {
+    pub fn new(game_map: &GameMap, goal: (i32, i32)) -> Self {
+        let tile_count = game_map.tile_count();
+        let mut field = Self {
+            goal,
+            map_width: game_map.map_width,
+            map_height: game_map.map_height,
+            costs: vec![f32::INFINITY; tile_count],
+            next: vec![None; tile_count],
+        };
+        if !game_map.is_passable(goal.0, goal.1) {
+            return field;
+        }
+
+        // Dijkstra outwards from the goal, along steps taken backwards
+        let mut closed = vec![false; tile_count];
+        let mut open = BinaryHeap::new();
+        let goal_index = game_map.map_index(goal.0, goal.1) as usize;
+        field.costs[goal_index] = 0.;
+        open.push(OpenTile { estimate: 0., index: goal_index });
+
+        while let Some(OpenTile { index, .. }) = open.pop() {
+            if closed[index] {
+                continue;
+            }
+            closed[index] = true;
+
+            let (x, y) = game_map.map_coords(index as i32);
+            let cost = game_map.movement_cost(x, y).unwrap();
+            for (dx, dy) in NEIGHBOURS {
+                let (from_x, from_y) = (x + dx, y + dy);
+                if !game_map.is_passable(from_x, from_y) {
+                    continue;
+                }
+                let Some(distance) = step_distance(game_map, (from_x, from_y), (-dx, -dy)) else { continue };
+
+                let from_index = game_map.map_index(from_x, from_y) as usize;
+                let from_cost = field.costs[index] + distance * cost;
+                if from_cost < field.costs[from_index] {
+                    field.costs[from_index] = from_cost;
+                    field.next[from_index] = Some((x, y));
+                    open.push(OpenTile { estimate: from_cost, index: from_index });
+                }
+            }
+        }
+
+        // Anything stranded on an impassable tile steps off onto whichever neighbour is closest to the goal
+        for index in 0..tile_count {
+            let (x, y) = game_map.map_coords(index as i32);
+            if game_map.is_passable(x, y) {
+                continue;
+            }
+            field.next[index] = NEIGHBOURS.iter()
+                .filter_map(|&(dx, dy)| {
+                    let distance = step_distance(game_map, (x, y), (dx, dy))?;
+                    let step_cost = distance * game_map.movement_cost(x + dx, y + dy)?;
+                    let total = field.costs[game_map.map_index(x + dx, y + dy) as usize] + step_cost;
+                    total.is_finite().then_some(((x + dx, y + dy), total))
+                })
+                .min_by(|(_, a), (_, b)| a.total_cmp(b))
+                .map(|(tile, _)| tile);
+        }
+
+        field
+    }
+
+    pub fn goal(&self) -> (i32, i32) {
+        self.goal
+    }
+
+    // Cost of the cheapest path from x, y to the goal, or None if the goal can't be reached from there
+    pub fn cost(&self, x: i32, y: i32) -> Option<f32> {
+        self.index(x, y).map(|index| self.costs[index]).filter(|cost| cost.is_finite())
+    }
+
+    // The tile to step onto next from x, y. None on the goal itself, and wherever the goal can't be reached from.
+    pub fn next_tile(&self, x: i32, y: i32) -> Option<(i32, i32)> {
+        self.index(x, y).and_then(|index| self.next[index])
+    }
+
+    // Where an entity at `position` should head next, in world coordinates: the centre of the next tile along the
+    // field, and then `destination` once it's on the goal tile. None once it gets there, or if it never can.
+    pub fn waypoint(&self, map_info: &MapInformation, position: Vec2, destination: Vec2) -> Option<Vec2> {
+        let (x, y) = map_info.world_to_tile(position);
+        match self.next_tile(x, y) {
+            Some((next_x, next_y)) => Some(map_info.tile_center(next_x, next_y)),
+            None if (x, y) == self.goal && position.distance(destination) >= 1. => Some(destination),
+            None => None,
+        }
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        (x >= 0 && y >= 0 && x < self.map_width && y < self.map_height).then(|| (y * self.map_width + x) as usize)
+    }
+}
+
+// Flow fields by goal tile, built the first time they're asked for. They're all thrown away whenever the map changes.
+#[derive(Default)]
+pub struct FlowFieldCache {
+    revision: u64,
+    fields: HashMap<(i32, i32), FlowField>,
+}
+
+impl FlowFieldCache {
+    pub fn get(&mut self, game_map: &GameMap, goal: (i32, i32)) -> &FlowField {
+        if self.revision != game_map.revision() {
+            self.fields.clear();
+            self.revision = game_map.revision();
+        }
+        if self.fields.len() >= MAX_CACHED_FLOW_FIELDS && !self.fields.contains_key(&goal) {
+            self.fields.clear();
+        }
+        self.fields.entry(goal).or_insert_with(|| FlowField::new(game_map, goal))
+    }
+
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::map::{map_from_rows, Biome, Tile};
+    use crate::pathfinding::find_path;
+    use super::*;
+
+    fn follow(field: &FlowField, start: (i32, i32)) -> Vec<(i32, i32)> {
+        let mut tiles = vec![start];
+        while let Some(next) = field.next_tile(tiles.last().unwrap().0, tiles.last().unwrap().1) {
+            tiles.push(next);
+            assert!(tiles.len() < 100, "{:?} loops", tiles);
+        }
+        tiles
+    }
+
+    #[test]
+    fn test_flow_field_matches_a_star() {
+        let game_map = map_from_rows(&[
+            "......",
+            ".###f.",
+            "...#f.",
+            "ff.#..",
+        ]);
+        let goal = (5, 3);
+        let field = FlowField::new(&game_map, goal);
+
+        // Every tile that can reach the goal is led there, as cheaply as A* would get there
+        for y in 0..game_map.map_height {
+            for x in 0..game_map.map_width {
+                if !game_map.is_passable(x, y) {
+                    continue;
+                }
+                let tiles = follow(&field, (x, y));
+                assert_eq!(tiles.last(), Some(&goal));
+                for step in tiles.windows(2) {
+                    let ((x, y), (next_x, next_y)) = (step[0], step[1]);
+                    // No cutting corners
+                    assert!(game_map.is_passable(next_x, y) && game_map.is_passable(x, next_y));
+                }
+
+                let a_star = find_path(&game_map, (x, y), goal).unwrap();
+                let a_star_cost: f32 = a_star.windows(2)
+                    .map(|step| step_distance(&game_map, step[0], (step[1].0 - step[0].0, step[1].1 - step[0].1)).unwrap()
+                        * game_map.movement_cost(step[1].0, step[1].1).unwrap())
+                    .sum();
+                assert!((field.cost(x, y).unwrap() - a_star_cost).abs() < 0.001, "from {:?}", (x, y));
+            }
+        }
+        assert_eq!(field.next_tile(goal.0, goal.1), None);
+        assert_eq!(field.cost(goal.0, goal.1), Some(0.));
+    }
+
+    #[test]
+    fn test_flow_field_unreachable() {
+        let game_map = map_from_rows(&[
+            "..#..",
+            "..#..",
+        ]);
+        let field = FlowField::new(&game_map, (4, 0));
+        assert_eq!(field.cost(0, 0), None);
+        assert_eq!(field.next_tile(0, 0), None);
+        assert_eq!(field.next_tile(3, 1), Some((4, 0)));
+
+        // An impassable goal can't be reached from anywhere
+        let field = FlowField::new(&game_map, (2, 0));
+        assert_eq!(field.next_tile(1, 0), None);
+    }
+
+    #[test]
+    fn test_flow_field_leads_off_impassable_tiles() {
+        let game_map = map_from_rows(&[
+            "....",
+            ".##.",
+            "....",
+        ]);
+        let field = FlowField::new(&game_map, (3, 1));
+        assert_eq!(field.next_tile(2, 1), Some((3, 1)));
+        assert_eq!(follow(&field, (1, 1)).last(), Some(&(3, 1)));
+    }
+
+    #[test]
+    fn test_waypoint() {
+        let game_map = map_from_rows(&["...."]);
+        let map_info = MapInformation { width: 4, height: 1, tile_size: 10., tile_scale: Vec2::ONE };
+        let field = FlowField::new(&game_map, (3, 0));
+        let destination = map_info.tile_center(3, 0);
+
+        assert_eq!(field.waypoint(&map_info, Vec2::new(1., 1.), destination), Some(Vec2::new(15., 5.)));
+        assert_eq!(field.waypoint(&map_info, Vec2::new(31., 1.), destination), Some(destination));
+        assert_eq!(field.waypoint(&map_info, destination, destination), None);
+    }
+
+    #[test]
+    fn test_cache_rebuilds_when_map_changes() {
+        let mut game_map = map_from_rows(&["....."]);
+        let mut cache = FlowFieldCache::default();
+
+        assert_eq!(cache.get(&game_map, (4, 0)).next_tile(0, 0), Some((1, 0)));
+        cache.get(&game_map, (4, 0));
+        cache.get(&game_map, (0, 0));
+        assert_eq!(cache.len(), 2);
+
+        // Building across the map cuts the goal off
+        game_map.set_tile(2, 0, Tile::new(Biome::Water, 0));
+        assert_eq!(cache.get(&game_map, (4, 0)).next_tile(0, 0), None);
+        assert_eq!(cache.len(), 1);
+    }
+}
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+use std::fs;
+use std::time::SystemTime;
+use legion::{IntoQuery, Resources, TryWrite, World, Write};
+use crate::animations::animation::{AnimationMap, UIAnimationMap};
+use crate::animations::controller::AnimationController;
+use crate::animations::cursor::load_cursor_animations;
+use crate::assets::{load_assets, load_manifest, AssetError, AssetReport, TextureMap};
+use crate::atlas::TextureAtlas;
+use crate::components::AnimatedComponent;
+
+// Watches the manifest and every file it lists for changes, by polling their modification times
+pub struct AssetWatcher {
+    manifest_path: String,
+    modified: HashMap<String, Option<SystemTime>>,
+}
+
+impl AssetWatcher {
+    pub fn new(manifest_path: &str) -> Self {
+        let mut watcher = Self { manifest_path: manifest_path.to_string(), modified: HashMap::new() };
+        watcher.modified = watcher.snapshot();
+        watcher
+    }
+
+    // Whether anything changed, appeared or disappeared since the last poll
+    pub fn poll(&mut self) -> bool {
+        let modified = self.snapshot();
+        let changed = modified != self.modified;
+        self.modified = modified;
+        changed
+    }
+
+    fn snapshot(&self) -> HashMap<String, Option<SystemTime>> {
+        // A manifest that can't be read is still watched itself, so fixing it triggers a reload
+        let manifest = load_manifest(&self.manifest_path).unwrap_or_default();
+        std::iter::once(&self.manifest_path)
+            .chain(manifest.textures.values())
+            .chain(manifest.sprite_sheets.values())
+            .chain(manifest.animations.values())
+            .map(|path| (path.clone(), fs::metadata(path).and_then(|metadata| metadata.modified()).ok()))
+            .collect()
+    }
+}
+
+// What a successful reload swapped in
+pub struct ReloadedAssets {
+    pub atlas: TextureAtlas,
+    // Labels of the animations that were loaded before but aren't any more, sorted
+    pub removed_animations: Vec<String>,
+}
+
+// Load everything in the manifest again, and swap it in for what the game is using. Animations and textures keep
+// their ids, so live entities pick up the new versions without being touched, other than making sure they aren't
+// left on a frame the new animation doesn't have, and that newly added art (e.g. a new facing) gets picked up.
+// Animations that are no longer defined are dropped from the AnimationMap, so creatures fall back to whatever art is
+// left, and are listed in the result. If anything fails to load, nothing is replaced.
+pub fn reload_assets(manifest_path: &str, world: &mut World, resources: &mut Resources, texture_map: &mut TextureMap) -> Result<ReloadedAssets, AssetReport> {
+    let assets = load_manifest(manifest_path).and_then(|manifest| load_assets(&manifest))?;
+    let ui_animations = match texture_map.id("cursor") {
+        Some(texture) => load_cursor_animations(&AnimationMap::new(assets.animations.clone()), texture)
+            .map_err(|source| AssetReport { errors: vec![AssetError::Animations { id: "cursor".to_string(), source }] })?,
+        None => HashMap::new(),
+    };
+
+    texture_map.replace_atlas(&assets.atlas);
+
+    let mut animation_map = resources.get_mut::<AnimationMap>().expect("No AnimationMap resource");
+    let mut removed_animations: Vec<String> = animation_map.labels()
+        .filter(|label| !assets.animations.contains_key(*label))
+        .map(str::to_string)
+        .collect();
+    removed_animations.sort();
+    for label in &removed_animations {
+        animation_map.remove(label);
+    }
+    for (label, animation) in assets.animations {
+        animation_map.insert(label, animation);
+    }
+    if let Some(mut ui_animation_map) = resources.get_mut::<UIAnimationMap>() {
+        ui_animation_map.animations = ui_animations;
+    }
+
+    let mut query = <(Write<AnimatedComponent>, TryWrite<AnimationController>)>::query();
+    for (animated, controller) in query.iter_mut(world) {
+        animated.refresh(&animation_map);
+        if let Some(controller) = controller {
+            controller.mark_changed();
+        }
+    }
+
+    Ok(ReloadedAssets { atlas: assets.atlas, removed_animations })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watcher_notices_changes() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let sheet_path = dir.join("rogue.json").to_string_lossy().into_owned();
+        let manifest_path = dir.join("manifest.json").to_string_lossy().into_owned();
+        fs::write(&manifest_path, format!(r#"{{"sprite_sheets": {{"rogue": {:?}}}}}"#, sheet_path)).unwrap();
+
+        let mut watcher = AssetWatcher::new(&manifest_path);
+        assert!(!watcher.poll());
+
+        // The sprite sheet doesn't exist yet, so creating it is a change
+        fs::write(&sheet_path, "{}").unwrap();
+        assert!(watcher.poll());
+        assert!(!watcher.poll());
+
+        fs::remove_file(&sheet_path).unwrap();
+        assert!(watcher.poll());
+    }
+}
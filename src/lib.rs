@@ -0,0 +1,149 @@
+pub mod animations;
+pub mod assets;
+pub mod atlas;
+pub mod components;
+pub mod features;
+pub mod flow_field;
+pub mod hot_reload;
+pub mod map;
+pub mod map_file;
+pub mod minimap;
+pub mod pathfinding;
+pub mod placeholder;
+pub mod render;
+pub mod resource_nodes;
+pub mod systems;
+pub mod tiled;
+pub mod validate;
+pub mod visibility;
+
+use std::time::Duration;
+use macroquad::math::{Rect, Vec2};
+
+pub struct MapInformation {
+    pub width: i32,
+    pub height: i32,
+    pub tile_size: f32,
+    pub tile_scale: Vec2,
+}
+
+impl MapInformation {
+    // Size of a tile in world coordinates
+    pub fn tile_world_size(&self) -> Vec2 {
+        self.tile_scale * self.tile_size
+    }
+
+    // The tile a world position falls in. Positions off the map give tiles off the map.
+    pub fn world_to_tile(&self, position: Vec2) -> (i32, i32) {
+        let tile = (position / self.tile_world_size()).floor();
+        (tile.x as i32, tile.y as i32)
+    }
+
+    // World position of a tile's top left corner
+    pub fn tile_to_world(&self, x: i32, y: i32) -> Vec2 {
+        Vec2::new(x as f32, y as f32) * self.tile_world_size()
+    }
+
+    pub fn tile_center(&self, x: i32, y: i32) -> Vec2 {
+        self.tile_to_world(x, y) + self.tile_world_size() / 2.
+    }
+
+    // First and last tiles (inclusive) of the part of the map a view in world coordinates covers, clipped to the map.
+    // The last tile is before the first when the view is entirely off the map.
+    pub fn tiles_in_view(&self, view: Rect) -> ((i32, i32), (i32, i32)) {
+        let (min_x, min_y) = self.world_to_tile(view.point());
+        let (max_x, max_y) = self.world_to_tile(view.point() + view.size());
+        ((min_x.max(0), min_y.max(0)), (max_x.min(self.width - 1), max_y.min(self.height - 1)))
+    }
+}
+
+pub struct FrameTime {
+    pub delta: Duration,
+}
+
+// Who an entity belongs to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Faction {
+    Player,
+    Enemy,
+}
+
+impl Faction {
+    pub const ALL: [Faction; 2] = [Faction::Player, Faction::Enemy];
+
+    // The faction with the given name, as returned by to_str
+    pub fn from_name(name: &str) -> Option<Faction> {
+        Faction::ALL.into_iter().find(|faction| faction.to_str() == name)
+    }
+
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            Faction::Player => "player",
+            Faction::Enemy => "enemy",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CreatureType {
+    Rogue,
+    Goblin,
+}
+
+impl CreatureType {
+    pub const ALL: [CreatureType; 2] = [CreatureType::Rogue, CreatureType::Goblin];
+
+    // The creature type with the given name, as returned by to_str
+    pub fn from_name(name: &str) -> Option<CreatureType> {
+        CreatureType::ALL.into_iter().find(|creature_type| creature_type.to_str() == name)
+    }
+
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            CreatureType::Rogue => "rogue",
+            CreatureType::Goblin => "goblin",
+        }
+    }
+
+    // Id of the creature's sprite sheet texture in the asset manifest
+    pub fn texture_id(&self) -> &'static str {
+        match self {
+            CreatureType::Rogue => "rogue",
+            CreatureType::Goblin => "goblin",
+        }
+    }
+
+    pub fn faction(&self) -> Faction {
+        match self {
+            CreatureType::Rogue => Faction::Player,
+            CreatureType::Goblin => Faction::Enemy,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_world_tile_conversion() {
+        let map_info = MapInformation { width: 10, height: 10, tile_size: 8., tile_scale: Vec2::new(2.5, 2.5) };
+
+        assert_eq!(map_info.world_to_tile(Vec2::new(0., 0.)), (0, 0));
+        assert_eq!(map_info.world_to_tile(Vec2::new(19.9, 40.)), (0, 2));
+        assert_eq!(map_info.world_to_tile(Vec2::new(-0.1, 5.)), (-1, 0));
+        assert_eq!(map_info.tile_to_world(3, 1), Vec2::new(60., 20.));
+        assert_eq!(map_info.world_to_tile(map_info.tile_center(3, 1)), (3, 1));
+    }
+
+    #[test]
+    fn test_tiles_in_view() {
+        let map_info = MapInformation { width: 10, height: 10, tile_size: 8., tile_scale: Vec2::new(2.5, 2.5) };
+
+        assert_eq!(map_info.tiles_in_view(Rect::new(30., 10., 45., 20.)), ((1, 0), (3, 1)));
+        // Clipped to the map
+        assert_eq!(map_info.tiles_in_view(Rect::new(-100., 150., 1000., 1000.)), ((0, 7), (9, 9)));
+        let ((min_x, _), (max_x, _)) = map_info.tiles_in_view(Rect::new(500., 0., 100., 100.));
+        assert!(max_x < min_x);
+    }
+}
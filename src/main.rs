@@ -1,23 +1,25 @@
-mod map;
-mod components;
-mod systems;
-mod animations;
-
-use crate::systems::{apply_random_death_system, apply_random_movement_system};
+use liege::systems::{apply_animation_controller_system, apply_random_movement_system, update_animations_system, update_facing_system, update_resource_nodes_system, update_visibility_system};
 use std::default::Default;
-use std::collections::HashMap;
 use std::time::Duration;
-use benimator::State;
-use legion::{Entity, IntoQuery, Read, Resources, Schedule, World, Write};
+use legion::{Entity, EntityStore, IntoQuery, Read, Resources, Schedule, World, Write};
 use macroquad::color::{BLACK};
 use macroquad::prelude::*;
 use rand::Rng;
-use crate::animations::animation::{AnimationMap, AnimationStates, LiegeAnimation, LiegeSprite, LiegeUIAnimation, UIAnimationMap};
-use crate::animations::cursor::load_cursor_animations;
-use crate::animations::goblin::{load_goblin_animations};
-use crate::animations::rogue::{load_rogue_animations};
-use crate::components::{AnimatedComponent, EntityKind, DrawableComponent, MovementComponent, SelectedComponent};
-use crate::map::GameMap;
+use liege::animations::animation::{AnimationMap, AnimationStates, LiegeSprite, LiegeUIAnimation, UIAnimationMap};
+use liege::animations::controller::{AnimationController, Facing};
+use liege::animations::cursor::load_cursor_animations;
+use liege::assets::{load_assets, load_manifest, TextureId, TextureMap, ASSET_MANIFEST_PATH};
+use liege::flow_field::FlowFieldCache;
+use liege::hot_reload::{reload_assets, AssetWatcher};
+use liege::components::{AnimatedComponent, BuildingComponent, EntityKind, DrawableComponent, FacingComponent, FactionComponent, MovementComponent, MovementMode, PathComponent, ResourceNodeComponent, ScenarioPropertiesComponent, SelectedComponent, SightComponent};
+use liege::map::{Biome, Feature, GameMap, MapGenConfig, MapSource};
+use liege::map_file::{load_map, save_map, SavedMap};
+use liege::minimap::{faction_colour, Minimap, MinimapLayout};
+use liege::tiled::load_tiled_map;
+use liege::render::{draw_sprite, sprite_bounds};
+use liege::resource_nodes::{harvest_resource_node, spawn_resource_nodes, ResourceKind, ResourceNodeIndex};
+use liege::visibility::{VisibilityMap, CASTLE_SIGHT_RADIUS, CREATURE_SIGHT_RADIUS};
+use liege::{CreatureType, Faction, FrameTime, MapInformation};
 extern crate rand;
 
 const TILE_SIZE: f32 = 8.;
@@ -26,36 +28,31 @@ const TILE_SCALE: Vec2 = Vec2::new(2.5, 2.5);
 const SPRITE_SCALE: Vec2 = Vec2::new(2.5, 2.5);
 const MAP_WIDTH: i32 = 200;
 const MAP_HEIGHT: i32 = 200;
-
-struct RenderData<'a> {
+// Where F6 saves the map, and F9 loads it from
+const MAP_SAVE_PATH: &str = "saved_map.json";
+const ATLAS_DUMP_DIRECTORY: &str = "atlas_dump";
+// How often to check the asset files for changes
+const ASSET_POLL_SECONDS: f32 = 1.;
+// How much pressing H takes out of the resource node under the mouse
+const HARVEST_AMOUNT: u32 = 10;
+// Resource nodes are drawn smaller than a tile, so the ground shows around them, and faded once they're worked out
+const RESOURCE_NODE_SCALE: f32 = 0.7;
+const DEPLETED_NODE_COLOUR: Color = Color::new(1., 1., 1., 0.35);
+// Drawn over explored tiles nothing can see right now. Unexplored tiles aren't drawn at all.
+const FOG_COLOUR: Color = Color::new(0., 0., 0., 0.5);
+// Size of the dots marking entities on the minimap, in pixels
+const MINIMAP_DOT_SIZE: f32 = 3.;
+
+const USAGE: &str = "usage: liege [--seed <seed> | --map <path> | --scenario <path>]";
+
+struct RenderData {
     position: Vec2,
-    texture_handle: &'a str,
-    animation: LiegeAnimation,
-    state: State,
+    texture: TextureId,
+    frame: LiegeSprite,
+    flip_x: bool,
     selected: bool,
 }
 
-pub struct MapInformation {
-    width: i32,
-    height: i32,
-    tile_size: f32,
-    tile_scale: Vec2,
-}
-
-pub enum CreatureType {
-    Rogue,
-    Goblin,
-}
-
-impl CreatureType {
-    pub fn to_str(&self) -> &str {
-        match self {
-            CreatureType::Rogue => "rogue",
-            CreatureType::Goblin => "goblin",
-        }
-    }
-}
-
 fn conf() -> Conf {
     Conf {
         window_title: "Liege".to_string(),
@@ -65,6 +62,13 @@ fn conf() -> Conf {
     }
 }
 
+// For a command line the game can't start from. Exits rather than panicking, as the mistake is the user's.
+fn exit_with_usage(message: impl std::fmt::Display) -> ! {
+    eprintln!("{}", message);
+    eprintln!("{}", USAGE);
+    std::process::exit(2)
+}
+
 #[macroquad::main(conf)]
 async fn main() {
     let mut show_debug = false;
@@ -76,14 +80,61 @@ async fn main() {
 
     let mut rng = rand::thread_rng();
 
-    let mut texture_map = load_resources().await;
-    let mut game_map = GameMap::new(MAP_WIDTH, MAP_HEIGHT);
-    game_map.generate_noise_map();
-
-    let mut animation_map = load_rogue_animations();
-    animation_map.extend(load_goblin_animations());
+    // Load everything listed in the asset manifest, reporting every broken asset at once
+    let assets = match load_manifest(ASSET_MANIFEST_PATH).and_then(|manifest| load_assets(&manifest)) {
+        Ok(assets) => assets,
+        Err(report) => panic!("{}", report),
+    };
+    for placeholder in &assets.placeholders {
+        eprintln!("Using a placeholder for missing {}", placeholder);
+    }
+    let mut texture_map = TextureMap::from_atlas(&assets.atlas);
+    let mut atlas = assets.atlas;
+    let biome_textures = Biome::ALL.map(|biome| texture_map.id(biome.texture_id()).expect("Missing a biome tile set in the asset manifest"));
+    let feature_textures = Feature::ALL.map(|feature| texture_map.id(feature.texture_id()).expect("Missing a feature tile set in the asset manifest"));
+    let resource_textures = ResourceKind::ALL.map(|kind| texture_map.id(kind.texture_id()).expect("Missing a resource node texture in the asset manifest"));
+    let edge_textures = Biome::ALL.map(|biome| biome.edge_texture_id().map(|id| texture_map.id(id).expect("Missing a biome edge tile set in the asset manifest")));
+    let mut asset_watcher = AssetWatcher::new(ASSET_MANIFEST_PATH);
+    let mut asset_poll_timer = 0.;
+
+    // Load the map or scenario given on the command line (`--map <path>` or `--scenario <path>`), or generate one from
+    // the seed given (`--seed <seed>`) or a random one. The seed is shown in the debug overlay, so a map can be
+    // generated again. Saved maps carry on with what had been explored when they were saved.
+    let mut scenario_objects = None;
+    let mut saved_visibility = None;
+    let game_map = match MapSource::from_args(std::env::args().skip(1)) {
+        Ok(MapSource::File(path)) => match load_map(&path) {
+            Ok(saved) => {
+                saved_visibility = Some(saved.visibility);
+                saved.game_map
+            }
+            Err(e) => exit_with_usage(format!("Failed to load map {}: {}", path, e)),
+        },
+        Ok(MapSource::Scenario(path)) => match load_tiled_map(&path) {
+            Ok(scenario) => {
+                scenario_objects = Some(scenario.objects);
+                scenario.game_map
+            }
+            Err(e) => exit_with_usage(format!("Failed to load scenario {}: {}", path, e)),
+        },
+        Ok(source) => {
+            let map_config = match source {
+                MapSource::Generate(config) => config,
+                _ => MapGenConfig::with_seed(rng.gen()),
+            };
+            let mut game_map = GameMap::new(MAP_WIDTH, MAP_HEIGHT, map_config);
+            game_map.generate_terrain();
+            game_map
+        }
+        Err(e) => exit_with_usage(e),
+    };
 
-    let mut ui_animation_map = load_cursor_animations();
+    let animation_map = AnimationMap::new(assets.animations);
+    let cursor_texture = texture_map.id("cursor").expect("No cursor texture in the asset manifest");
+    let ui_animation_map = match load_cursor_animations(&animation_map, cursor_texture) {
+        Ok(animations) => animations,
+        Err(e) => panic!("Failed to load cursor animations: {}", e),
+    };
 
     let mut camera = Camera2D {
         target: vec2(screen_width() / 2., screen_height() / 2.),
@@ -94,158 +145,295 @@ async fn main() {
     let mut world = World::default();
     let mut resources = Resources::default();
 
-    resources.insert(MapInformation{width: MAP_WIDTH, height: MAP_HEIGHT, tile_size: TILE_SIZE, tile_scale: TILE_SCALE});
-    resources.insert(AnimationMap{animations: animation_map});
+    let map_info = MapInformation{width: game_map.map_width, height: game_map.map_height, tile_size: TILE_SIZE, tile_scale: TILE_SCALE};
+    // Start everyone off at the castle site, with the camera on it. Maps without one start on dry land, as near to the
+    // top left as there is some.
+    let start_position = match game_map.castle_site {
+        Some((x, y)) => {
+            camera.target = map_info.tile_center(x, y);
+            map_info.tile_to_world(x, y)
+        }
+        None => {
+            let (start_x, start_y) = map_info.world_to_tile(Vec2::new(100., 100.));
+            match game_map.nearest_passable(start_x, start_y) {
+                Some((x, y)) => map_info.tile_to_world(x, y),
+                None => Vec2::new(100., 100.),
+            }
+        }
+    };
+
+    resources.insert(spawn_resource_nodes(&mut world, &game_map));
+    spawn_castle(&mut world, &game_map);
+    resources.insert(saved_visibility.unwrap_or_else(|| VisibilityMap::new(map_info.width, map_info.height)));
+    resources.insert(map_info);
+    resources.insert(game_map);
+    resources.insert(FlowFieldCache::default());
+    resources.insert(animation_map);
     resources.insert(UIAnimationMap{animations: ui_animation_map});
 
-    for _ in 0..5 {
-        // Create a single rogue entity
-        if let Some(mut animation_mapping) = resources.get_mut::<AnimationMap>() {
-            let animation = animation_mapping.animations.get("rogue_idle_right").unwrap().clone();
-            world.push(
-                (
-                    DrawableComponent {
-                        position: Vec2::new(100., 100.),
-                        texture_handle: "resources/characters/rogue/rogue.png"
-                    },
-                    AnimatedComponent {
-                        animated_sprite_label: "rogue_idle_right".to_string(),
-                        liege_animation: animation,
-                        animation_state: State::new()
-                    },
-                    MovementComponent{ destination: Vec2::ZERO, speed: 0.5},
-                    EntityKind { kind: CreatureType::Rogue.to_str() },
-                    SelectedComponent { selected: false }
-                )
-            );
+    if let Some(animation_mapping) = resources.get::<AnimationMap>() {
+        match &scenario_objects {
+            // Scenarios place their own creatures
+            Some(objects) => {
+                let map_info = resources.get::<MapInformation>().unwrap();
+                for object in objects {
+                    if let Some((creature_type, count)) = object.spawns() {
+                        for _ in 0..count {
+                            let creature = spawn_creature(&mut world, &animation_mapping, &texture_map, creature_type, map_info.tile_center(object.tile.0, object.tile.1));
+                            world.entry(creature).unwrap().add_component(ScenarioPropertiesComponent { properties: object.properties.clone() });
+                        }
+                    }
+                }
+            }
+            None => {
+                for _ in 0..5 {
+                    spawn_creature(&mut world, &animation_mapping, &texture_map, CreatureType::Rogue, start_position);
+                }
+                for _ in 0..5 {
+                    spawn_creature(&mut world, &animation_mapping, &texture_map, CreatureType::Goblin, start_position);
+                }
+            }
         }
     }
-
-    for _ in 0..5 {
-        // Create a single goblin entity
-        if let Some(mut animation_mapping) = resources.get_mut::<AnimationMap>() {
-            let animation = animation_mapping.animations.get("goblin_idle_right").unwrap().clone();
-            world.push(
-                (
-                    DrawableComponent {
-                        position: Vec2::new(100., 100.),
-                        texture_handle: "resources/characters/goblin/goblin.png"
-                    },
-                    AnimatedComponent {
-                        animated_sprite_label: "goblin_idle_right".to_string(),
-                        liege_animation: animation,
-                        animation_state: State::new()
-                    },
-                    MovementComponent{ destination: Vec2::ZERO, speed: 0.5},
-                    EntityKind { kind: CreatureType::Goblin.to_str() },
-                    SelectedComponent { selected: false }
-                )
-            );
-        }
+    // Buildings can see around themselves, and lairs have nothing to spawn yet, so they're only outlined in the debug
+    // overlay
+    let scenario_objects = scenario_objects.unwrap_or_default();
+    for object in &scenario_objects {
+        let Some((faction, sight)) = object.building() else { continue };
+        world.push((
+            BuildingComponent { tile: object.tile, size: object.size },
+            SightComponent { radius: sight },
+            FactionComponent { faction },
+            ScenarioPropertiesComponent { properties: object.properties.clone() },
+        ));
     }
 
     let mut schedule = Schedule::builder()
+        .add_system(update_animations_system())
+        .flush()
         .add_system(apply_random_movement_system())
         //.add_system(apply_random_death_system())
+        .add_system(update_facing_system())
+        .add_system(apply_animation_controller_system())
+        .add_system(update_resource_nodes_system())
+        .add_system(update_visibility_system())
         .build();
 
     // Build a vector of current UI animations that will be run every frame
     let mut current_ui_animations: Vec<LiegeUIAnimation> = Vec::new();
 
+    // The minimap's texture is updated whenever its image changes, and only remade if the map's size does
+    let mut minimap = Minimap::default();
+    let mut minimap_texture: Option<Texture2D> = None;
+    let mut dragging_minimap = false;
+
     loop {
         clear_background(BLACK);
 
         // Execute all systems
         if !paused {
+            // Weird: We have to convert to nanoseconds, or we lose precision in the Duration object, and our animations
+            // never update.
+            let nanos = (get_frame_time() * 1_000_000_000.0) as u64;
+            resources.insert(FrameTime { delta: Duration::from_nanos(nanos) });
             schedule.execute(&mut world, &mut resources);
         }
 
+        // Reload the assets whenever their files change, or on demand
+        let mut reload = is_key_pressed(KeyCode::F5);
+        asset_poll_timer += get_frame_time();
+        if asset_poll_timer >= ASSET_POLL_SECONDS {
+            asset_poll_timer = 0.;
+            reload |= asset_watcher.poll();
+        }
+        if reload {
+            match reload_assets(ASSET_MANIFEST_PATH, &mut world, &mut resources, &mut texture_map) {
+                Ok(reloaded) => {
+                    atlas = reloaded.atlas;
+                    if !reloaded.removed_animations.is_empty() {
+                        eprintln!("Animations removed by the reload: {}", reloaded.removed_animations.join(", "));
+                    }
+                }
+                Err(report) => eprintln!("Failed to reload assets, carrying on with the old ones: {}", report),
+            }
+        }
+
         set_camera(&camera);
 
-        for i in 0..game_map.noise_map.len() {
-            let tile_index = game_map.noise_map[i];
+        // Only the tiles the camera can see are drawn, found from the chunks under the view
+        let game_map = resources.get::<GameMap>().expect("No GameMap resource");
+        let visibility = resources.get::<VisibilityMap>().expect("No VisibilityMap resource");
+        let (first_tile, last_tile) = resources.get::<MapInformation>().unwrap().tiles_in_view(camera_view(&camera));
+        if minimap.refresh(&game_map, &visibility) {
+            let image = minimap.image();
+            match &minimap_texture {
+                Some(texture) if texture.size() == vec2(image.width as f32, image.height as f32) => texture.update(image),
+                _ => {
+                    let texture = Texture2D::from_image(image);
+                    texture.set_filter(FilterMode::Nearest);
+                    minimap_texture = Some(texture);
+                }
+            }
+        }
+        for ((x, y), tile) in game_map.tiles_in(first_tile, last_tile) {
+            if !visibility.is_explored(x, y) {
+                continue;
+            }
+            let texture = biome_textures[tile.biome as usize];
+            let region = texture_map.region(texture);
+            let draw_params = DrawTextureParams{
+                source: Option::from(Rect::new(region.x + (tile.variant as f32) * TILE_SIZE, region.y, TILE_SIZE, TILE_SIZE)),
+                dest_size: Option::from(TILE_SCALE * vec2(TILE_SIZE, TILE_SIZE)),
+                ..Default::default()
+            };
+            draw_texture_ex(
+                texture_map.get(texture),
+                (x as f32) * (TILE_SIZE * TILE_SCALE.x),
+                (y as f32) * (TILE_SIZE * TILE_SCALE.y),
+                WHITE,
+                draw_params)
+            ;
+        }
+        // Then the edges of neighbouring biomes over the top, from the render layer. Edge tiles are in the first row of
+        // an edge tile set, and corner tiles in the second.
+        for ((x, y), transition) in game_map.transitions_in(first_tile, last_tile) {
+            let Some(texture) = edge_textures[transition.biome as usize] else { continue };
+            if !visibility.is_explored(x, y) {
+                continue;
+            }
+            let region = texture_map.region(texture);
+            for (mask, row) in [(transition.edges, 0.), (transition.corners, 1.)] {
+                if mask == 0 {
+                    continue;
+                }
+                let draw_params = DrawTextureParams{
+                    source: Option::from(Rect::new(region.x + (mask as f32) * TILE_SIZE, region.y + row * TILE_SIZE, TILE_SIZE, TILE_SIZE)),
+                    dest_size: Option::from(TILE_SCALE * vec2(TILE_SIZE, TILE_SIZE)),
+                    ..Default::default()
+                };
+                draw_texture_ex(texture_map.get(texture), (x as f32) * (TILE_SIZE * TILE_SCALE.x), (y as f32) * (TILE_SIZE * TILE_SCALE.y), WHITE, draw_params);
+            }
+        }
+        // Rivers, lakes, roads and bridges on top of everything
+        for ((x, y), tile) in game_map.tiles_in(first_tile, last_tile) {
+            let Some(feature) = tile.feature else { continue };
+            if !visibility.is_explored(x, y) {
+                continue;
+            }
+            let texture = feature_textures[feature as usize];
+            let region = texture_map.region(texture);
+            let draw_params = DrawTextureParams{
+                source: Option::from(Rect::new(region.x, region.y, TILE_SIZE, TILE_SIZE)),
+                dest_size: Option::from(TILE_SCALE * vec2(TILE_SIZE, TILE_SIZE)),
+                ..Default::default()
+            };
+            draw_texture_ex(texture_map.get(texture), (x as f32) * (TILE_SIZE * TILE_SCALE.x), (y as f32) * (TILE_SIZE * TILE_SCALE.y), WHITE, draw_params);
+        }
+        // Then resource nodes, in the middle of their tiles
+        let node_size = TILE_SCALE * TILE_SIZE * RESOURCE_NODE_SCALE;
+        for entity in resources.get::<ResourceNodeIndex>().expect("No ResourceNodeIndex resource").nodes_in(first_tile, last_tile) {
+            let Ok(entry) = world.entry_ref(entity) else { continue };
+            let Ok(node) = entry.get_component::<ResourceNodeComponent>() else { continue };
+            let (x, y) = node.tile;
+            if !visibility.is_explored(x, y) {
+                continue;
+            }
+            let texture = resource_textures[node.kind as usize];
+            let region = texture_map.region(texture);
             let draw_params = DrawTextureParams{
-                source: Option::from(Rect::new((tile_index as f32) * TILE_SIZE, 0., TILE_SIZE, TILE_SIZE)),
-                dest_size: Option::from((TILE_SCALE * vec2(TILE_SIZE, TILE_SIZE))),
+                source: Option::from(Rect::new(region.x, region.y, TILE_SIZE, TILE_SIZE)),
+                dest_size: Option::from(node_size),
                 ..Default::default()
             };
-            let (x, y) = game_map.map_coords(i as i32);
-            if is_object_in_view((x as f32) * (TILE_SIZE * TILE_SCALE.x), (y as f32) * (TILE_SIZE * TILE_SCALE.y), &camera) {
-                draw_texture_ex(
-                    texture_map.get("resources/map/plains.png").unwrap(),
-                    (x as f32) * (TILE_SIZE * TILE_SCALE.x),
-                    (y as f32) * (TILE_SIZE * TILE_SCALE.y),
-                    WHITE,
-                    draw_params)
-                ;
+            let corner = vec2(x as f32 + 0.5, y as f32 + 0.5) * TILE_SCALE * TILE_SIZE - node_size / 2.;
+            let colour = if node.is_depleted() { DEPLETED_NODE_COLOUR } else { WHITE };
+            draw_texture_ex(texture_map.get(texture), corner.x, corner.y, colour, draw_params);
+        }
+        // Darken whatever has been seen before but is out of sight now
+        for ((x, y), _) in game_map.tiles_in(first_tile, last_tile) {
+            if visibility.is_explored(x, y) && !visibility.is_visible(x, y) {
+                draw_rectangle((x as f32) * (TILE_SIZE * TILE_SCALE.x), (y as f32) * (TILE_SIZE * TILE_SCALE.y), TILE_SIZE * TILE_SCALE.x, TILE_SIZE * TILE_SCALE.y, FOG_COLOUR);
+            }
+        }
+        if show_debug {
+            for object in &scenario_objects {
+                let corner = vec2(object.tile.0 as f32, object.tile.1 as f32) * TILE_SCALE * TILE_SIZE;
+                let size = vec2(object.size.0 as f32, object.size.1 as f32) * TILE_SCALE * TILE_SIZE;
+                draw_rectangle_lines(corner.x, corner.y, size.x, size.y, 2.0, YELLOW);
+                draw_text(&object.name, corner.x, corner.y - 2., 16., YELLOW);
+            }
+            // The sites the map's roads were laid between
+            let sites = game_map.castle_site.iter().map(|&site| (site, "castle site")).chain(game_map.points_of_interest.iter().map(|&site| (site, "point of interest")));
+            for ((x, y), label) in sites {
+                let corner = vec2(x as f32, y as f32) * TILE_SCALE * TILE_SIZE;
+                draw_rectangle_lines(corner.x, corner.y, TILE_SIZE * TILE_SCALE.x, TILE_SIZE * TILE_SCALE.y, 2.0, ORANGE);
+                draw_text(label, corner.x, corner.y - 2., 16., ORANGE);
             }
         }
+        let map_seed = game_map.config.seed;
+        drop(game_map);
 
+        // Enemies only show up while something of the player's can see them
         let mut render_data = Vec::new();
-        let mut query = <(Read<DrawableComponent>, Read<AnimatedComponent>, Read<SelectedComponent>)>::query();
-        for (drawable, animated, selected) in query.iter(&world) {
-            render_data.push(RenderData {
-                position: drawable.position,
-                texture_handle: drawable.texture_handle,
-                animation: animated.liege_animation.clone(),
-                state: animated.animation_state.clone(),
-                selected: selected.selected,
-            });
+        if let Some(animation_mapping) = resources.get::<AnimationMap>() {
+            let map_info = resources.get::<MapInformation>().unwrap();
+            let mut query = <(Read<DrawableComponent>, Read<AnimatedComponent>, Read<SelectedComponent>, Read<FactionComponent>)>::query();
+            for (drawable, animated, selected, owner) in query.iter(&world) {
+                let (x, y) = map_info.world_to_tile(drawable.position);
+                if owner.faction != Faction::Player && !visibility.is_visible(x, y) {
+                    continue;
+                }
+                render_data.push(RenderData {
+                    position: drawable.position,
+                    texture: drawable.texture,
+                    frame: *animated.current_frame(&animation_mapping),
+                    flip_x: animated.flip_x,
+                    selected: selected.selected,
+                });
+            }
         }
 
+        drop(visibility);
+
         for data in render_data {
             if is_sprite_in_view(data.position.x + (SPRITE_SIZE * SPRITE_SCALE.x), data.position.y + (SPRITE_SIZE * SPRITE_SCALE.y), &camera) {
-                let frame: LiegeSprite = data.animation.frames[data.state.frame_index()].clone();
-                let source_rect = Rect::new(frame.frame.x as f32, frame.frame.y as f32, frame.frame.w as f32, frame.frame.h as f32);
-                let draw_params = DrawTextureParams{
-                    source: Some(source_rect),
-                    dest_size: Option::from((SPRITE_SCALE * vec2(frame.source_size.w as f32, frame.source_size.h as f32))),
-                    ..Default::default()
-                };
-
-                draw_texture_ex(texture_map.get(data.texture_handle).unwrap(), data.position.x, data.position.y, WHITE, draw_params);
+                draw_sprite(texture_map.get(data.texture), &data.frame, data.position, SPRITE_SCALE, data.flip_x);
 
                 if data.selected {
                     // This entity is currently selected by the player, draw a selection rectangle around the entity sprite
-                    let scaled_size = SPRITE_SCALE * vec2(frame.frame.w as f32, frame.frame.h as f32);
+                    let scaled_size = sprite_bounds(&data.frame, SPRITE_SCALE);
                     draw_rectangle_lines(data.position.x, data.position.y, scaled_size.x, scaled_size.y, 2.0, RED);
                 }
             }
         }
 
-        // Draw our custom mouse cursor
-        let cur_mouse_position = camera.screen_to_world(Vec2::from(mouse_position()));
-        if let Some(mut animation_mapping) = resources.get_mut::<UIAnimationMap>() {
-            if let Some(cursor_animation) = animation_mapping.animations.get("cursor_idle") {
-                let frame: LiegeSprite = cursor_animation.frames[0].clone();
-                let source_rect = Rect::new(frame.frame.x as f32, frame.frame.y as f32, frame.frame.w as f32, frame.frame.h as f32);
-                let draw_params = DrawTextureParams{
-                    source: Some(source_rect),
-                    dest_size: Option::from((SPRITE_SCALE * vec2(frame.source_size.w as f32, frame.source_size.h as f32))),
-                    ..Default::default()
-                };
-
-                draw_texture_ex(texture_map.get("resources/ui/cursor/cursor.png").unwrap(), cur_mouse_position.x, cur_mouse_position.y, WHITE, draw_params);
-            }
-
+        // Clicking or dragging on the minimap moves the camera there, rather than selecting or spawning anything
+        let minimap_layout = MinimapLayout::new(&resources.get::<MapInformation>().unwrap(), vec2(screen_width(), screen_height()));
+        let screen_mouse_position = Vec2::from(mouse_position());
+        if is_mouse_button_pressed(MouseButton::Left) && minimap_layout.contains(screen_mouse_position) {
+            dragging_minimap = true;
+        } else if !is_mouse_button_down(MouseButton::Left) {
+            dragging_minimap = false;
         }
 
         // Check for mouse clicks, capture the position
-        if is_mouse_button_pressed(MouseButton::Left) {
+        if dragging_minimap {
+            camera.target = minimap_layout.minimap_to_world(screen_mouse_position);
+        } else if is_mouse_button_pressed(MouseButton::Left) {
             let click_position = camera.screen_to_world(Vec2::from(mouse_position()));
 
             // Before we do anything else, kick off a new UI animation for the click
-            if let Some(mut ui_animation_mapping) = resources.get_mut::<UIAnimationMap>() {
+            if let Some(ui_animation_mapping) = resources.get_mut::<UIAnimationMap>() {
                 let mut click_anim = ui_animation_mapping.animations.get("cursor_click").unwrap().clone();
                 click_anim.position = click_position - 8.;
                 current_ui_animations.push(click_anim);
             }
 
+            let animation_mapping = resources.get::<AnimationMap>().unwrap();
             let mut query = <(Read<DrawableComponent>, Read<AnimatedComponent>, Read<EntityKind>, Write<SelectedComponent>)>::query();
-            for (drawable, animated, kind, selected) in query.iter_mut(&mut world) {
+            for (drawable, animated, _kind, selected) in query.iter_mut(&mut world) {
                 // Check all entities for the coordinates of the mouse click
-                let frame = animated.liege_animation.frames[animated.animation_state.frame_index()];
-                let scaled_size = SPRITE_SCALE * vec2(frame.frame.w as f32, frame.frame.h as f32);
+                let scaled_size = sprite_bounds(animated.current_frame(&animation_mapping), SPRITE_SCALE);
                 if click_position.x >= drawable.position.x && click_position.x <= drawable.position.x + scaled_size.x &&
                     click_position.y >= drawable.position.y && click_position.y <= drawable.position.y + scaled_size.y {
                     selected.selected = true;
@@ -255,52 +443,16 @@ async fn main() {
                     selected.selected = false;
                 }
             }
-        } else if is_mouse_button_pressed(MouseButton::Right) {
+        } else if is_mouse_button_pressed(MouseButton::Right) && !minimap_layout.contains(screen_mouse_position) {
             // Spawn a new random entity (Rogue or Goblin, currently)
             let random_choice = rng.gen_range(0..2);
             let click_position = camera.screen_to_world(Vec2::from(mouse_position()));
-            if random_choice == 0 {
-                // Create a Rogue
-                if let Some(mut animation_mapping) = resources.get_mut::<AnimationMap>() {
-                    let animation = animation_mapping.animations.get("rogue_idle_right").unwrap().clone();
-                    world.push(
-                        (
-                            DrawableComponent {
-                                position: Vec2::new(click_position.x, click_position.y),
-                                texture_handle: "resources/characters/rogue/rogue.png"
-                            },
-                            AnimatedComponent {
-                                animated_sprite_label: "rogue_idle_right".to_string(),
-                                liege_animation: animation,
-                                animation_state: State::new()
-                            },
-                            MovementComponent{ destination: Vec2::ZERO, speed: 0.5},
-                            EntityKind { kind: CreatureType::Rogue.to_str() },
-                            SelectedComponent { selected: false }
-                        )
-                    );
-                }
-            } else {
-                // Create a Goblin
-                if let Some(mut animation_mapping) = resources.get_mut::<AnimationMap>() {
-                    let animation = animation_mapping.animations.get("goblin_idle_right").unwrap().clone();
-                    world.push(
-                        (
-                            DrawableComponent {
-                                position: Vec2::new(click_position.x, click_position.y),
-                                texture_handle: "resources/characters/goblin/goblin.png"
-                            },
-                            AnimatedComponent {
-                                animated_sprite_label: "goblin_idle_right".to_string(),
-                                liege_animation: animation,
-                                animation_state: State::new()
-                            },
-                            MovementComponent{ destination: Vec2::ZERO, speed: 0.5},
-                            EntityKind { kind: CreatureType::Goblin.to_str() },
-                            SelectedComponent { selected: false }
-                        )
-                    );
-                }
+            let creature_type = if random_choice == 0 { CreatureType::Rogue } else { CreatureType::Goblin };
+            // Nothing can be spawned anywhere it couldn't walk
+            let (tile_x, tile_y) = resources.get::<MapInformation>().unwrap().world_to_tile(click_position);
+            let passable = resources.get::<GameMap>().is_some_and(|game_map| game_map.is_passable(tile_x, tile_y));
+            if let (true, Some(animation_mapping)) = (passable, resources.get::<AnimationMap>()) {
+                spawn_creature(&mut world, &animation_mapping, &texture_map, creature_type, click_position);
             }
         }
 
@@ -322,18 +474,69 @@ async fn main() {
         if is_key_down(KeyCode::LeftShift) && is_key_down(KeyCode::D) {
             show_debug = !show_debug;
         }
+        if is_key_pressed(KeyCode::G) {
+            // Send everything to the tile under the mouse, all steering along one shared flow field
+            let map_info = resources.get::<MapInformation>().unwrap();
+            let goal = map_info.world_to_tile(camera.screen_to_world(Vec2::from(mouse_position())));
+            if resources.get::<GameMap>().is_some_and(|game_map| game_map.is_passable(goal.0, goal.1)) {
+                let mut query = <(Write<MovementComponent>, Write<PathComponent>)>::query();
+                for (movement, path) in query.iter_mut(&mut world) {
+                    movement.mode = MovementMode::FlowField { goal };
+                    movement.destination = map_info.tile_center(goal.0, goal.1);
+                    *path = PathComponent::default();
+                }
+            }
+        }
+        if is_key_pressed(KeyCode::H) {
+            // Harvest from the resource node under the mouse
+            let tile = resources.get::<MapInformation>().unwrap().world_to_tile(camera.screen_to_world(Vec2::from(mouse_position())));
+            let mut resource_node_index = resources.get_mut::<ResourceNodeIndex>().expect("No ResourceNodeIndex resource");
+            if let Some(entity) = resource_node_index.at(tile) {
+                harvest_resource_node(&mut world, &mut resource_node_index, entity, HARVEST_AMOUNT);
+            }
+        }
+        if is_key_pressed(KeyCode::F6) {
+            if let (Some(game_map), Some(visibility)) = (resources.get::<GameMap>(), resources.get::<VisibilityMap>()) {
+                match save_map(&game_map, &visibility, MAP_SAVE_PATH) {
+                    Ok(()) => eprintln!("Saved the map to {}", MAP_SAVE_PATH),
+                    Err(e) => eprintln!("Failed to save the map: {}", e),
+                }
+            }
+        }
+        if is_key_pressed(KeyCode::F9) {
+            match load_map(MAP_SAVE_PATH) {
+                Ok(SavedMap { game_map, visibility }) => {
+                    // Cached flow fields, resource nodes, buildings and what's been explored belong to the old map, so
+                    // they go with it, and the saved map's exploration comes back
+                    let old_nodes: Vec<Entity> = <(Entity, Read<ResourceNodeComponent>)>::query().iter(&world).map(|(entity, _)| *entity).collect();
+                    let old_buildings: Vec<Entity> = <(Entity, Read<BuildingComponent>)>::query().iter(&world).map(|(entity, _)| *entity).collect();
+                    for entity in old_nodes.into_iter().chain(old_buildings) {
+                        world.remove(entity);
+                    }
+                    resources.insert(spawn_resource_nodes(&mut world, &game_map));
+                    spawn_castle(&mut world, &game_map);
+                    resources.insert(visibility);
+                    resources.insert(MapInformation{width: game_map.map_width, height: game_map.map_height, tile_size: TILE_SIZE, tile_scale: TILE_SCALE});
+                    resources.insert(game_map);
+                    resources.insert(FlowFieldCache::default());
+                    minimap = Minimap::default();
+                }
+                Err(e) => eprintln!("Failed to load the map from {}: {}", MAP_SAVE_PATH, e),
+            }
+        }
+        if is_key_pressed(KeyCode::F12) {
+            // Dump the texture atlas, to check how everything was packed
+            match atlas.dump(ATLAS_DUMP_DIRECTORY) {
+                Ok(paths) => eprintln!("Wrote texture atlas to {}", paths.join(", ")),
+                Err(e) => eprintln!("Failed to write texture atlas: {}", e),
+            }
+        }
 
         // Run any UI animtations
         let nanos = (get_frame_time() * 1_000_000_000.0) as u64;
-        for mut animation in current_ui_animations.iter_mut() {
-            let frame: LiegeSprite = animation.frames[animation.state.frame_index()].clone();
-            let source_rect = Rect::new(frame.frame.x as f32, frame.frame.y as f32, frame.frame.w as f32, frame.frame.h as f32);
-            let draw_params = DrawTextureParams{
-                source: Some(source_rect),
-                dest_size: Option::from((SPRITE_SCALE * vec2(frame.source_size.w as f32, frame.source_size.h as f32))),
-                ..Default::default()
-            };
-            draw_texture_ex(texture_map.get(animation.texture_handle).unwrap(), animation.position.x, animation.position.y, WHITE, draw_params);
+        for animation in current_ui_animations.iter_mut() {
+            let frame: LiegeSprite = animation.frames[animation.state.frame_index()];
+            draw_sprite(texture_map.get(animation.texture), &frame, animation.position, SPRITE_SCALE, false);
 
             animation.state.update(&animation.animation, Duration::from_nanos(nanos));
         }
@@ -342,6 +545,19 @@ async fn main() {
 
         set_default_camera();
 
+        if let Some(texture) = &minimap_texture {
+            draw_minimap(&minimap_layout, texture, &world, &resources, &camera);
+        }
+
+        // Draw our custom mouse cursor, over the minimap. Screen space is the same scale as the world.
+        if let Some(animation_mapping) = resources.get_mut::<UIAnimationMap>() {
+            if let Some(cursor_animation) = animation_mapping.animations.get("cursor_idle") {
+                let frame: LiegeSprite = cursor_animation.frames[0];
+                draw_sprite(texture_map.get(cursor_texture), &frame, screen_mouse_position, SPRITE_SCALE, false);
+            }
+
+        }
+
         if show_debug {
             // Debug information, printed in screen space
             let entity_count = world.len();
@@ -349,69 +565,96 @@ async fn main() {
             draw_text(&format!("Entity Count: {}", entity_count), 10., 31., 20., WHITE);
             draw_text(&format!("Current Mouse Position (Screen): {}", Vec2::from(mouse_position())), 10., 42., 20., WHITE);
             draw_text(&format!("Current Mouse Position (World): {}", camera.screen_to_world(Vec2::from(mouse_position()))), 10., 53., 20., WHITE);
+            draw_text(&format!("Map Seed: {}", map_seed), 10., 64., 20., WHITE);
 
             let mut query = <(Entity, Read<EntityKind>, Read<SelectedComponent>)>::query();
             for (entity, kind, selected) in query.iter(&world) {
                 if selected.selected {
-                    draw_text(&format!("Selected Entity: {:?}, {}", entity, kind.kind), 10., 64., 20., WHITE);
+                    draw_text(&format!("Selected Entity: {:?}, {}", entity, kind.kind), 10., 75., 20., WHITE);
                 }
             }
         }
 
-        let nanos = (get_frame_time() * 1_000_000_000.0) as u64;
-        if !paused {
-            let mut query = <(Write<AnimatedComponent>)>::query();
-            for mut animated in query.iter_mut(&mut world) {
-                // Weird: We have to convert to nanoseconds, or we lose precision in the Duration object, and our animations
-                // never update.
-                animated.animation_state.update(&animated.liege_animation.animation, Duration::from_nanos(nanos));
-            }
-        }
-
         next_frame().await;
     }
 }
 
-async fn load_resources() -> HashMap<String, Texture2D> {
-    let mut texture_assets: HashMap<String, Texture2D> = HashMap::new();
-    let texture_paths = vec![
-        "resources/map/grass_tiles.png",
-        "resources/map/plains.png",
-        "resources/characters/rogue/rogue.png",
-        "resources/characters/goblin/goblin.png",
-        "resources/ui/cursor/cursor.png",
-    ];
-
-    for path in texture_paths {
-        let texture = load_texture(path).await.unwrap();
-        texture.set_filter(FilterMode::Nearest);
-        texture_assets.insert(path.to_string(), texture);
-    }
-
-    // TODO: This seems to not work, find out why --seems to be a known issue with macroquad
-    // https://github.com/not-fl3/macroquad/issues/659
-    // build_textures_atlas();
+fn spawn_creature(world: &mut World, animation_mapping: &AnimationMap, texture_map: &TextureMap, creature_type: CreatureType, position: Vec2) -> Entity {
+    let controller = AnimationController::new(AnimationStates::Idle, Facing::Right);
+    let (animation, flip_x) = controller.resolve_animation(animation_mapping, creature_type.to_str()).unwrap();
+    let mut animated = AnimatedComponent::new(animation);
+    animated.flip_x = flip_x;
+    let entity = world.push(
+        (
+            DrawableComponent {
+                position,
+                texture: texture_map.id(creature_type.texture_id()).unwrap()
+            },
+            animated,
+            controller,
+            FacingComponent { facing: Facing::Right },
+            MovementComponent{ destination: Vec2::ZERO, speed: 0.5, velocity: Vec2::ZERO, mode: MovementMode::Path },
+            PathComponent::default(),
+            EntityKind { kind: creature_type.to_str() },
+            SelectedComponent { selected: false }
+        )
+    );
+    // Legion only pushes up to eight components at once
+    let mut entry = world.entry(entity).unwrap();
+    entry.add_component(FactionComponent { faction: creature_type.faction() });
+    entry.add_component(SightComponent { radius: CREATURE_SIGHT_RADIUS });
+    entity
+}
 
-    texture_assets
+// The player's castle, on the map's castle site if it has one
+fn spawn_castle(world: &mut World, game_map: &GameMap) -> Option<Entity> {
+    let tile = game_map.castle_site?;
+    Some(world.push((
+        BuildingComponent { tile, size: (1, 1) },
+        SightComponent { radius: CASTLE_SIGHT_RADIUS },
+        FactionComponent { faction: Faction::Player },
+    )))
 }
 
-fn is_object_in_view(x: f32, y:f32, camera: &Camera2D) -> bool {
-    // Calculate the world coordinates of the camera's view boundaries
-    let screen_half_width = (screen_width() / 2.0);
-    let screen_half_height = (screen_height() / 2.0);
+// The map in the corner of the screen, with dots for everything the player owns and any enemies in sight, and the
+// part of the map the camera is on outlined
+fn draw_minimap(layout: &MinimapLayout, texture: &Texture2D, world: &World, resources: &Resources, camera: &Camera2D) {
+    let map_info = resources.get::<MapInformation>().unwrap();
+    let visibility = resources.get::<VisibilityMap>().unwrap();
+    let rect = layout.rect;
+    draw_texture_ex(texture, rect.x, rect.y, WHITE, DrawTextureParams { dest_size: Some(rect.size()), ..Default::default() });
+
+    let creatures = <(Read<DrawableComponent>, Read<FactionComponent>)>::query()
+        .iter(world)
+        .map(|(drawable, owner)| (map_info.world_to_tile(drawable.position), owner.faction))
+        .collect::<Vec<_>>();
+    let buildings = <(Read<BuildingComponent>, Read<FactionComponent>)>::query()
+        .iter(world)
+        .map(|(building, owner)| (building.centre(), owner.faction))
+        .collect::<Vec<_>>();
+    for ((x, y), faction) in creatures.into_iter().chain(buildings) {
+        if faction != Faction::Player && !visibility.is_visible(x, y) {
+            continue;
+        }
+        let dot = layout.world_to_minimap(map_info.tile_center(x, y)) - MINIMAP_DOT_SIZE / 2.;
+        draw_rectangle(dot.x, dot.y, MINIMAP_DOT_SIZE, MINIMAP_DOT_SIZE, faction_colour(faction));
+    }
 
-    let left_bound = camera.target.x - screen_half_width - (TILE_SIZE * TILE_SCALE.x);
-    let right_bound = camera.target.x + screen_half_width;
-    let top_bound = camera.target.y - screen_half_height - (TILE_SIZE * TILE_SCALE.y);
-    let bottom_bound = camera.target.y + screen_half_height;
+    if let Some(view) = layout.view_to_minimap(camera_view(camera)).intersect(rect) {
+        draw_rectangle_lines(view.x, view.y, view.w, view.h, 1.0, WHITE);
+    }
+    draw_rectangle_lines(rect.x - 1., rect.y - 1., rect.w + 2., rect.h + 2., 2.0, GRAY);
+}
 
-    x > left_bound && x < right_bound && y > top_bound && y < bottom_bound
+// The part of the world the camera can see, in world coordinates
+fn camera_view(camera: &Camera2D) -> Rect {
+    Rect::new(camera.target.x - screen_width() / 2., camera.target.y - screen_height() / 2., screen_width(), screen_height())
 }
 
 fn is_sprite_in_view(x: f32, y:f32, camera: &Camera2D) -> bool {
     // Calculate the world coordinates of the camera's view boundaries
-    let screen_half_width = (screen_width() / 2.0);
-    let screen_half_height = (screen_height() / 2.0);
+    let screen_half_width = screen_width() / 2.0;
+    let screen_half_height = screen_height() / 2.0;
 
     let left_bound = camera.target.x - screen_half_width - (SPRITE_SIZE * SPRITE_SCALE.x);
     let right_bound = camera.target.x + screen_half_width;
@@ -1,82 +1,599 @@
+use std::collections::VecDeque;
 use noise::{NoiseFn, Perlin};
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use crate::features::{add_rivers, add_roads, place_sites};
 
-pub enum GrassTileType {
-    Grass1,
-    Grass2,
-    Grass3,
-    Grass4,
-    Grass5,
-    Grass6,
-    Grass7
+// Width and height of a chunk, in tiles
+pub const CHUNK_SIZE: i32 = 32;
+
+// How many of the latest tile changes the map remembers, for changes_since
+const TRACKED_CHANGES: usize = 1024;
+
+// Movement cost of a tile with a road or bridge on it, whatever the land underneath
+pub const ROAD_MOVEMENT_COST: f32 = 0.5;
+
+// Bits of a transition's edge mask, for the sides of a tile that a neighbouring biome is drawn over
+pub const EDGE_NORTH: u8 = 1;
+pub const EDGE_EAST: u8 = 2;
+pub const EDGE_SOUTH: u8 = 4;
+pub const EDGE_WEST: u8 = 8;
+
+// Bits of a transition's corner mask, for corners where a neighbouring biome only touches diagonally
+pub const CORNER_NORTH_EAST: u8 = 1;
+pub const CORNER_SOUTH_EAST: u8 = 2;
+pub const CORNER_SOUTH_WEST: u8 = 4;
+pub const CORNER_NORTH_WEST: u8 = 8;
+
+const EDGE_NEIGHBOURS: [((i32, i32), u8); 4] = [((0, -1), EDGE_NORTH), ((1, 0), EDGE_EAST), ((0, 1), EDGE_SOUTH), ((-1, 0), EDGE_WEST)];
+const CORNER_NEIGHBOURS: [((i32, i32), u8); 4] = [
+    ((1, -1), CORNER_NORTH_EAST),
+    ((1, 1), CORNER_SOUTH_EAST),
+    ((-1, 1), CORNER_SOUTH_WEST),
+    ((-1, -1), CORNER_NORTH_WEST),
+];
+
+// Where each biome starts. Elevation decides between water, beaches, lowlands and high ground, and moisture
+// decides whether lowlands are forested.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BiomeThresholds {
+    // Below this elevation is water
+    pub water: f64,
+    // Below this elevation (and above water) is sand
+    pub sand: f64,
+    // At or above this elevation is hills
+    pub hills: f64,
+    // At or above this elevation is mountain
+    pub mountain: f64,
+    // Lowlands at or above this moisture are forest, the rest grassland
+    pub forest: f64,
+}
+
+impl Default for BiomeThresholds {
+    fn default() -> Self {
+        Self { water: 0.38, sand: 0.43, hills: 0.6, mountain: 0.7, forest: 0.52 }
+    }
+}
+
+impl BiomeThresholds {
+    // Pick a biome from elevation and moisture, both in 0..=1
+    pub fn biome(&self, elevation: f64, moisture: f64) -> Biome {
+        match elevation {
+            e if e < self.water => Biome::Water,
+            e if e < self.sand => Biome::Sand,
+            e if e >= self.mountain => Biome::Mountain,
+            e if e >= self.hills => Biome::Hills,
+            _ if moisture >= self.forest => Biome::Forest,
+            _ => Biome::Grassland,
+        }
+    }
+}
+
+// Everything that decides what a generated map looks like. The same config always generates the same map. Anything
+// missing when it's read from a file is left at its default.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MapGenConfig {
+    pub seed: u32,
+    // Size of terrain features, in tiles
+    pub scale: f64,
+    // Layers of noise added together, each adding finer detail
+    pub octaves: u32,
+    // How much each octave contributes compared to the one before it
+    pub persistence: f64,
+    // How much finer each octave is than the one before it
+    pub lacunarity: f64,
+    pub thresholds: BiomeThresholds,
+    // How many rivers to try to start in the highlands
+    pub rivers: u32,
+    // How many places for roads to lead to from the castle site
+    pub points_of_interest: u32,
+}
+
+impl Default for MapGenConfig {
+    fn default() -> Self {
+        Self {
+            seed: 1001010101,
+            scale: 40.,
+            octaves: 4,
+            persistence: 0.5,
+            lacunarity: 2.,
+            thresholds: BiomeThresholds::default(),
+            rivers: 6,
+            points_of_interest: 5,
+        }
+    }
+}
+
+impl MapGenConfig {
+    pub fn with_seed(seed: u32) -> Self {
+        Self { seed, ..Default::default() }
+    }
+
+}
+
+// Where the game's map comes from, as picked on the command line
+#[derive(Clone, Debug, PartialEq)]
+pub enum MapSource {
+    // Generated from a random seed
+    Random,
+    Generate(MapGenConfig),
+    // Loaded from a saved map file
+    File(String),
+    // Loaded from a scenario made in Tiled
+    Scenario(String),
+}
+
+impl MapSource {
+    // Read `--seed <seed>`, `--map <path>` or `--scenario <path>` from the command line arguments (without the
+    // program name). Any other argument is an error.
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut source = MapSource::Random;
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let next = match arg.as_str() {
+                "--seed" => {
+                    let value = args.next().ok_or("--seed needs a value")?;
+                    let seed = value.parse().map_err(|_| format!("'{}' is not a valid seed", value))?;
+                    MapSource::Generate(MapGenConfig::with_seed(seed))
+                }
+                "--map" => MapSource::File(args.next().ok_or("--map needs a path")?),
+                "--scenario" => MapSource::Scenario(args.next().ok_or("--scenario needs a path")?),
+                _ => return Err(format!("unknown argument '{}'", arg)),
+            };
+            if source != MapSource::Random {
+                return Err("only one of --seed, --map and --scenario can be given".to_string());
+            }
+            source = next;
+        }
+        Ok(source)
+    }
 }
 
-impl GrassTileType {
-    fn as_i32(&self) -> i32 {
+// Biomes are in drawing order. Where two meet, the edges of the later one are drawn over the earlier one, so grass
+// spills onto sand, and sand onto water.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Biome {
+    Water,
+    Sand,
+    Grassland,
+    Forest,
+    Hills,
+    Mountain,
+}
+
+impl Biome {
+    pub const ALL: [Biome; 6] = [Biome::Water, Biome::Sand, Biome::Grassland, Biome::Forest, Biome::Hills, Biome::Mountain];
+
+    // Id of the biome's tile set in the asset manifest. Tile sets are a row of TILE_SIZE tiles, one per variation.
+    pub fn texture_id(&self) -> &'static str {
+        match self {
+            Biome::Water => "water",
+            Biome::Sand => "sand",
+            Biome::Grassland => "grassland",
+            Biome::Forest => "forest",
+            Biome::Hills => "hills",
+            Biome::Mountain => "mountain",
+        }
+    }
+
+    // Id of the tile set the biome's edges are drawn over lower biomes with. The first row has a tile for each edge
+    // mask, and the second a tile for each corner mask. Water is never drawn over anything, so has none.
+    pub fn edge_texture_id(&self) -> Option<&'static str> {
+        match self {
+            Biome::Water => None,
+            Biome::Sand => Some("sand_edges"),
+            Biome::Grassland => Some("grassland_edges"),
+            Biome::Forest => Some("forest_edges"),
+            Biome::Hills => Some("hills_edges"),
+            Biome::Mountain => Some("mountain_edges"),
+        }
+    }
+
+    // The biome with the given name, the same as its texture id
+    pub fn from_name(name: &str) -> Option<Biome> {
+        Biome::ALL.into_iter().find(|biome| biome.texture_id() == name)
+    }
+
+    // How many different tiles the biome's tile set has
+    pub fn variations(&self) -> u8 {
+        match self {
+            Biome::Water => 3,
+            Biome::Sand => 3,
+            Biome::Grassland => 5,
+            Biome::Forest => 4,
+            Biome::Hills => 3,
+            Biome::Mountain => 3,
+        }
+    }
+
+    // How much slower than open grassland it is to cross the biome, or None if it can't be crossed on foot
+    pub fn movement_cost(&self) -> Option<f32> {
+        match self {
+            Biome::Water => None,
+            Biome::Sand => Some(1.25),
+            Biome::Grassland => Some(1.),
+            Biome::Forest => Some(2.),
+            Biome::Hills => Some(1.5),
+            Biome::Mountain => Some(3.),
+        }
+    }
+}
+
+// Something on top of a tile's biome, drawn over it and changing how it can be crossed
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Feature {
+    Road,
+    River,
+    Lake,
+    // Where a road crosses a river
+    Bridge,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 4] = [Feature::Road, Feature::River, Feature::Lake, Feature::Bridge];
+
+    // Id of the feature's tile set in the asset manifest
+    pub fn texture_id(&self) -> &'static str {
+        match self {
+            Feature::Road => "road",
+            Feature::River => "river",
+            Feature::Lake => "lake",
+            Feature::Bridge => "bridge",
+        }
+    }
+
+    // The feature with the given name, the same as its texture id
+    pub fn from_name(name: &str) -> Option<Feature> {
+        Feature::ALL.into_iter().find(|feature| feature.texture_id() == name)
+    }
+
+    // Movement cost of a tile with the feature on it, given the cost of the biome underneath
+    pub fn movement_cost(&self, biome_cost: Option<f32>) -> Option<f32> {
         match self {
-            GrassTileType::Grass1 => 1,
-            GrassTileType::Grass2 => 2,
-            GrassTileType::Grass3 => 3,
-            GrassTileType::Grass4 => 4,
-            GrassTileType::Grass5 => 5,
-            GrassTileType::Grass6 => 6,
-            GrassTileType::Grass7 => 7,
+            // Roads speed up travel over land, but don't make water crossable
+            Feature::Road => biome_cost.map(|_| ROAD_MOVEMENT_COST),
+            Feature::Bridge => Some(ROAD_MOVEMENT_COST),
+            Feature::River | Feature::Lake => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Tile {
+    pub biome: Biome,
+    // Which of the biome's tiles to draw, so large areas of one biome don't look uniform
+    pub variant: u8,
+    pub feature: Option<Feature>,
+}
+
+impl Tile {
+    pub fn new(biome: Biome, variant: u8) -> Self {
+        Self { biome, variant: variant % biome.variations(), feature: None }
+    }
+
+    pub fn with_feature(self, feature: Feature) -> Self {
+        Self { feature: Some(feature), ..self }
+    }
+
+    pub fn movement_cost(&self) -> Option<f32> {
+        match self.feature {
+            Some(feature) => feature.movement_cost(self.biome.movement_cost()),
+            None => self.biome.movement_cost(),
         }
     }
+
+    pub fn is_passable(&self) -> bool {
+        self.movement_cost().is_some()
+    }
+}
+
+// A neighbouring biome drawn over the edges of a tile, so that biomes blend into each other rather than meeting in
+// square cuts. Picks the tiles to draw from the biome's edge tile set.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Transition {
+    pub biome: Biome,
+    // EDGE_* bits for the sides the biome is on
+    pub edges: u8,
+    // CORNER_* bits for the corners the biome is on, where it isn't on either side of the corner as well
+    pub corners: u8,
+}
+
+// A square block of tiles, stored together so that each part of the map is in one place in memory however large
+// the map is. Chunks on the right and bottom edges of the map can hang over the edge.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chunk {
+    tiles: Vec<Tile>,
+    // The render layer: transitions for the tiles that have any, by index into tiles, lowest biome first
+    transitions: Vec<(u16, Transition)>,
+}
+
+impl Chunk {
+    fn tile(&self, local_x: i32, local_y: i32) -> Tile {
+        self.tiles[local_index(local_x, local_y)]
+    }
 }
 
 pub struct GameMap {
-    pub tiles: Vec<i32>,
-    pub noise_map: Vec<i32>,
     pub map_width: i32,
-    pub map_height: i32
+    pub map_height: i32,
+    pub config: MapGenConfig,
+    // Where the player's castle goes, if the map has somewhere set aside for it
+    pub castle_site: Option<(i32, i32)>,
+    // Places worth going to, that roads lead to from the castle site
+    pub points_of_interest: Vec<(i32, i32)>,
+    // Chunks row by row, CHUNK_SIZE tiles square. Empty until the map is generated.
+    chunks: Vec<Chunk>,
+    // Bumped on every change to the tiles made through the map, so anything derived from them knows to rebuild
+    revision: u64,
+    // The latest tiles changed one at a time, with the revision each change made, oldest first
+    changes: VecDeque<(u64, (i32, i32))>,
 }
 
 impl GameMap {
-    pub fn new(map_width: i32, map_height: i32) -> Self {
+    pub fn new(map_width: i32, map_height: i32, config: MapGenConfig) -> Self {
         Self {
-            tiles: Vec::new(),
-            noise_map: Vec::new(),
             map_width,
-            map_height
+            map_height,
+            config,
+            castle_site: None,
+            points_of_interest: Vec::new(),
+            chunks: Vec::new(),
+            revision: 0,
+            changes: VecDeque::new(),
         }
     }
 
     pub fn generate_simple_map(&mut self) {
-        // Generate a map of map_width by map_height dimensions, and fill each tile with a random grass index
-        let mut rng = rand::thread_rng();
-        let mut tiles = Vec::<i32>::new();
+        // Generate a map of map_width by map_height dimensions, and fill each tile with a random grass tile
+        let mut rng = StdRng::seed_from_u64(self.config.seed as u64);
+        self.fill(|_, _| Tile::new(Biome::Grassland, rng.gen_range(0..Biome::Grassland.variations())));
+    }
+
+    // Generate terrain from two layers of noise, elevation and moisture, which together decide each tile's biome. Then
+    // rivers run downhill from the highlands, and roads are laid from the castle site to the points of interest.
+    pub fn generate_terrain(&mut self) {
+        let config = self.config;
+        let elevation_noise = Perlin::new(config.seed);
+        let moisture_noise = Perlin::new(config.seed.wrapping_add(1));
+
+        let elevation: Vec<f64> = (0..self.tile_count() as i32)
+            .map(|index| {
+                let (x, y) = self.map_coords(index);
+                fractal_noise(&elevation_noise, &config, x, y)
+            })
+            .collect();
+        let map_width = self.map_width;
+        self.fill(|x, y| {
+            let moisture = fractal_noise(&moisture_noise, &config, x, y);
+            let biome = config.thresholds.biome(elevation[(y * map_width + x) as usize], moisture);
+            Tile::new(biome, (tile_hash(x, y, config.seed) % biome.variations() as u32) as u8)
+        });
+
+        let mut rng = StdRng::seed_from_u64(config.seed as u64);
+        add_rivers(self, &elevation, &mut rng);
+        place_sites(self, &mut rng);
+        add_roads(self);
+    }
+
+    // Replace every tile on the map with tile_at(x, y). Tiles are visited a chunk at a time.
+    pub fn fill(&mut self, mut tile_at: impl FnMut(i32, i32) -> Tile) {
+        let (chunks_wide, chunks_high) = self.chunk_dimensions();
+        let mut chunks = Vec::with_capacity((chunks_wide * chunks_high) as usize);
+        for chunk_y in 0..chunks_high {
+            for chunk_x in 0..chunks_wide {
+                let mut tiles = Vec::with_capacity((CHUNK_SIZE * CHUNK_SIZE) as usize);
+                for local_y in 0..CHUNK_SIZE {
+                    for local_x in 0..CHUNK_SIZE {
+                        let (x, y) = (chunk_x * CHUNK_SIZE + local_x, chunk_y * CHUNK_SIZE + local_y);
+                        // The part of an edge chunk hanging off the map is never read
+                        tiles.push(if self.in_bounds(x, y) { tile_at(x, y) } else { Tile::new(Biome::Water, 0) });
+                    }
+                }
+                chunks.push(Chunk { tiles, transitions: Vec::new() });
+            }
+        }
+
+        self.chunks = chunks;
+        self.autotile();
+        self.revision += 1;
+        // Every tile changed, which is more than changes_since can say
+        self.changes.clear();
+    }
+
+    // Work out the transitions for every tile on the map, from a copy of the biomes that's quicker to look around
+    fn autotile(&mut self) {
+        let mut biomes = vec![Biome::Water; self.tile_count()];
+        for ((x, y), tile) in self.tiles() {
+            biomes[self.map_index(x, y) as usize] = tile.biome;
+        }
+        let biome_at = |x: i32, y: i32| self.in_bounds(x, y).then(|| biomes[self.map_index(x, y) as usize]);
+
+        let (chunks_wide, _) = self.chunk_dimensions();
+        let mut chunk_transitions = Vec::with_capacity(self.chunks.len());
+        for chunk_index in 0..self.chunks.len() as i32 {
+            let (chunk_left, chunk_top) = ((chunk_index % chunks_wide) * CHUNK_SIZE, (chunk_index / chunks_wide) * CHUNK_SIZE);
+            let mut transitions = Vec::new();
+            for local_y in 0..CHUNK_SIZE {
+                for local_x in 0..CHUNK_SIZE {
+                    let index = local_index(local_x, local_y) as u16;
+                    let tile_transitions = transitions_at(biome_at, chunk_left + local_x, chunk_top + local_y);
+                    transitions.extend(tile_transitions.into_iter().map(|transition| (index, transition)));
+                }
+            }
+            chunk_transitions.push(transitions);
+        }
+        for (chunk, transitions) in self.chunks.iter_mut().zip(chunk_transitions) {
+            chunk.transitions = transitions;
+        }
+    }
+
+    // Work out the transitions for the tile at x, y again, after it or a neighbour changed
+    fn retile(&mut self, x: i32, y: i32) {
+        if !self.in_bounds(x, y) {
+            return;
+        }
+        let transitions = transitions_at(|x, y| self.tile(x, y).map(|tile| tile.biome), x, y);
+        let index = local_index(x % CHUNK_SIZE, y % CHUNK_SIZE) as u16;
+        let chunk_index = self.chunk_index(x, y);
+        if let Some(chunk) = self.chunks.get_mut(chunk_index) {
+            chunk.transitions.retain(|&(tile_index, _)| tile_index != index);
+            let at = chunk.transitions.partition_point(|&(tile_index, _)| tile_index < index);
+            chunk.transitions.splice(at..at, transitions.into_iter().map(|transition| (index, transition)));
+        }
+    }
+
+    // How many chunks across and down the map is
+    pub fn chunk_dimensions(&self) -> (i32, i32) {
+        ((self.map_width + CHUNK_SIZE - 1) / CHUNK_SIZE, (self.map_height + CHUNK_SIZE - 1) / CHUNK_SIZE)
+    }
+
+    pub fn tile(&self, x: i32, y: i32) -> Option<Tile> {
+        if self.in_bounds(x, y) {
+            self.chunks.get(self.chunk_index(x, y)).map(|chunk| chunk.tile(x % CHUNK_SIZE, y % CHUNK_SIZE))
+        } else {
+            None
+        }
+    }
+
+    // Every tile from min to max (inclusive, and clipped to the map) with its position, visiting only the chunks that
+    // overlap that area, so the cost depends on the size of the area and not the size of the map
+    pub fn tiles_in(&self, min: (i32, i32), max: (i32, i32)) -> impl Iterator<Item = ((i32, i32), Tile)> + '_ {
+        let ((min_x, min_y), (max_x, max_y)) = self.clip(min, max);
+        self.chunks_in((min_x, min_y), (max_x, max_y)).flat_map(move |(chunk_left, chunk_top, chunk)| {
+            let rows = chunk_top.max(min_y)..=(chunk_top + CHUNK_SIZE - 1).min(max_y);
+            let columns = chunk_left.max(min_x)..=(chunk_left + CHUNK_SIZE - 1).min(max_x);
+            rows.flat_map(move |y| columns.clone().map(move |x| ((x, y), chunk.tile(x - chunk_left, y - chunk_top))))
+        })
+    }
+
+    // The render layer's transitions for every tile from min to max (inclusive, and clipped to the map), a chunk at a
+    // time like tiles_in. A tile's transitions come lowest biome first, which is the order they're drawn in.
+    pub fn transitions_in(&self, min: (i32, i32), max: (i32, i32)) -> impl Iterator<Item = ((i32, i32), Transition)> + '_ {
+        let ((min_x, min_y), (max_x, max_y)) = self.clip(min, max);
+        self.chunks_in((min_x, min_y), (max_x, max_y)).flat_map(move |(chunk_left, chunk_top, chunk)| {
+            chunk.transitions.iter().filter_map(move |&(index, transition)| {
+                let (x, y) = (chunk_left + index as i32 % CHUNK_SIZE, chunk_top + index as i32 / CHUNK_SIZE);
+                (x >= min_x && x <= max_x && y >= min_y && y <= max_y).then_some(((x, y), transition))
+            })
+        })
+    }
+
+    pub fn transitions(&self, x: i32, y: i32) -> Vec<Transition> {
+        self.transitions_in((x, y), (x, y)).map(|(_, transition)| transition).collect()
+    }
+
+    fn clip(&self, min: (i32, i32), max: (i32, i32)) -> ((i32, i32), (i32, i32)) {
+        ((min.0.max(0), min.1.max(0)), (max.0.min(self.map_width - 1), max.1.min(self.map_height - 1)))
+    }
+
+    // The chunks overlapping min to max, with the position of their top left tile
+    fn chunks_in(&self, (min_x, min_y): (i32, i32), (max_x, max_y): (i32, i32)) -> impl Iterator<Item = (i32, i32, &Chunk)> + '_ {
+        let (chunks_wide, _) = self.chunk_dimensions();
+        let chunk_columns = min_x.div_euclid(CHUNK_SIZE)..=max_x.div_euclid(CHUNK_SIZE);
+        (min_y.div_euclid(CHUNK_SIZE)..=max_y.div_euclid(CHUNK_SIZE))
+            .flat_map(move |chunk_y| chunk_columns.clone().map(move |chunk_x| (chunk_x, chunk_y)))
+            .filter_map(move |(chunk_x, chunk_y)| {
+                let chunk = self.chunks.get((chunk_y * chunks_wide + chunk_x) as usize)?;
+                Some((chunk_x * CHUNK_SIZE, chunk_y * CHUNK_SIZE, chunk))
+            })
+    }
+
+    // Every tile on the map with its position, a chunk at a time
+    pub fn tiles(&self) -> impl Iterator<Item = ((i32, i32), Tile)> + '_ {
+        self.tiles_in((0, 0), (self.map_width - 1, self.map_height - 1))
+    }
+
+    pub fn tile_count(&self) -> usize {
+        self.map_width as usize * self.map_height as usize
+    }
+
+    // Movement cost of the tile at x, y, or None if it can't be entered (including anywhere off the map)
+    pub fn movement_cost(&self, x: i32, y: i32) -> Option<f32> {
+        self.tile(x, y).and_then(|tile| tile.movement_cost())
+    }
+
+    pub fn is_passable(&self, x: i32, y: i32) -> bool {
+        self.movement_cost(x, y).is_some()
+    }
 
-        for _ in 0..(self.map_width * self.map_height) {
-            tiles.push(rng.gen_range(0..7));
+    pub fn set_feature(&mut self, x: i32, y: i32, feature: Option<Feature>) {
+        if let Some(tile) = self.tile(x, y) {
+            self.set_tile(x, y, Tile { feature, ..tile });
         }
+    }
 
-        self.tiles = tiles;
+    // Replace the tile at x, y (e.g. when something is built on it)
+    pub fn set_tile(&mut self, x: i32, y: i32, tile: Tile) {
+        if self.in_bounds(x, y) {
+            let chunk_index = self.chunk_index(x, y);
+            if let Some(chunk) = self.chunks.get_mut(chunk_index) {
+                let biome_changed = chunk.tiles[local_index(x % CHUNK_SIZE, y % CHUNK_SIZE)].biome != tile.biome;
+                chunk.tiles[local_index(x % CHUNK_SIZE, y % CHUNK_SIZE)] = tile;
+                // Transitions only depend on biomes, and only reach as far as the neighbouring tiles
+                if biome_changed {
+                    for (dx, dy) in (-1..=1).flat_map(|dy| (-1..=1).map(move |dx| (dx, dy))) {
+                        self.retile(x + dx, y + dy);
+                    }
+                }
+                self.revision += 1;
+                if self.changes.len() == TRACKED_CHANGES {
+                    self.changes.pop_front();
+                }
+                self.changes.push_back((self.revision, (x, y)));
+            }
+        }
     }
 
-    pub fn generate_noise_map(&mut self) {
-        let mut map = Vec::with_capacity(self.map_width as usize * self.map_height as usize);
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
 
-        let perlin = Perlin::new(1001010101);
+    // The tiles changed since the map was at `revision`, so anything derived from them can update just those. None if
+    // that's further back than the map remembers, or the whole map has been replaced since, and everything has to be
+    // rebuilt.
+    pub fn changes_since(&self, revision: u64) -> Option<impl Iterator<Item = (i32, i32)> + '_> {
+        let remembered = match self.changes.front() {
+            Some(&(oldest, _)) => oldest <= revision + 1,
+            None => revision == self.revision,
+        };
+        remembered.then(|| self.changes.iter().filter(move |(change, _)| *change > revision).map(|(_, tile)| *tile))
+    }
 
-        for y in 0..self.map_height {
-            for x in 0..self.map_width {
-                let scale = 10.;
-                // Scale the coordinates to get different noise patterns
-                let nx = x as f64 / scale;
-                let ny = y as f64 / scale;
+    // The passable tile closest to x, y, if there is one
+    pub fn nearest_passable(&self, x: i32, y: i32) -> Option<(i32, i32)> {
+        self.nearest_tile(x, y, |tile| tile.is_passable())
+    }
 
-                // Generate the noise value at this point and push it to the map
-                let value = perlin.get([nx, ny]);
-                map.push(((value + 1.0) * (5.0 / 2.0)).floor() as i32);
+    // The tile closest to x, y that `accept` is true for (searching outwards a square ring at a time), if there is one
+    pub fn nearest_tile(&self, x: i32, y: i32, accept: impl Fn(Tile) -> bool) -> Option<(i32, i32)> {
+        let max_radius = self.map_width.max(self.map_height);
+        for radius in 0..=max_radius {
+            let ring = (-radius..=radius).flat_map(|dy| (-radius..=radius).map(move |dx| (dx, dy)))
+                .filter(|(dx, dy)| dx.abs() == radius || dy.abs() == radius);
+            let closest = ring
+                .map(|(dx, dy)| (x + dx, y + dy))
+                .filter(|&(tile_x, tile_y)| self.tile(tile_x, tile_y).is_some_and(&accept))
+                .min_by_key(|&(tile_x, tile_y)| (tile_x - x).pow(2) + (tile_y - y).pow(2));
+            if closest.is_some() {
+                return closest;
             }
         }
+        None
+    }
+
+    pub fn in_bounds(&self, x: i32, y: i32) -> bool {
+        x >= 0 && y >= 0 && x < self.map_width && y < self.map_height
+    }
 
-        self.noise_map = map.clone();
+    fn chunk_index(&self, x: i32, y: i32) -> usize {
+        ((y / CHUNK_SIZE) * self.chunk_dimensions().0 + x / CHUNK_SIZE) as usize
     }
 
+    // Index of a tile in row by row order, for anything keeping its own per tile data
     pub fn map_index(&self, x: i32, y: i32) -> i32 {
-        ((y * self.map_width) + x)
+        (y * self.map_width) + x
     }
 
     pub fn map_coords(&self, index: i32) -> (i32, i32) {
@@ -84,44 +601,424 @@ impl GameMap {
     }
 }
 
+// Every biome later in the drawing order than the one at x, y that neighbours it, with the sides and corners it
+// touches. biome_at gives None off the map, and nothing spills over from there.
+fn transitions_at(biome_at: impl Fn(i32, i32) -> Option<Biome>, x: i32, y: i32) -> Vec<Transition> {
+    let Some(own_biome) = biome_at(x, y) else { return Vec::new() };
+    let neighbour = |(dx, dy): (i32, i32)| biome_at(x + dx, y + dy).filter(|&biome| biome > own_biome);
+
+    let mut transitions: Vec<Transition> = Vec::new();
+    let mut add = |biome: Biome, edges: u8, corners: u8| match transitions.iter_mut().find(|transition| transition.biome == biome) {
+        Some(transition) => {
+            transition.edges |= edges;
+            transition.corners |= corners;
+        }
+        None => transitions.push(Transition { biome, edges, corners }),
+    };
+    for (offset, bit) in EDGE_NEIGHBOURS {
+        if let Some(biome) = neighbour(offset) {
+            add(biome, bit, 0);
+        }
+    }
+    for ((dx, dy), bit) in CORNER_NEIGHBOURS {
+        // A corner next to a side the biome is on is already covered by that side
+        if let Some(biome) = neighbour((dx, dy)).filter(|&biome| neighbour((dx, 0)) != Some(biome) && neighbour((0, dy)) != Some(biome)) {
+            add(biome, 0, bit);
+        }
+    }
+    transitions.sort_by_key(|transition| transition.biome);
+    transitions
+}
+
+// Index of a tile in its chunk
+fn local_index(local_x: i32, local_y: i32) -> usize {
+    (local_y * CHUNK_SIZE + local_x) as usize
+}
+
+// Several octaves of Perlin noise added together, for detail at more than one scale, mapped to 0..=1
+fn fractal_noise(perlin: &Perlin, config: &MapGenConfig, x: i32, y: i32) -> f64 {
+    let mut value = 0.;
+    let mut amplitude = 1.;
+    let mut frequency = 1. / config.scale;
+    let mut total_amplitude = 0.;
+
+    for _ in 0..config.octaves.max(1) {
+        value += perlin.get([x as f64 * frequency, y as f64 * frequency]) * amplitude;
+        total_amplitude += amplitude;
+        amplitude *= config.persistence;
+        frequency *= config.lacunarity;
+    }
+
+    ((value / total_amplitude + 1.) / 2.).clamp(0., 1.)
+}
+
+// A cheap, well mixed hash of a tile position, for picking tile variations without needing an rng
+fn tile_hash(x: i32, y: i32, seed: u32) -> u32 {
+    let mut hash = seed ^ (x as u32).wrapping_mul(0x27d4eb2d) ^ (y as u32).wrapping_mul(0x165667b1);
+    hash ^= hash >> 15;
+    hash = hash.wrapping_mul(0x85ebca6b);
+    hash ^= hash >> 13;
+    hash
+}
+
+// A map from rows of characters, for tests: '#' water, 's' sand, '.' grassland, 'f' forest, 'h' hills, 'm' mountain,
+// and '=' a road over grassland
+#[cfg(test)]
+pub(crate) fn map_from_rows(rows: &[&str]) -> GameMap {
+    let mut game_map = GameMap::new(rows[0].len() as i32, rows.len() as i32, MapGenConfig::default());
+    game_map.fill(|x, y| match rows[y as usize].as_bytes()[x as usize] {
+        b'#' => Tile::new(Biome::Water, 0),
+        b's' => Tile::new(Biome::Sand, 0),
+        b'.' => Tile::new(Biome::Grassland, 0),
+        b'f' => Tile::new(Biome::Forest, 0),
+        b'h' => Tile::new(Biome::Hills, 0),
+        b'm' => Tile::new(Biome::Mountain, 0),
+        b'=' => Tile::new(Biome::Grassland, 0).with_feature(Feature::Road),
+        other => panic!("no tile for '{}' in test map row '{}'", other as char, rows[y as usize]),
+    });
+    game_map
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_new_map() {
-        let game_map = GameMap::new(3, 3);
+        let game_map = GameMap::new(3, 3, MapGenConfig::default());
 
         assert_eq!(game_map.map_width, 3);
         assert_eq!(game_map.map_height, 3);
-        assert_eq!(game_map.tiles.len(), 0);
+        assert_eq!(game_map.tile(0, 0), None);
+        assert_eq!(game_map.tiles().count(), 0);
     }
 
     #[test]
     fn test_generate_simple_map() {
-        let mut game_map = GameMap::new(3, 3);
+        let mut game_map = GameMap::new(3, 3, MapGenConfig::default());
+        game_map.generate_simple_map();
+
+        assert_eq!(game_map.tiles().count(), 9);
+        for (_, tile) in game_map.tiles() {
+            assert_eq!(tile.biome, Biome::Grassland);
+            assert!(tile.variant < Biome::Grassland.variations());
+        }
+    }
+
+    #[test]
+    fn test_generate_terrain() {
+        let mut game_map = GameMap::new(3, 3, MapGenConfig::default());
+        game_map.generate_terrain();
+
+        assert_eq!(game_map.tiles().count(), 9);
+        for (_, tile) in game_map.tiles() {
+            assert!(tile.variant < tile.biome.variations());
+        }
+    }
+
+    #[test]
+    fn test_biome_distribution() {
+        let mut game_map = GameMap::new(200, 200, MapGenConfig::default());
+        game_map.generate_terrain();
+
+        let share = |biome: Biome| {
+            game_map.tiles().filter(|(_, tile)| tile.biome == biome).count() as f64 / game_map.tile_count() as f64
+        };
+
+        // Every biome turns up, with grassland and forest making up most of the land, for this map's seed
+        assert!((0.08..0.25).contains(&share(Biome::Water)), "water {}", share(Biome::Water));
+        assert!((0.05..0.15).contains(&share(Biome::Sand)), "sand {}", share(Biome::Sand));
+        assert!((0.20..0.40).contains(&share(Biome::Grassland)), "grassland {}", share(Biome::Grassland));
+        assert!((0.12..0.30).contains(&share(Biome::Forest)), "forest {}", share(Biome::Forest));
+        assert!((0.10..0.25).contains(&share(Biome::Hills)), "hills {}", share(Biome::Hills));
+        assert!((0.03..0.15).contains(&share(Biome::Mountain)), "mountain {}", share(Biome::Mountain));
+    }
+
+    #[test]
+    fn test_biomes_are_contiguous() {
+        let mut game_map = GameMap::new(200, 200, MapGenConfig::default());
+        game_map.generate_terrain();
+
+        // Noise based terrain forms regions, so most tiles share their biome with the tile to their right
+        let same = (0..200).flat_map(|y| (0..199).map(move |x| (x, y)))
+            .filter(|&(x, y)| game_map.tile(x, y).unwrap().biome == game_map.tile(x + 1, y).unwrap().biome)
+            .count();
+        assert!(same as f64 / (200. * 199.) > 0.85);
+    }
+
+    #[test]
+    fn test_movement_costs() {
+        assert_eq!(Tile::new(Biome::Grassland, 0).movement_cost(), Some(1.));
+        assert!(Tile::new(Biome::Forest, 0).movement_cost().unwrap() > 1.);
+        assert!(!Tile::new(Biome::Water, 0).is_passable());
+
+        // Roads are fast on any land, but there's no road over water
+        let road = |biome| Tile::new(biome, 0).with_feature(Feature::Road);
+        assert_eq!(road(Biome::Forest).movement_cost(), Some(ROAD_MOVEMENT_COST));
+        assert_eq!(road(Biome::Water).movement_cost(), None);
+        // Rivers and lakes can't be crossed, except where there's a bridge
+        assert!(!Tile::new(Biome::Grassland, 0).with_feature(Feature::River).is_passable());
+        assert!(!Tile::new(Biome::Grassland, 0).with_feature(Feature::Lake).is_passable());
+        assert_eq!(Tile::new(Biome::Hills, 0).with_feature(Feature::Bridge).movement_cost(), Some(ROAD_MOVEMENT_COST));
+    }
+
+    #[test]
+    fn test_changes_since() {
+        let mut game_map = GameMap::new(3, 3, MapGenConfig::default());
         game_map.generate_simple_map();
+        let generated = game_map.revision();
+        assert_eq!(game_map.changes_since(generated).map(|changes| changes.count()), Some(0));
+        // Before the map was filled, nothing is known
+        assert!(game_map.changes_since(generated - 1).is_none());
 
-        assert_eq!(game_map.tiles.len(), 9);
-        for tile in game_map.tiles {
-            assert!(tile >= 0 && tile < 7);
+        game_map.set_tile(1, 1, Tile::new(Biome::Water, 0));
+        let watered = game_map.revision();
+        game_map.set_feature(0, 2, Some(Feature::Road));
+        assert_eq!(game_map.changes_since(generated).unwrap().collect::<Vec<_>>(), vec![(1, 1), (0, 2)]);
+        assert_eq!(game_map.changes_since(watered).unwrap().collect::<Vec<_>>(), vec![(0, 2)]);
+
+        // Only so many changes are remembered
+        for _ in 0..TRACKED_CHANGES {
+            game_map.set_tile(2, 2, Tile::new(Biome::Sand, 0));
         }
+        assert!(game_map.changes_since(watered).is_none());
+        assert_eq!(game_map.changes_since(game_map.revision() - 2).unwrap().count(), 2);
+
+        game_map.fill(|_, _| Tile::new(Biome::Forest, 0));
+        assert!(game_map.changes_since(game_map.revision() - 1).is_none());
+    }
+
+    #[test]
+    fn test_map_passability() {
+        let mut game_map = GameMap::new(3, 3, MapGenConfig::default());
+        game_map.generate_simple_map();
+        game_map.set_tile(1, 1, Tile::new(Biome::Water, 0));
+        let revision = game_map.revision();
+        game_map.set_feature(0, 0, Some(Feature::Road));
+        assert!(game_map.revision() > revision);
+
+        assert!(!game_map.is_passable(1, 1));
+        assert!(game_map.is_passable(2, 1));
+        assert_eq!(game_map.movement_cost(0, 0), Some(ROAD_MOVEMENT_COST));
+        // Off the map is never passable
+        assert_eq!(game_map.movement_cost(-1, 0), None);
+        assert_eq!(game_map.movement_cost(3, 0), None);
+    }
+
+    #[test]
+    fn test_nearest_passable() {
+        let mut game_map = GameMap::new(5, 5, MapGenConfig::default());
+        game_map.fill(|_, _| Tile::new(Biome::Water, 0));
+        assert_eq!(game_map.nearest_passable(2, 2), None);
+
+        game_map.set_tile(4, 2, Tile::new(Biome::Sand, 0));
+        game_map.set_tile(0, 0, Tile::new(Biome::Sand, 0));
+        assert_eq!(game_map.nearest_passable(3, 2), Some((4, 2)));
+        assert_eq!(game_map.nearest_passable(4, 2), Some((4, 2)));
+        assert_eq!(game_map.nearest_passable(1, 1), Some((0, 0)));
+    }
+
+    #[test]
+    fn test_biome_thresholds() {
+        let thresholds = BiomeThresholds::default();
+        assert_eq!(thresholds.biome(0.1, 0.9), Biome::Water);
+        assert_eq!(thresholds.biome(0.4, 0.9), Biome::Sand);
+        assert_eq!(thresholds.biome(0.5, 0.2), Biome::Grassland);
+        assert_eq!(thresholds.biome(0.5, 0.8), Biome::Forest);
+        assert_eq!(thresholds.biome(0.65, 0.8), Biome::Hills);
+        assert_eq!(thresholds.biome(0.9, 0.1), Biome::Mountain);
+    }
+
+    #[test]
+    fn test_same_config_generates_same_map() {
+        let config = MapGenConfig { seed: 42, octaves: 5, ..Default::default() };
+        let generate = |config: MapGenConfig| {
+            let mut game_map = GameMap::new(100, 80, config);
+            game_map.generate_terrain();
+            game_map.tiles().collect::<Vec<_>>()
+        };
+
+        assert_eq!(generate(config), generate(config));
+        assert_ne!(generate(config), generate(MapGenConfig { seed: 43, ..config }));
     }
 
     #[test]
-    fn test_generate_noise_map() {
-        let mut game_map = GameMap::new(3, 3);
-        game_map.generate_noise_map();
+    fn test_same_seed_generates_same_simple_map() {
+        let generate = |seed| {
+            let mut game_map = GameMap::new(20, 20, MapGenConfig::with_seed(seed));
+            game_map.generate_simple_map();
+            game_map.tiles().collect::<Vec<_>>()
+        };
+
+        assert_eq!(generate(7), generate(7));
+        assert_ne!(generate(7), generate(8));
+    }
 
-        assert_eq!(game_map.noise_map.len(), 9);
-        for noise_value in game_map.noise_map {
-            assert!(noise_value >= 0 && noise_value <= 3);
+    #[test]
+    fn test_map_source_from_args() {
+        let args = |args: &[&str]| MapSource::from_args(args.iter().map(|arg| arg.to_string()));
+
+        assert_eq!(args(&[]), Ok(MapSource::Random));
+        assert_eq!(args(&["--seed", "1234"]), Ok(MapSource::Generate(MapGenConfig::with_seed(1234))));
+        assert_eq!(args(&["--map", "maps/island.json"]), Ok(MapSource::File("maps/island.json".to_string())));
+        assert!(args(&["--seed"]).is_err());
+        assert!(args(&["--seed", "abc"]).is_err());
+        assert_eq!(args(&["--scenario", "maps/siege.tmj"]), Ok(MapSource::Scenario("maps/siege.tmj".to_string())));
+        assert!(args(&["--map"]).is_err());
+        assert!(args(&["--seed", "1", "--map", "maps/island.json"]).is_err());
+        assert!(args(&["--size", "3"]).is_err());
+    }
+
+    #[test]
+    fn test_tile() {
+        let mut game_map = GameMap::new(3, 3, MapGenConfig::default());
+        game_map.generate_terrain();
+
+        assert_eq!(game_map.tile(1, 2), game_map.tiles().find(|&(position, _)| position == (1, 2)).map(|(_, tile)| tile));
+        assert_eq!(game_map.tile(3, 0), None);
+        assert_eq!(game_map.tile(0, -1), None);
+    }
+
+    #[test]
+    fn test_tiles_span_chunks() {
+        // Not a whole number of chunks, so the edge chunks hang off the map
+        let (width, height) = (CHUNK_SIZE * 2 + 5, CHUNK_SIZE + 3);
+        let mut game_map = GameMap::new(width, height, MapGenConfig::default());
+        game_map.fill(|x, y| Tile::new(Biome::Grassland, ((x + y * width) % 5) as u8));
+
+        assert_eq!(game_map.chunk_dimensions(), (3, 2));
+        assert_eq!(game_map.tiles().count(), game_map.tile_count());
+        for ((x, y), tile) in game_map.tiles() {
+            assert_eq!(tile.variant, ((x + y * width) % 5) as u8);
+            assert_eq!(game_map.tile(x, y), Some(tile));
         }
+
+        game_map.set_tile(CHUNK_SIZE * 2 + 4, CHUNK_SIZE + 2, Tile::new(Biome::Water, 0));
+        assert_eq!(game_map.tile(CHUNK_SIZE * 2 + 4, CHUNK_SIZE + 2).map(|tile| tile.biome), Some(Biome::Water));
+        assert_eq!(game_map.tile(CHUNK_SIZE * 2 + 5, CHUNK_SIZE + 2), None);
+    }
+
+    #[test]
+    fn test_tiles_in_only_visits_the_area() {
+        let (width, height) = (CHUNK_SIZE * 3, CHUNK_SIZE * 3);
+        let mut game_map = GameMap::new(width, height, MapGenConfig::default());
+        game_map.fill(|_, _| Tile::new(Biome::Grassland, 0));
+
+        // An area across the corner of four chunks
+        let mut positions: Vec<_> = game_map.tiles_in((CHUNK_SIZE - 2, CHUNK_SIZE - 1), (CHUNK_SIZE + 1, CHUNK_SIZE)).map(|(position, _)| position).collect();
+        positions.sort();
+        let expected: Vec<_> = (CHUNK_SIZE - 2..=CHUNK_SIZE + 1).flat_map(|x| (CHUNK_SIZE - 1..=CHUNK_SIZE).map(move |y| (x, y))).collect();
+        assert_eq!(positions, expected);
+
+        // Clipped to the map, and empty when entirely off it
+        assert_eq!(game_map.tiles_in((-10, -10), (1, 1)).count(), 4);
+        assert_eq!(game_map.tiles_in((width, 0), (width + 10, 10)).count(), 0);
+        assert_eq!(game_map.tiles_in((5, 5), (4, 4)).count(), 0);
+    }
+
+    #[test]
+    fn test_large_map() {
+        // Maps far bigger than the screen cost no more to look at a screenful of
+        let mut game_map = GameMap::new(2048, 2048, MapGenConfig::default());
+        game_map.fill(|_, _| Tile::new(Biome::Grassland, 0));
+
+        assert_eq!(game_map.tiles_in((1000, 1000), (1049, 1039)).count(), 50 * 40);
+        assert_eq!(game_map.tile(2047, 2047).map(|tile| tile.biome), Some(Biome::Grassland));
+    }
+
+    fn transition(biome: Biome, edges: u8, corners: u8) -> Transition {
+        Transition { biome, edges, corners }
+    }
+
+    #[test]
+    fn test_autotile_edges_and_corners() {
+        let game_map = map_from_rows(&[
+            "###",
+            "#s#",
+            "###",
+        ]);
+
+        // Sand spills onto the water around it, over the sides next to it and the corners diagonal to it
+        assert_eq!(game_map.transitions(1, 0), vec![transition(Biome::Sand, EDGE_SOUTH, 0)]);
+        assert_eq!(game_map.transitions(2, 1), vec![transition(Biome::Sand, EDGE_WEST, 0)]);
+        assert_eq!(game_map.transitions(0, 0), vec![transition(Biome::Sand, 0, CORNER_SOUTH_EAST)]);
+        assert_eq!(game_map.transitions(2, 2), vec![transition(Biome::Sand, 0, CORNER_NORTH_WEST)]);
+        // Nothing is drawn over the sand, and nothing comes in from off the map
+        assert_eq!(game_map.transitions(1, 1), vec![]);
+    }
+
+    #[test]
+    fn test_autotile_corners_next_to_sides() {
+        let game_map = map_from_rows(&[
+            "ss",
+            "#s",
+        ]);
+
+        // The corner between two sand sides is part of those sides
+        assert_eq!(game_map.transitions(0, 1), vec![transition(Biome::Sand, EDGE_NORTH | EDGE_EAST, 0)]);
+
+        let game_map = map_from_rows(&[
+            "s#s",
+            "###",
+            "s#s",
+        ]);
+        assert_eq!(game_map.transitions(1, 1), vec![transition(Biome::Sand, 0, CORNER_NORTH_EAST | CORNER_SOUTH_EAST | CORNER_SOUTH_WEST | CORNER_NORTH_WEST)]);
+        assert_eq!(game_map.transitions(1, 0), vec![transition(Biome::Sand, EDGE_EAST | EDGE_WEST, 0)]);
+    }
+
+    #[test]
+    fn test_autotile_layers_biomes() {
+        let game_map = map_from_rows(&[
+            ".s#",
+            "..#",
+        ]);
+
+        // Grass over sand over water
+        assert_eq!(game_map.transitions(0, 0), vec![]);
+        assert_eq!(game_map.transitions(1, 0), vec![transition(Biome::Grassland, EDGE_SOUTH | EDGE_WEST, 0)]);
+        // Water takes edges from both, lowest first, so they're drawn in order
+        assert_eq!(game_map.transitions(2, 0), vec![transition(Biome::Sand, EDGE_WEST, 0), transition(Biome::Grassland, 0, CORNER_SOUTH_WEST)]);
+        assert_eq!(game_map.transitions(2, 1), vec![transition(Biome::Sand, 0, CORNER_NORTH_WEST), transition(Biome::Grassland, EDGE_WEST, 0)]);
+    }
+
+    #[test]
+    fn test_autotile_follows_changes() {
+        let mut game_map = map_from_rows(&[
+            "fff",
+            "fff",
+            "fff",
+        ]);
+        assert_eq!(game_map.transitions_in((0, 0), (2, 2)).count(), 0);
+
+        game_map.set_tile(1, 1, Tile::new(Biome::Water, 0));
+        assert_eq!(game_map.transitions(1, 1), vec![transition(Biome::Forest, EDGE_NORTH | EDGE_EAST | EDGE_SOUTH | EDGE_WEST, 0)]);
+        // Features don't change the biome, so leave the transitions alone
+        game_map.set_feature(1, 0, Some(Feature::Road));
+        assert_eq!(game_map.transitions_in((0, 0), (2, 2)).count(), 1);
+
+        game_map.set_tile(1, 1, Tile::new(Biome::Forest, 2));
+        assert_eq!(game_map.transitions_in((0, 0), (2, 2)).count(), 0);
+    }
+
+    #[test]
+    fn test_transitions_across_chunks() {
+        // A lake either side of a chunk boundary, and its shore on both sides too
+        let mut game_map = GameMap::new(CHUNK_SIZE * 2, 4, MapGenConfig::default());
+        game_map.fill(|x, _| Tile::new(if x == CHUNK_SIZE - 1 || x == CHUNK_SIZE { Biome::Water } else { Biome::Grassland }, 0));
+
+        let positions: Vec<_> = game_map.transitions_in((0, 0), (CHUNK_SIZE * 2, 0)).map(|(position, _)| position).collect();
+        assert_eq!(positions, vec![(CHUNK_SIZE - 1, 0), (CHUNK_SIZE, 0)]);
+        assert_eq!(game_map.transitions(CHUNK_SIZE - 1, 2), vec![transition(Biome::Grassland, EDGE_WEST, 0)]);
+        assert_eq!(game_map.transitions(CHUNK_SIZE, 2), vec![transition(Biome::Grassland, EDGE_EAST, 0)]);
+
+        game_map.set_tile(CHUNK_SIZE - 2, 0, Tile::new(Biome::Water, 0));
+        assert_eq!(game_map.transitions(CHUNK_SIZE - 1, 0), vec![transition(Biome::Grassland, 0, CORNER_SOUTH_WEST)]);
+        assert_eq!(game_map.transitions(CHUNK_SIZE - 2, 0), vec![transition(Biome::Grassland, EDGE_SOUTH | EDGE_WEST, 0)]);
     }
 
     #[test]
     fn test_map_index() {
-        let game_map = GameMap::new(3, 3);
+        let game_map = GameMap::new(3, 3, MapGenConfig::default());
         let index = game_map.map_index(1, 2);
 
         assert_eq!(index, 7);
@@ -129,9 +1026,9 @@ mod tests {
 
     #[test]
     fn test_map_coords() {
-        let game_map = GameMap::new(3, 3);
+        let game_map = GameMap::new(3, 3, MapGenConfig::default());
         let coords = game_map.map_coords(7);
 
         assert_eq!(coords, (1, 2));
     }
-}
\ No newline at end of file
+}
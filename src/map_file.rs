@@ -0,0 +1,384 @@
+use std::fmt;
+use std::fs;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use crate::map::{Biome, Feature, GameMap, MapGenConfig, Tile};
+use crate::visibility::VisibilityMap;
+
+// Bumped whenever the format changes. Older files are migrated up to this version when they're loaded.
+pub const MAP_FILE_VERSION: u64 = 3;
+
+// A map as it's saved to disk. Layers hold one entry per tile, row by row, and sites list the tiles they're on.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct MapFile {
+    version: u64,
+    width: i32,
+    height: i32,
+    // What the map was generated with, so it can be generated again
+    config: MapGenConfig,
+    layers: MapLayers,
+    sites: MapSites,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct MapLayers {
+    // Index into Biome::ALL
+    biome: Vec<u8>,
+    variant: Vec<u8>,
+    // 0 for none, otherwise one more than the index into Feature::ALL
+    feature: Vec<u8>,
+    // 1 where the player has explored, otherwise 0
+    explored: Vec<u8>,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+struct MapSites {
+    castle: Option<(i32, i32)>,
+    points_of_interest: Vec<(i32, i32)>,
+}
+
+#[derive(Debug)]
+pub enum MapFileError {
+    Io { path: String, source: std::io::Error },
+    Parse { source: serde_json::Error },
+    MissingVersion,
+    UnsupportedVersion { version: u64 },
+    Invalid { reason: String },
+}
+
+impl fmt::Display for MapFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapFileError::Io { path, source } => write!(f, "failed to access {}: {}", path, source),
+            MapFileError::Parse { source } => write!(f, "not a valid map file: {}", source),
+            MapFileError::MissingVersion => write!(f, "map file has no version"),
+            MapFileError::UnsupportedVersion { version } => {
+                write!(f, "map file version {} is not supported (this build reads up to version {})", version, MAP_FILE_VERSION)
+            }
+            MapFileError::Invalid { reason } => write!(f, "invalid map file: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for MapFileError {}
+
+// A loaded map file: the map, and how much of it the player had explored
+pub struct SavedMap {
+    pub game_map: GameMap,
+    // Everything explored is out of sight until the player's creatures and buildings next look around
+    pub visibility: VisibilityMap,
+}
+
+pub fn save_map(game_map: &GameMap, visibility: &VisibilityMap, file_path: &str) -> Result<(), MapFileError> {
+    fs::write(file_path, map_to_json(game_map, visibility)).map_err(|source| MapFileError::Io { path: file_path.to_string(), source })
+}
+
+pub fn load_map(file_path: &str) -> Result<SavedMap, MapFileError> {
+    let file_content = fs::read_to_string(file_path).map_err(|source| MapFileError::Io { path: file_path.to_string(), source })?;
+    map_from_json(&file_content)
+}
+
+pub fn map_to_json(game_map: &GameMap, visibility: &VisibilityMap) -> String {
+    let tile_count = game_map.tile_count();
+    let mut layers = MapLayers {
+        biome: Vec::with_capacity(tile_count),
+        variant: Vec::with_capacity(tile_count),
+        feature: Vec::with_capacity(tile_count),
+        explored: Vec::with_capacity(tile_count),
+    };
+    for y in 0..game_map.map_height {
+        for x in 0..game_map.map_width {
+            // An ungenerated map is saved as all water
+            let tile = game_map.tile(x, y).unwrap_or(Tile::new(Biome::Water, 0));
+            layers.biome.push(tile.biome as u8);
+            layers.variant.push(tile.variant);
+            layers.feature.push(tile.feature.map_or(0, |feature| feature as u8 + 1));
+            layers.explored.push(visibility.is_explored(x, y) as u8);
+        }
+    }
+
+    let map_file = MapFile {
+        version: MAP_FILE_VERSION,
+        width: game_map.map_width,
+        height: game_map.map_height,
+        config: game_map.config,
+        layers,
+        sites: MapSites { castle: game_map.castle_site, points_of_interest: game_map.points_of_interest.clone() },
+    };
+    serde_json::to_string(&map_file).expect("Map files always serialize")
+}
+
+pub fn map_from_json(json: &str) -> Result<SavedMap, MapFileError> {
+    let value: Value = serde_json::from_str(json).map_err(|source| MapFileError::Parse { source })?;
+    let version = value.get("version").and_then(Value::as_u64).ok_or(MapFileError::MissingVersion)?;
+    if version > MAP_FILE_VERSION {
+        return Err(MapFileError::UnsupportedVersion { version });
+    }
+
+    let map_file: MapFile = serde_json::from_value(migrate(value, version)?).map_err(|source| MapFileError::Parse { source })?;
+    build_map(map_file)
+}
+
+// Upgrade a file written by an older version of the format to the current one, a version at a time
+fn migrate(file: Value, version: u64) -> Result<Value, MapFileError> {
+    match version {
+        MAP_FILE_VERSION => Ok(file),
+        // Version 3 added the explored layer
+        2 => migrate(migrate_version_2(file)?, 3),
+        // Version 2 replaced the list of roads with a layer of features, and added sites
+        1 => migrate(migrate_version_1(file)?, 2),
+        // Version 1 was the first
+        _ => Err(MapFileError::UnsupportedVersion { version }),
+    }
+}
+
+fn migrate_version_1(mut file: Value) -> Result<Value, MapFileError> {
+    let invalid = |reason: &str| MapFileError::Invalid { reason: reason.to_string() };
+    let width = file["width"].as_i64().ok_or_else(|| invalid("map has no width"))?;
+    let height = file["height"].as_i64().ok_or_else(|| invalid("map has no height"))?;
+    let map = file.as_object_mut().ok_or_else(|| invalid("map is not an object"))?;
+    let mut features = map.remove("features").ok_or_else(|| invalid("map has no features"))?;
+    let features = features.as_object_mut().ok_or_else(|| invalid("map has no features"))?;
+    let roads: Vec<(i64, i64)> = serde_json::from_value(features.remove("roads").unwrap_or(Value::Null))
+        .map_err(|source| MapFileError::Parse { source })?;
+    let layers = map.get_mut("layers").and_then(Value::as_object_mut).ok_or_else(|| invalid("map has no layers"))?;
+
+    // The size is only trusted once the biome layer agrees with it
+    let tile_count = layers.get("biome").and_then(Value::as_array).map(Vec::len);
+    if width < 0 || height < 0 || width.checked_mul(height).map(|count| count as usize) != tile_count {
+        return Err(invalid("biome layer doesn't match the map size"));
+    }
+    let mut feature = vec![0u8; (width * height) as usize];
+    for (x, y) in roads {
+        if x < 0 || y < 0 || x >= width || y >= height {
+            return Err(MapFileError::Invalid { reason: format!("road at {:?} is off the map", (x, y)) });
+        }
+        feature[(y * width + x) as usize] = Feature::Road as u8 + 1;
+    }
+    layers.insert("feature".to_string(), feature.into());
+    map.insert("version".to_string(), 2.into());
+    map.insert("sites".to_string(), json!({ "castle": null, "points_of_interest": [] }));
+    Ok(file)
+}
+
+// Files from before the explored layer start with nothing explored
+fn migrate_version_2(mut file: Value) -> Result<Value, MapFileError> {
+    let invalid = |reason: &str| MapFileError::Invalid { reason: reason.to_string() };
+    let map = file.as_object_mut().ok_or_else(|| invalid("map is not an object"))?;
+    let layers = map.get_mut("layers").and_then(Value::as_object_mut).ok_or_else(|| invalid("map has no layers"))?;
+    let tile_count = layers.get("biome").and_then(Value::as_array).map_or(0, Vec::len);
+    layers.insert("explored".to_string(), vec![0u8; tile_count].into());
+    map.insert("version".to_string(), 3.into());
+    Ok(file)
+}
+
+fn build_map(map_file: MapFile) -> Result<SavedMap, MapFileError> {
+    let invalid = |reason: String| MapFileError::Invalid { reason };
+    let MapFile { width, height, config, layers, sites, .. } = map_file;
+    if width <= 0 || height <= 0 {
+        return Err(invalid(format!("map is {}x{}", width, height)));
+    }
+    let tile_count = width as usize * height as usize;
+    for (name, layer) in [("biome", &layers.biome), ("variant", &layers.variant), ("feature", &layers.feature), ("explored", &layers.explored)] {
+        if layer.len() != tile_count {
+            return Err(invalid(format!("{} layer has {} tiles, but a {}x{} map has {}", name, layer.len(), width, height, tile_count)));
+        }
+    }
+
+    let mut tiles = Vec::with_capacity(tile_count);
+    for (index, ((&biome, &variant), &feature)) in layers.biome.iter().zip(&layers.variant).zip(&layers.feature).enumerate() {
+        let position = (index as i32 % width, index as i32 / width);
+        let biome = *Biome::ALL.get(biome as usize).ok_or_else(|| invalid(format!("tile {:?} has unknown biome {}", position, biome)))?;
+        if variant >= biome.variations() {
+            return Err(invalid(format!("tile {:?} has variant {}, but {:?} only has {}", position, variant, biome, biome.variations())));
+        }
+        let feature = match feature {
+            0 => None,
+            feature => Some(*Feature::ALL.get(feature as usize - 1).ok_or_else(|| invalid(format!("tile {:?} has unknown feature {}", position, feature)))?),
+        };
+        tiles.push(Tile { feature, ..Tile::new(biome, variant) });
+    }
+    if let Some(site) = sites.castle.iter().chain(&sites.points_of_interest).find(|&&(x, y)| x < 0 || y < 0 || x >= width || y >= height) {
+        return Err(invalid(format!("site at {:?} is off the map", site)));
+    }
+
+    let mut game_map = GameMap::new(width, height, config);
+    game_map.fill(|x, y| tiles[(y * width + x) as usize]);
+    game_map.castle_site = sites.castle;
+    game_map.points_of_interest = sites.points_of_interest;
+
+    let mut visibility = VisibilityMap::new(width, height);
+    for (index, &explored) in layers.explored.iter().enumerate() {
+        let position = (index as i32 % width, index as i32 / width);
+        match explored {
+            0 => {}
+            1 => visibility.explore(position.0, position.1),
+            explored => return Err(invalid(format!("tile {:?} has explored set to {}, rather than 0 or 1", position, explored))),
+        }
+    }
+    Ok(SavedMap { game_map, visibility })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tiles(game_map: &GameMap) -> Vec<((i32, i32), Tile)> {
+        game_map.tiles().collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut game_map = GameMap::new(40, 35, MapGenConfig::with_seed(77));
+        game_map.generate_terrain();
+        game_map.set_feature(3, 4, Some(Feature::Road));
+        game_map.set_feature(39, 34, Some(Feature::Bridge));
+        game_map.castle_site = Some((20, 20));
+        game_map.points_of_interest = vec![(1, 2), (30, 4)];
+
+        let mut visibility = VisibilityMap::new(40, 35);
+        visibility.reveal((5, 5), 3);
+        visibility.update([]);
+
+        let SavedMap { game_map: loaded, visibility: loaded_visibility } = map_from_json(&map_to_json(&game_map, &visibility)).unwrap();
+        assert_eq!((loaded.map_width, loaded.map_height), (40, 35));
+        assert_eq!(loaded.config, game_map.config);
+        assert_eq!(tiles(&loaded), tiles(&game_map));
+        assert_eq!(loaded.castle_site, Some((20, 20)));
+        assert_eq!(loaded.points_of_interest, vec![(1, 2), (30, 4)]);
+        // What was explored stays explored, but nothing is in sight until something looks again
+        assert_eq!(loaded_visibility.explored_count(), visibility.explored_count());
+        assert!(loaded_visibility.is_explored(5, 8) && !loaded_visibility.is_visible(5, 8));
+        assert!(!loaded_visibility.is_explored(5, 9));
+    }
+
+    #[test]
+    fn test_save_and_load_file() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("map.json").to_string_lossy().into_owned();
+        let mut game_map = GameMap::new(10, 10, MapGenConfig::default());
+        game_map.generate_terrain();
+
+        save_map(&game_map, &VisibilityMap::new(10, 10), &path).unwrap();
+        let loaded = load_map(&path).unwrap().game_map;
+        fs::remove_file(&path).unwrap();
+        assert_eq!(tiles(&loaded), tiles(&game_map));
+
+        assert!(matches!(load_map(&path), Err(MapFileError::Io { .. })));
+    }
+
+    #[test]
+    fn test_load_version_1() {
+        // Written by hand, so changes to the format that would break existing files get noticed
+        let json = r#"{
+            "version": 1,
+            "width": 3,
+            "height": 2,
+            "config": {
+                "seed": 5, "scale": 40.0, "octaves": 4, "persistence": 0.5, "lacunarity": 2.0,
+                "thresholds": { "water": 0.38, "sand": 0.43, "hills": 0.6, "mountain": 0.7, "forest": 0.52 }
+            },
+            "layers": {
+                "biome": [0, 1, 2, 3, 4, 5],
+                "variant": [0, 1, 2, 3, 0, 1]
+            },
+            "features": { "roads": [[1, 0], [2, 1]] }
+        }"#;
+        let game_map = map_from_json(json).unwrap().game_map;
+
+        // Settings added to the config since are left at their defaults
+        assert_eq!(game_map.config, MapGenConfig::with_seed(5));
+        assert_eq!(game_map.tile(2, 0), Some(Tile::new(Biome::Grassland, 2)));
+        // Roads were migrated to the feature layer
+        assert_eq!(game_map.tile(1, 0), Some(Tile::new(Biome::Sand, 1).with_feature(Feature::Road)));
+        assert_eq!(game_map.tile(2, 1), Some(Tile::new(Biome::Mountain, 1).with_feature(Feature::Road)));
+        assert_eq!(game_map.castle_site, None);
+
+        // Roads off the map are still caught
+        let mut value: Value = serde_json::from_str(json).unwrap();
+        value["features"]["roads"] = json!([[3, 0]]);
+        assert!(matches!(map_from_json(&value.to_string()), Err(MapFileError::Invalid { .. })));
+
+        // As are malformed files, without panicking
+        let broken = |change: fn(&mut Value)| {
+            let mut value: Value = serde_json::from_str(json).unwrap();
+            change(&mut value);
+            map_from_json(&value.to_string())
+        };
+        assert!(matches!(broken(|value| { value.as_object_mut().unwrap().remove("layers"); }), Err(MapFileError::Invalid { .. })));
+        assert!(matches!(broken(|value| value["layers"] = json!([])), Err(MapFileError::Invalid { .. })));
+        assert!(matches!(broken(|value| value["features"] = json!(5)), Err(MapFileError::Invalid { .. })));
+        assert!(matches!(broken(|value| value["width"] = json!(2_000_000_000)), Err(MapFileError::Invalid { .. })));
+    }
+
+    #[test]
+    fn test_load_version_2() {
+        let json = r#"{
+            "version": 2,
+            "width": 2,
+            "height": 2,
+            "config": {
+                "seed": 5, "scale": 40.0, "octaves": 4, "persistence": 0.5, "lacunarity": 2.0,
+                "thresholds": { "water": 0.38, "sand": 0.43, "hills": 0.6, "mountain": 0.7, "forest": 0.52 },
+                "rivers": 2, "points_of_interest": 1
+            },
+            "layers": {
+                "biome": [2, 2, 2, 0],
+                "variant": [0, 1, 2, 0],
+                "feature": [1, 2, 4, 0]
+            },
+            "sites": { "castle": [0, 0], "points_of_interest": [[1, 1]] }
+        }"#;
+        let SavedMap { game_map, visibility } = map_from_json(json).unwrap();
+
+        assert_eq!(game_map.config.rivers, 2);
+        assert_eq!(game_map.tile(0, 0), Some(Tile::new(Biome::Grassland, 0).with_feature(Feature::Road)));
+        assert_eq!(game_map.tile(1, 0), Some(Tile::new(Biome::Grassland, 1).with_feature(Feature::River)));
+        assert_eq!(game_map.tile(0, 1), Some(Tile::new(Biome::Grassland, 2).with_feature(Feature::Bridge)));
+        assert_eq!(game_map.tile(1, 1), Some(Tile::new(Biome::Water, 0)));
+        assert_eq!(game_map.castle_site, Some((0, 0)));
+        assert_eq!(game_map.points_of_interest, vec![(1, 1)]);
+        // Nothing was saved as explored before version 3
+        assert_eq!(visibility.explored_count(), 0);
+
+        let mut value: Value = serde_json::from_str(json).unwrap();
+        value["layers"] = json!([]);
+        assert!(matches!(map_from_json(&value.to_string()), Err(MapFileError::Invalid { .. })));
+    }
+
+    #[test]
+    fn test_reject_unknown_versions() {
+        let game_map = GameMap::new(2, 2, MapGenConfig::default());
+        let mut value: Value = serde_json::from_str(&map_to_json(&game_map, &VisibilityMap::new(2, 2))).unwrap();
+
+        value["version"] = (MAP_FILE_VERSION + 1).into();
+        assert!(matches!(map_from_json(&value.to_string()), Err(MapFileError::UnsupportedVersion { .. })));
+        value["version"] = 0.into();
+        assert!(matches!(map_from_json(&value.to_string()), Err(MapFileError::UnsupportedVersion { version: 0 })));
+        value.as_object_mut().unwrap().remove("version");
+        assert!(matches!(map_from_json(&value.to_string()), Err(MapFileError::MissingVersion)));
+        assert!(matches!(map_from_json("not a map"), Err(MapFileError::Parse { .. })));
+    }
+
+    #[test]
+    fn test_reject_invalid_maps() {
+        let game_map = GameMap::new(2, 2, MapGenConfig::default());
+        let value: Value = serde_json::from_str(&map_to_json(&game_map, &VisibilityMap::new(2, 2))).unwrap();
+        let load = |change: &dyn Fn(&mut Value)| {
+            let mut value = value.clone();
+            change(&mut value);
+            map_from_json(&value.to_string())
+        };
+
+        assert!(load(&|_| {}).is_ok());
+        assert!(matches!(load(&|value| value["layers"]["biome"] = json!([0, 0, 0])), Err(MapFileError::Invalid { .. })));
+        assert!(matches!(load(&|value| value["layers"]["biome"][1] = 9.into()), Err(MapFileError::Invalid { .. })));
+        assert!(matches!(load(&|value| value["layers"]["variant"][1] = 3.into()), Err(MapFileError::Invalid { .. })));
+        assert!(matches!(load(&|value| value["layers"]["feature"][1] = 5.into()), Err(MapFileError::Invalid { .. })));
+        assert!(matches!(load(&|value| value["layers"]["feature"] = json!([0, 0])), Err(MapFileError::Invalid { .. })));
+        assert!(matches!(load(&|value| value["layers"]["explored"][1] = 2.into()), Err(MapFileError::Invalid { .. })));
+        assert!(matches!(load(&|value| value["layers"]["explored"] = json!([1])), Err(MapFileError::Invalid { .. })));
+        assert!(matches!(load(&|value| value["sites"]["castle"] = json!([2, 0])), Err(MapFileError::Invalid { .. })));
+        assert!(matches!(load(&|value| value["width"] = 0.into()), Err(MapFileError::Invalid { .. })));
+    }
+}
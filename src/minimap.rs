@@ -0,0 +1,222 @@
+use macroquad::color::{Color, BLACK};
+use macroquad::math::{Rect, Vec2};
+use macroquad::prelude::Image;
+use crate::map::{Biome, Feature, GameMap, Tile};
+use crate::visibility::VisibilityMap;
+use crate::{Faction, MapInformation};
+
+// The longest side of the minimap on screen, and how far it sits from the bottom right corner of the screen
+pub const MINIMAP_SIZE: f32 = 200.;
+pub const MINIMAP_MARGIN: f32 = 10.;
+
+// The colour a tile shows as on the minimap, its feature's if it has one
+pub fn tile_colour(tile: &Tile) -> Color {
+    match tile.feature {
+        Some(Feature::Road) | Some(Feature::Bridge) => Color::from_rgba(140, 110, 70, 255),
+        Some(Feature::River) | Some(Feature::Lake) => Color::from_rgba(60, 110, 200, 255),
+        None => match tile.biome {
+            Biome::Water => Color::from_rgba(30, 60, 140, 255),
+            Biome::Sand => Color::from_rgba(220, 200, 130, 255),
+            Biome::Grassland => Color::from_rgba(100, 170, 70, 255),
+            Biome::Forest => Color::from_rgba(40, 110, 45, 255),
+            Biome::Hills => Color::from_rgba(140, 130, 90, 255),
+            Biome::Mountain => Color::from_rgba(130, 130, 135, 255),
+        },
+    }
+}
+
+// Colour of the dots marking entities on the minimap
+pub fn faction_colour(faction: Faction) -> Color {
+    match faction {
+        Faction::Player => Color::from_rgba(90, 200, 255, 255),
+        Faction::Enemy => Color::from_rgba(230, 40, 40, 255),
+    }
+}
+
+// The map drawn one pixel to a tile, with anything unexplored left black
+pub struct Minimap {
+    image: Image,
+    // The map revision and explored tile count the image is up to date with
+    drawn_for: Option<(u64, usize)>,
+}
+
+impl Default for Minimap {
+    fn default() -> Self {
+        Self { image: Image::empty(), drawn_for: None }
+    }
+}
+
+impl Minimap {
+    // Bring the image up to date with the map and what's been explored, returning whether anything was drawn so the
+    // texture made from it can be updated too. Only the tiles that changed or were explored since the last refresh are
+    // drawn, unless the map was replaced or changed too much to say which tiles did.
+    pub fn refresh(&mut self, game_map: &GameMap, visibility: &VisibilityMap) -> bool {
+        let drawn_for = (game_map.revision(), visibility.explored_count());
+        let size = (game_map.map_width as u16, game_map.map_height as u16);
+        let Some((drawn_revision, drawn_explored)) = self.drawn_for.filter(|_| (self.image.width, self.image.height) == size) else {
+            self.redraw(game_map, visibility);
+            return true;
+        };
+        if (drawn_revision, drawn_explored) == drawn_for {
+            return false;
+        }
+        let Some(changes) = game_map.changes_since(drawn_revision) else {
+            self.redraw(game_map, visibility);
+            return true;
+        };
+
+        // Tiles are always in sight when they're first explored, so the newly explored ones are among those visible
+        let explored = (drawn_for.1 != drawn_explored).then(|| visibility.visible_tiles()).into_iter().flatten();
+        for (x, y) in changes.chain(explored) {
+            if let (true, Some(tile)) = (visibility.is_explored(x, y), game_map.tile(x, y)) {
+                self.image.set_pixel(x as u32, y as u32, tile_colour(&tile));
+            }
+        }
+        self.drawn_for = Some(drawn_for);
+        true
+    }
+
+    fn redraw(&mut self, game_map: &GameMap, visibility: &VisibilityMap) {
+        self.image = Image::gen_image_color(game_map.map_width as u16, game_map.map_height as u16, BLACK);
+        for ((x, y), tile) in game_map.tiles() {
+            if visibility.is_explored(x, y) {
+                self.image.set_pixel(x as u32, y as u32, tile_colour(&tile));
+            }
+        }
+        self.drawn_for = Some((game_map.revision(), visibility.explored_count()));
+    }
+
+    pub fn image(&self) -> &Image {
+        &self.image
+    }
+}
+
+// Where the minimap is on screen, and how points on it match up with the world
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MinimapLayout {
+    pub rect: Rect,
+    world_size: Vec2,
+}
+
+impl MinimapLayout {
+    // In the bottom right corner of the screen, the same shape as the map
+    pub fn new(map_info: &MapInformation, screen_size: Vec2) -> Self {
+        let tiles = Vec2::new(map_info.width.max(1) as f32, map_info.height.max(1) as f32);
+        let size = tiles * (MINIMAP_SIZE / tiles.max_element());
+        let corner = screen_size - size - MINIMAP_MARGIN;
+        Self {
+            rect: Rect::new(corner.x, corner.y, size.x, size.y),
+            world_size: tiles * map_info.tile_world_size(),
+        }
+    }
+
+    pub fn contains(&self, point: Vec2) -> bool {
+        self.rect.contains(point)
+    }
+
+    // Where a world position is on the minimap, in screen coordinates
+    pub fn world_to_minimap(&self, position: Vec2) -> Vec2 {
+        self.rect.point() + position / self.world_size * self.rect.size()
+    }
+
+    // The world position under a point on the minimap. Points off the minimap give the nearest position on the map, so
+    // dragging past its edge keeps the camera on the map.
+    pub fn minimap_to_world(&self, point: Vec2) -> Vec2 {
+        let fraction = ((point - self.rect.point()) / self.rect.size()).clamp(Vec2::ZERO, Vec2::ONE);
+        fraction * self.world_size
+    }
+
+    // A view of the world, in world coordinates, as a rectangle on the minimap
+    pub fn view_to_minimap(&self, view: Rect) -> Rect {
+        let corner = self.world_to_minimap(view.point());
+        let size = view.size() / self.world_size * self.rect.size();
+        Rect::new(corner.x, corner.y, size.x, size.y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::map::MapGenConfig;
+    use super::*;
+
+    fn map_info(width: i32, height: i32) -> MapInformation {
+        MapInformation { width, height, tile_size: 8., tile_scale: Vec2::new(2.5, 2.5) }
+    }
+
+    #[test]
+    fn test_layout_keeps_map_shape() {
+        let layout = MinimapLayout::new(&map_info(200, 100), Vec2::new(800., 600.));
+        assert_eq!(layout.rect, Rect::new(590., 490., 200., 100.));
+
+        // Each tile is a pixel on the minimap here, and a tile is 20 world units across
+        assert_eq!(layout.world_to_minimap(Vec2::new(200., 400.)), Vec2::new(600., 510.));
+        assert_eq!(layout.minimap_to_world(Vec2::new(600., 510.)), Vec2::new(200., 400.));
+        let view = layout.view_to_minimap(Rect::new(0., 0., 800., 600.));
+        assert_eq!(view.point(), Vec2::new(590., 490.));
+        assert!(view.size().abs_diff_eq(Vec2::new(40., 30.), 0.001));
+        assert!(layout.contains(Vec2::new(700., 550.)));
+        assert!(!layout.contains(Vec2::new(100., 550.)));
+    }
+
+    #[test]
+    fn test_minimap_to_world_stays_on_map() {
+        let layout = MinimapLayout::new(&map_info(100, 100), Vec2::new(800., 600.));
+        assert_eq!(layout.minimap_to_world(Vec2::new(0., 0.)), Vec2::ZERO);
+        assert_eq!(layout.minimap_to_world(Vec2::new(1000., 1000.)), Vec2::new(2000., 2000.));
+    }
+
+    #[test]
+    fn test_refresh_only_when_tiles_change() {
+        let mut game_map = GameMap::new(8, 4, MapGenConfig::default());
+        game_map.fill(|_, _| Tile::new(Biome::Grassland, 0));
+        let mut visibility = VisibilityMap::new(8, 4);
+        let mut minimap = Minimap::default();
+
+        // Nothing explored yet, so it's all black
+        assert!(minimap.refresh(&game_map, &visibility));
+        assert_eq!((minimap.image().width, minimap.image().height), (8, 4));
+        assert_eq!(minimap.image().get_pixel(1, 1), BLACK);
+        assert!(!minimap.refresh(&game_map, &visibility));
+
+        visibility.update([((1, 1), 1)]);
+        assert!(minimap.refresh(&game_map, &visibility));
+        assert_eq!(minimap.image().get_pixel(1, 1), tile_colour(&Tile::new(Biome::Grassland, 0)));
+        assert_eq!(minimap.image().get_pixel(6, 1), BLACK);
+
+        // Looking around what's already been explored changes nothing, and changing a tile does
+        visibility.update([((1, 1), 1)]);
+        assert!(!minimap.refresh(&game_map, &visibility));
+        game_map.set_tile(1, 1, Tile::new(Biome::Water, 0));
+        assert!(minimap.refresh(&game_map, &visibility));
+        assert_eq!(minimap.image().get_pixel(1, 1), tile_colour(&Tile::new(Biome::Water, 0)));
+
+        // Changes to unexplored tiles stay hidden
+        game_map.set_tile(6, 1, Tile::new(Biome::Water, 0));
+        assert!(minimap.refresh(&game_map, &visibility));
+        assert_eq!(minimap.image().get_pixel(6, 1), BLACK);
+    }
+
+    #[test]
+    fn test_refresh_only_draws_what_changed() {
+        let mut game_map = GameMap::new(8, 4, MapGenConfig::default());
+        game_map.fill(|_, _| Tile::new(Biome::Grassland, 0));
+        let mut visibility = VisibilityMap::new(8, 4);
+        visibility.update([((1, 1), 1)]);
+        let mut minimap = Minimap::default();
+        minimap.refresh(&game_map, &visibility);
+
+        // Scribble over a pixel nothing has changed under, which a full redraw would put back
+        minimap.image.set_pixel(0, 1, BLACK);
+        visibility.update([((5, 1), 1)]);
+        game_map.set_tile(1, 1, Tile::new(Biome::Water, 0));
+        assert!(minimap.refresh(&game_map, &visibility));
+        assert_eq!(minimap.image().get_pixel(0, 1), BLACK);
+        assert_eq!(minimap.image().get_pixel(1, 1), tile_colour(&Tile::new(Biome::Water, 0)));
+        assert_eq!(minimap.image().get_pixel(5, 1), tile_colour(&Tile::new(Biome::Grassland, 0)));
+
+        // A new map is drawn from scratch
+        game_map.fill(|_, _| Tile::new(Biome::Forest, 0));
+        assert!(minimap.refresh(&game_map, &visibility));
+        assert_eq!(minimap.image().get_pixel(0, 1), tile_colour(&Tile::new(Biome::Forest, 0)));
+    }
+}
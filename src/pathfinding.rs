@@ -0,0 +1,309 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use crate::map::{GameMap, ROAD_MOVEMENT_COST};
+
+pub(crate) const DIAGONAL_DISTANCE: f32 = std::f32::consts::SQRT_2;
+pub(crate) const NEIGHBOURS: [(i32, i32); 8] = [(1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1), (0, -1), (1, -1)];
+
+// A tile waiting to be searched, ordered so the BinaryHeap pops the lowest estimate first
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) struct OpenTile {
+    pub(crate) estimate: f32,
+    pub(crate) index: usize,
+}
+
+impl Eq for OpenTile {}
+
+impl Ord for OpenTile {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.estimate.total_cmp(&self.estimate).then_with(|| other.index.cmp(&self.index))
+    }
+}
+
+impl PartialOrd for OpenTile {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Octile distance, scaled by the cost of the cheapest tiles (roads) so it never overestimates
+fn heuristic(from: (i32, i32), to: (i32, i32)) -> f32 {
+    let (dx, dy) = ((from.0 - to.0).abs() as f32, (from.1 - to.1).abs() as f32);
+    (dx.max(dy) + (DIAGONAL_DISTANCE - 1.) * dx.min(dy)) * ROAD_MOVEMENT_COST
+}
+
+// How far a step from a tile to a neighbouring one is, or None if it's a diagonal step that would cut the corner
+// of an impassable tile
+pub(crate) fn step_distance(game_map: &GameMap, (x, y): (i32, i32), (dx, dy): (i32, i32)) -> Option<f32> {
+    if dx != 0 && dy != 0 {
+        (game_map.is_passable(x + dx, y) && game_map.is_passable(x, y + dy)).then_some(DIAGONAL_DISTANCE)
+    } else {
+        Some(1.)
+    }
+}
+
+// The cheapest path of tiles from start to goal (both included), moving to any of the 8 neighbouring tiles. Each
+// step costs its length times the movement cost of the tile stepped onto, and diagonal steps can't cut the corner
+// of an impassable tile. None if the goal can't be reached. The start tile itself doesn't need to be passable, so
+// anything stranded can find its way out.
+pub fn find_path(game_map: &GameMap, start: (i32, i32), goal: (i32, i32)) -> Option<Vec<(i32, i32)>> {
+    if !game_map.in_bounds(start.0, start.1) || !game_map.is_passable(goal.0, goal.1) {
+        return None;
+    }
+
+    let tile_count = game_map.tile_count();
+    let mut cost_so_far = vec![f32::INFINITY; tile_count];
+    let mut came_from = vec![usize::MAX; tile_count];
+    let mut closed = vec![false; tile_count];
+    let mut open = BinaryHeap::new();
+
+    let start_index = game_map.map_index(start.0, start.1) as usize;
+    let goal_index = game_map.map_index(goal.0, goal.1) as usize;
+    cost_so_far[start_index] = 0.;
+    open.push(OpenTile { estimate: heuristic(start, goal), index: start_index });
+
+    while let Some(OpenTile { index, .. }) = open.pop() {
+        if index == goal_index {
+            let mut path = vec![goal];
+            let mut current = index;
+            while current != start_index {
+                current = came_from[current];
+                path.push(game_map.map_coords(current as i32));
+            }
+            path.reverse();
+            return Some(path);
+        }
+        // A tile can be queued more than once, when a cheaper way to it is found later on
+        if closed[index] {
+            continue;
+        }
+        closed[index] = true;
+
+        let (x, y) = game_map.map_coords(index as i32);
+        for (dx, dy) in NEIGHBOURS {
+            let (next_x, next_y) = (x + dx, y + dy);
+            let Some(cost) = game_map.movement_cost(next_x, next_y) else { continue };
+
+            let Some(distance) = step_distance(game_map, (x, y), (dx, dy)) else { continue };
+
+            let next_index = game_map.map_index(next_x, next_y) as usize;
+            let next_cost = cost_so_far[index] + distance * cost;
+            if next_cost < cost_so_far[next_index] {
+                cost_so_far[next_index] = next_cost;
+                came_from[next_index] = index;
+                open.push(OpenTile { estimate: next_cost + heuristic((next_x, next_y), goal), index: next_index });
+            }
+        }
+    }
+
+    None
+}
+
+// Drop every waypoint that can be skipped by walking in a straight line past it, so paths don't zig-zag along the
+// grid. A shortcut has to stay on passable tiles, and on tiles no more costly than the ones it replaces, so it never
+// cuts through terrain (like a forest) the path went around.
+pub fn smooth_path(game_map: &GameMap, path: &[(i32, i32)]) -> Vec<(i32, i32)> {
+    if path.len() <= 2 {
+        return path.to_vec();
+    }
+
+    let cost = |(x, y): (i32, i32)| game_map.movement_cost(x, y).unwrap_or(0.);
+    let mut smoothed = vec![path[0]];
+    let mut anchor = 0;
+    while anchor < path.len() - 1 {
+        let mut next = anchor + 1;
+        let mut max_cost = cost(path[next]);
+        for candidate in anchor + 2..path.len() {
+            max_cost = max_cost.max(cost(path[candidate]));
+            if line_walkable(game_map, path[anchor], path[candidate], max_cost) {
+                next = candidate;
+            }
+        }
+        smoothed.push(path[next]);
+        anchor = next;
+    }
+    smoothed
+}
+
+// Whether every tile a straight line between two tile centres passes through (other than the first) is passable
+// and costs at most max_cost. A line passing exactly through a corner touches both tiles beside it.
+fn line_walkable(game_map: &GameMap, from: (i32, i32), to: (i32, i32), max_cost: f32) -> bool {
+    let walkable = |x: i32, y: i32| game_map.movement_cost(x, y).is_some_and(|cost| cost <= max_cost);
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let (steps_x, steps_y) = (dx.abs(), dy.abs());
+    let (mut x, mut y) = from;
+    let (mut ix, mut iy) = (0, 0);
+
+    while ix < steps_x || iy < steps_y {
+        // Which comes first along the line, crossing into the next column or into the next row
+        match ((1 + 2 * ix) * steps_y).cmp(&((1 + 2 * iy) * steps_x)) {
+            Ordering::Equal => {
+                if !walkable(x + dx.signum(), y) || !walkable(x, y + dy.signum()) {
+                    return false;
+                }
+                x += dx.signum();
+                y += dy.signum();
+                ix += 1;
+                iy += 1;
+            }
+            Ordering::Less => {
+                x += dx.signum();
+                ix += 1;
+            }
+            Ordering::Greater => {
+                y += dy.signum();
+                iy += 1;
+            }
+        }
+        if !walkable(x, y) {
+            return false;
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::map::{map_from_rows, Biome};
+    use super::*;
+
+    fn path_cost(game_map: &GameMap, path: &[(i32, i32)]) -> f32 {
+        path.windows(2)
+            .map(|step| {
+                let distance = if step[0].0 != step[1].0 && step[0].1 != step[1].1 { DIAGONAL_DISTANCE } else { 1. };
+                distance * game_map.movement_cost(step[1].0, step[1].1).unwrap()
+            })
+            .sum()
+    }
+
+    #[test]
+    fn test_straight_path() {
+        let game_map = map_from_rows(&["....."]);
+        assert_eq!(find_path(&game_map, (0, 0), (4, 0)), Some(vec![(0, 0), (1, 0), (2, 0), (3, 0), (4, 0)]));
+        assert_eq!(find_path(&game_map, (2, 0), (2, 0)), Some(vec![(2, 0)]));
+    }
+
+    #[test]
+    fn test_path_around_wall() {
+        let game_map = map_from_rows(&[
+            ".....",
+            ".###.",
+            "...#.",
+            "...#.",
+        ]);
+        let path = find_path(&game_map, (0, 3), (4, 3)).unwrap();
+
+        assert_eq!(path.first(), Some(&(0, 3)));
+        assert_eq!(path.last(), Some(&(4, 3)));
+        // Up the left side and over the top of the wall, without cutting the corners at either end of it
+        for step in path.windows(2) {
+            let ((x, y), (next_x, next_y)) = (step[0], step[1]);
+            assert!((next_x - x).abs() <= 1 && (next_y - y).abs() <= 1);
+            assert!(game_map.is_passable(next_x, next_y) && game_map.is_passable(next_x, y) && game_map.is_passable(x, next_y));
+        }
+        assert_eq!(path_cost(&game_map, &path), 10.);
+    }
+
+    #[test]
+    fn test_no_corner_cutting() {
+        let game_map = map_from_rows(&[
+            ".#",
+            "#.",
+        ]);
+        assert_eq!(find_path(&game_map, (0, 0), (1, 1)), None);
+
+        let game_map = map_from_rows(&[
+            "..",
+            "#.",
+        ]);
+        assert_eq!(find_path(&game_map, (0, 0), (1, 1)), Some(vec![(0, 0), (1, 0), (1, 1)]));
+    }
+
+    #[test]
+    fn test_unreachable_goal() {
+        let game_map = map_from_rows(&[
+            "..#..",
+            "..#..",
+            "..#..",
+        ]);
+        assert_eq!(find_path(&game_map, (0, 0), (4, 0)), None);
+        // Impassable and off map goals can't be reached either
+        assert_eq!(find_path(&game_map, (0, 0), (2, 0)), None);
+        assert_eq!(find_path(&game_map, (0, 0), (9, 0)), None);
+    }
+
+    #[test]
+    fn test_path_prefers_cheap_tiles() {
+        let game_map = map_from_rows(&[
+            "=====",
+            ".fff.",
+        ]);
+        // Walking through the forest is shorter, but the road round it is quicker
+        let path = find_path(&game_map, (0, 1), (4, 1)).unwrap();
+        assert!(path.iter().all(|&(x, y)| game_map.tile(x, y).unwrap().biome != Biome::Forest), "{:?}", path);
+        assert!(path_cost(&game_map, &path) < 4. * 2.);
+    }
+
+    #[test]
+    fn test_path_from_impassable_start() {
+        let game_map = map_from_rows(&["#.."]);
+        assert_eq!(find_path(&game_map, (0, 0), (2, 0)), Some(vec![(0, 0), (1, 0), (2, 0)]));
+    }
+
+    #[test]
+    fn test_smooth_path() {
+        let game_map = map_from_rows(&[
+            "......",
+            "......",
+            "......",
+        ]);
+        // A staircase across open ground becomes one straight line
+        let path = vec![(0, 0), (1, 0), (1, 1), (2, 1), (3, 1), (4, 2), (5, 2)];
+        assert_eq!(smooth_path(&game_map, &path), vec![(0, 0), (5, 2)]);
+    }
+
+    #[test]
+    fn test_smooth_path_keeps_corners_around_walls() {
+        let game_map = map_from_rows(&[
+            ".....",
+            ".###.",
+            "...#.",
+            "...#.",
+        ]);
+        let path = find_path(&game_map, (0, 3), (4, 3)).unwrap();
+        let smoothed = smooth_path(&game_map, &path);
+
+        assert_eq!((smoothed.first(), smoothed.last()), (path.first(), path.last()));
+        assert!(smoothed.len() < path.len());
+        for leg in smoothed.windows(2) {
+            assert!(line_walkable(&game_map, leg[0], leg[1], 1.));
+        }
+    }
+
+    #[test]
+    fn test_smooth_path_stays_off_costly_tiles() {
+        let game_map = map_from_rows(&[
+            "=====",
+            "=fff=",
+            "=====",
+        ]);
+        // Cutting across the forest would be shorter, but slower than going round on the road
+        let path = vec![(0, 1), (0, 0), (1, 0), (2, 0), (3, 0), (4, 0), (4, 1)];
+        let smoothed = smooth_path(&game_map, &path);
+        assert!(smoothed.windows(2).all(|leg| line_walkable(&game_map, leg[0], leg[1], ROAD_MOVEMENT_COST)), "{:?}", smoothed);
+    }
+
+    #[test]
+    fn test_line_walkable_through_corners() {
+        let game_map = map_from_rows(&[
+            "...",
+            ".#.",
+            "...",
+        ]);
+        // Exactly through the corner of the water
+        assert!(!line_walkable(&game_map, (0, 1), (1, 0), 1.));
+        assert!(!line_walkable(&game_map, (0, 1), (2, 1), 1.));
+        assert!(line_walkable(&game_map, (0, 0), (2, 0), 1.));
+        assert!(line_walkable(&game_map, (0, 2), (2, 2), 1.));
+    }
+}
@@ -0,0 +1,215 @@
+use macroquad::color::{hsl_to_rgb, Color};
+use macroquad::prelude::Image;
+use crate::animations::animation::{CreatureAnimations, Frame, LiegeSprite, Size, SpriteSheet};
+use crate::animations::aseprite::{FrameTag, TagDirection};
+use crate::map::{CORNER_NORTH_EAST, CORNER_NORTH_WEST, CORNER_SOUTH_EAST, CORNER_SOUTH_WEST, EDGE_EAST, EDGE_NORTH, EDGE_SOUTH, EDGE_WEST};
+
+// Stand-ins for art that isn't there, so the game can run without the (undistributed) graphical resources
+pub const PLACEHOLDER_FRAME_SIZE: i32 = 9;
+const PLACEHOLDER_COLUMNS: usize = 16;
+const PLACEHOLDER_FRAME_MILLIS: u32 = 100;
+const CHECKER_SIZE: u32 = 4;
+const TILE_SIZE: u32 = 8;
+const TILESET_SIZE: u16 = 128;
+// How far into a tile placeholder edges reach, in pixels
+const EDGE_DEPTH: u32 = 3;
+// Texture ids ending in this are edge tile sets, drawn over the edges of other tiles
+pub const EDGE_TILESET_SUFFIX: &str = "_edges";
+
+// A stable colour for an asset id, so each creature gets its own
+pub fn placeholder_colour(id: &str) -> Color {
+    // FNV-1a, which is enough to spread similar ids around the colour wheel
+    let hash = id.bytes().fold(0x811c9dc5u32, |hash, byte| (hash ^ byte as u32).wrapping_mul(0x01000193));
+    hsl_to_rgb((hash % 360) as f32 / 360., 0.6, 0.5)
+}
+
+// Sprite sheet metadata with a frame for every frame the given animation definitions play. Frame tags can't say how
+// many frames they cover without the real sheet, so each tag gets a single frame.
+pub fn placeholder_sprite_sheet(definitions: &[&CreatureAnimations]) -> SpriteSheet {
+    let empty_sheet = SpriteSheet::new(Vec::new(), Vec::new());
+    let mut frame_names: Vec<String> = Vec::new();
+    let mut frame_tags = Vec::new();
+
+    for creature_animations in definitions {
+        for definition in &creature_animations.animations {
+            let names = match &definition.tag {
+                Some(tag) => {
+                    frame_tags.push(FrameTag { name: tag.clone(), from: frame_names.len(), to: frame_names.len(), direction: TagDirection::Forward, repeat: None });
+                    vec![format!("{}_{}.png", creature_animations.creature, tag)]
+                }
+                // A broken definition is reported when the animations are built
+                None => definition.frame_names(&creature_animations.creature, &empty_sheet).unwrap_or_default(),
+            };
+            for name in names {
+                if !frame_names.contains(&name) {
+                    frame_names.push(name);
+                }
+            }
+        }
+    }
+
+    let frames = frame_names.into_iter().enumerate().map(|(index, name)| (name, placeholder_sprite(index))).collect();
+    SpriteSheet::new(frames, frame_tags)
+}
+
+fn placeholder_sprite(index: usize) -> LiegeSprite {
+    let size = PLACEHOLDER_FRAME_SIZE;
+    LiegeSprite {
+        frame: Frame {
+            x: (index % PLACEHOLDER_COLUMNS) as i32 * size,
+            y: (index / PLACEHOLDER_COLUMNS) as i32 * size,
+            w: size,
+            h: size,
+        },
+        rotated: false,
+        trimmed: false,
+        sprite_source_size: Frame { x: 0, y: 0, w: size, h: size },
+        source_size: Size { w: size, h: size },
+        duration: Some(PLACEHOLDER_FRAME_MILLIS),
+    }
+}
+
+// A texture for a sprite sheet, with a bordered box in the given colour wherever the sheet has a frame
+pub fn placeholder_sprite_image(sprite_sheet: &SpriteSheet, colour: Color) -> Image {
+    let (width, height) = sprite_sheet.frames.values().fold((1, 1), |(width, height), sprite| {
+        let (w, h) = if sprite.rotated { (sprite.frame.h, sprite.frame.w) } else { (sprite.frame.w, sprite.frame.h) };
+        (width.max(sprite.frame.x + w), height.max(sprite.frame.y + h))
+    });
+
+    let border = Color::new(colour.r * 0.5, colour.g * 0.5, colour.b * 0.5, 1.);
+    let mut image = Image::gen_image_color(width as u16, height as u16, Color::new(0., 0., 0., 0.));
+    for sprite in sprite_sheet.frames.values() {
+        let (w, h) = if sprite.rotated { (sprite.frame.h, sprite.frame.w) } else { (sprite.frame.w, sprite.frame.h) };
+        for y in 0..h {
+            for x in 0..w {
+                let edge = x == 0 || y == 0 || x == w - 1 || y == h - 1;
+                image.set_pixel((sprite.frame.x + x) as u32, (sprite.frame.y + y) as u32, if edge { border } else { colour });
+            }
+        }
+    }
+    image
+}
+
+// A checkerboard texture for tile sets. Each tile along the x axis gets a slightly different shade, so different
+// tile types can still be told apart.
+pub fn placeholder_tile_image(colour: Color) -> Image {
+    let mut image = Image::gen_image_color(TILESET_SIZE, TILESET_SIZE, colour);
+    for y in 0..TILESET_SIZE as u32 {
+        for x in 0..TILESET_SIZE as u32 {
+            let shade = 0.6 + 0.1 * ((x / TILE_SIZE) % 5) as f32;
+            let dark = ((x / CHECKER_SIZE) + (y / CHECKER_SIZE)) % 2 == 1;
+            let shade = if dark { shade * 0.7 } else { shade };
+            image.set_pixel(x, y, Color::new(colour.r * shade, colour.g * shade, colour.b * shade, 1.));
+        }
+    }
+    image
+}
+
+// An edge tile set: a row of tiles with a band in the given colour along the sides in each edge mask, and a row with
+// a square in the corners in each corner mask. The rest is transparent, so the tile underneath shows through.
+pub fn placeholder_edge_image(colour: Color) -> Image {
+    let masks = 16;
+    let mut image = Image::gen_image_color(masks * TILE_SIZE as u16, 2 * TILE_SIZE as u16, Color::new(0., 0., 0., 0.));
+    let (near, far) = (EDGE_DEPTH, TILE_SIZE - EDGE_DEPTH);
+    for mask in 0..masks as u8 {
+        for y in 0..TILE_SIZE {
+            for x in 0..TILE_SIZE {
+                let on = |bit: u8| mask & bit != 0;
+                let edge = (on(EDGE_NORTH) && y < near) || (on(EDGE_EAST) && x >= far) || (on(EDGE_SOUTH) && y >= far) || (on(EDGE_WEST) && x < near);
+                let corner = (on(CORNER_NORTH_EAST) && x >= far && y < near)
+                    || (on(CORNER_SOUTH_EAST) && x >= far && y >= far)
+                    || (on(CORNER_SOUTH_WEST) && x < near && y >= far)
+                    || (on(CORNER_NORTH_WEST) && x < near && y < near);
+                let left = mask as u32 * TILE_SIZE + x;
+                if edge {
+                    image.set_pixel(left, y, colour);
+                }
+                if corner {
+                    image.set_pixel(left, TILE_SIZE + y, colour);
+                }
+            }
+        }
+    }
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::animations::animation::AnimationDefinition;
+    use super::*;
+
+    fn creature_animations() -> CreatureAnimations {
+        CreatureAnimations {
+            creature: "rogue".to_string(),
+            sprite_sheet: "rogue".to_string(),
+            animations: vec![
+                AnimationDefinition { name: "walk_right".to_string(), frame_prefix: Some("rogue_walk_right_".to_string()), frame_count: 4, ..Default::default() },
+                AnimationDefinition { name: "idle".to_string(), frames: vec!["rogue_walk_right_1.png".to_string()], ..Default::default() },
+                AnimationDefinition { name: "attack".to_string(), tag: Some("attack".to_string()), ..Default::default() },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_placeholder_sprite_sheet_has_every_frame() {
+        let definitions = creature_animations();
+        let sprite_sheet = placeholder_sprite_sheet(&[&definitions]);
+
+        // Shared frames are only added once
+        assert_eq!(sprite_sheet.frame_names.len(), 5);
+        assert_eq!(sprite_sheet.frames["rogue_walk_right_2.png"].frame, Frame { x: 9, y: 0, w: 9, h: 9 });
+        assert_eq!(sprite_sheet.frame_tag("attack").map(|tag| (tag.from, tag.to)), Some((4, 4)));
+
+        // Every animation builds, even without an fps, as the placeholder frames have durations
+        let animations = definitions.build(&sprite_sheet).unwrap();
+        assert_eq!(animations["rogue_walk_right"].frames.len(), 4);
+        assert_eq!(animations["rogue_attack"].frames.len(), 1);
+    }
+
+    #[test]
+    fn test_placeholder_sprite_image_covers_frames() {
+        let definitions = creature_animations();
+        let sprite_sheet = placeholder_sprite_sheet(&[&definitions]);
+        let colour = placeholder_colour("rogue");
+        let image = placeholder_sprite_image(&sprite_sheet, colour);
+
+        assert_eq!((image.width, image.height), (45, 9));
+        let inside = image.get_pixel(13, 4);
+        assert!((inside.r - colour.r).abs() < 0.01 && (inside.g - colour.g).abs() < 0.01 && inside.a == 1.);
+        // Frames have a darker border
+        assert_ne!(image.get_pixel(9, 4), inside);
+    }
+
+    #[test]
+    fn test_placeholder_colours_differ_by_id() {
+        assert_eq!(placeholder_colour("rogue"), placeholder_colour("rogue"));
+        assert_ne!(placeholder_colour("rogue"), placeholder_colour("goblin"));
+    }
+
+    #[test]
+    fn test_placeholder_tiles_are_checkered() {
+        let image = placeholder_tile_image(placeholder_colour("plains"));
+        assert_ne!(image.get_pixel(0, 0), image.get_pixel(4, 0));
+        assert_eq!(image.get_pixel(0, 0), image.get_pixel(4, 4));
+        // The next tile over is a different shade
+        assert_ne!(image.get_pixel(0, 0), image.get_pixel(8, 0));
+    }
+
+    #[test]
+    fn test_placeholder_edges_follow_masks() {
+        let image = placeholder_edge_image(placeholder_colour("sand"));
+        let tile = |mask: u32, row: u32, x: u32, y: u32| image.get_pixel(mask * TILE_SIZE + x, row * TILE_SIZE + y).a;
+        assert_eq!((image.width, image.height), (128, 16));
+
+        // Edges along the sides in the mask, and nothing in the middle
+        assert_eq!(tile(EDGE_NORTH as u32, 0, 4, 0), 1.);
+        assert_eq!(tile(EDGE_NORTH as u32, 0, 4, 7), 0.);
+        assert_eq!(tile((EDGE_EAST | EDGE_WEST) as u32, 0, 0, 4), 1.);
+        assert_eq!(tile((EDGE_EAST | EDGE_WEST) as u32, 0, 7, 4), 1.);
+        assert_eq!(tile(15, 0, 4, 4), 0.);
+        // Corners only in the corner
+        assert_eq!(tile(CORNER_SOUTH_WEST as u32, 1, 0, 7), 1.);
+        assert_eq!(tile(CORNER_SOUTH_WEST as u32, 1, 4, 7), 0.);
+        assert_eq!(tile(0, 1, 0, 0), 0.);
+    }
+}
@@ -0,0 +1,156 @@
+use std::f32::consts::FRAC_PI_2;
+use macroquad::prelude::*;
+use crate::animations::animation::LiegeSprite;
+
+// Everything draw_texture_ex needs to put a single sprite frame on screen
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpriteDrawRects {
+    pub source: Rect,
+    pub dest: Vec2,
+    pub dest_size: Vec2,
+    pub rotation: f32,
+    pub flip_x: bool,
+    pub flip_y: bool,
+}
+
+// Work out where a packed sprite frame comes from in its texture, and where it should land in the world. The
+// position is the top left corner of the untrimmed sprite (its `source_size`), so trimmed frames are offset by
+// `sprite_source_size`, and frames the packer rotated 90 degrees clockwise are rotated back. Mirrored sprites are
+// flipped inside their source size, so the trim offset is measured from the right hand edge instead.
+pub fn sprite_draw_rects(sprite: &LiegeSprite, position: Vec2, scale: Vec2, flip_x: bool) -> SpriteDrawRects {
+    let frame = sprite.frame;
+    let offset_x = if flip_x {
+        sprite.source_size.w - sprite.sprite_source_size.x - frame.w
+    } else {
+        sprite.sprite_source_size.x
+    };
+    let offset = scale * vec2(offset_x as f32, sprite.sprite_source_size.y as f32);
+    let size = scale * vec2(frame.w as f32, frame.h as f32);
+
+    if sprite.rotated {
+        // The frame size is the unrotated size, so the region in the texture is h wide and w tall. It is drawn with
+        // the same footprint, then turned back a quarter turn around its centre. The turn maps the region's vertical
+        // axis onto the screen's horizontal one, so mirroring flips it vertically.
+        let center = position + offset + size / 2.;
+        let dest_size = vec2(size.y, size.x);
+        SpriteDrawRects {
+            source: Rect::new(frame.x as f32, frame.y as f32, frame.h as f32, frame.w as f32),
+            dest: center - dest_size / 2.,
+            dest_size,
+            rotation: -FRAC_PI_2,
+            flip_x: false,
+            flip_y: flip_x,
+        }
+    } else {
+        SpriteDrawRects {
+            source: Rect::new(frame.x as f32, frame.y as f32, frame.w as f32, frame.h as f32),
+            dest: position + offset,
+            dest_size: size,
+            rotation: 0.,
+            flip_x,
+            flip_y: false,
+        }
+    }
+}
+
+// The on-screen size of a sprite, before trimming. Used for selection and hit testing, so that it doesn't change
+// from frame to frame.
+pub fn sprite_bounds(sprite: &LiegeSprite, scale: Vec2) -> Vec2 {
+    scale * vec2(sprite.source_size.w as f32, sprite.source_size.h as f32)
+}
+
+pub fn draw_sprite(texture: &Texture2D, sprite: &LiegeSprite, position: Vec2, scale: Vec2, flip_x: bool) {
+    let rects = sprite_draw_rects(sprite, position, scale, flip_x);
+    let draw_params = DrawTextureParams {
+        source: Some(rects.source),
+        dest_size: Some(rects.dest_size),
+        rotation: rects.rotation,
+        flip_x: rects.flip_x,
+        flip_y: rects.flip_y,
+        ..Default::default()
+    };
+
+    draw_texture_ex(texture, rects.dest.x, rects.dest.y, WHITE, draw_params);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::animations::animation::{Frame, Size};
+
+    fn sprite(frame: Frame, rotated: bool, sprite_source_size: Frame, source_size: Size) -> LiegeSprite {
+        LiegeSprite {
+            frame,
+            rotated,
+            trimmed: sprite_source_size.w != source_size.w || sprite_source_size.h != source_size.h,
+            sprite_source_size,
+            source_size,
+            duration: None,
+        }
+    }
+
+    #[test]
+    fn test_untrimmed_sprite() {
+        let sprite = sprite(Frame { x: 26, y: 0, w: 9, h: 9 }, false, Frame { x: 0, y: 0, w: 9, h: 9 }, Size { w: 9, h: 9 });
+        let rects = sprite_draw_rects(&sprite, vec2(100., 50.), vec2(2., 2.), false);
+
+        assert_eq!(rects.source, Rect::new(26., 0., 9., 9.));
+        assert_eq!(rects.dest, vec2(100., 50.));
+        assert_eq!(rects.dest_size, vec2(18., 18.));
+        assert_eq!(rects.rotation, 0.);
+    }
+
+    #[test]
+    fn test_trimmed_sprite_is_offset_into_source_size() {
+        // A 9x9 sprite trimmed down to the 5x6 region starting at (3, 2)
+        let sprite = sprite(Frame { x: 40, y: 10, w: 5, h: 6 }, false, Frame { x: 3, y: 2, w: 5, h: 6 }, Size { w: 9, h: 9 });
+        let rects = sprite_draw_rects(&sprite, vec2(100., 50.), vec2(2., 2.), false);
+
+        assert_eq!(rects.source, Rect::new(40., 10., 5., 6.));
+        assert_eq!(rects.dest, vec2(106., 54.));
+        assert_eq!(rects.dest_size, vec2(10., 12.));
+        assert_eq!(sprite_bounds(&sprite, vec2(2., 2.)), vec2(18., 18.));
+    }
+
+    #[test]
+    fn test_rotated_sprite_is_turned_back() {
+        // A 4x8 sprite stored in the texture as an 8x4 region, trimmed by one pixel on the left
+        let sprite = sprite(Frame { x: 10, y: 20, w: 4, h: 8 }, true, Frame { x: 1, y: 0, w: 4, h: 8 }, Size { w: 5, h: 8 });
+        let rects = sprite_draw_rects(&sprite, vec2(0., 0.), vec2(2., 2.), false);
+
+        assert_eq!(rects.source, Rect::new(10., 20., 8., 4.));
+        assert_eq!(rects.dest_size, vec2(16., 8.));
+        assert_eq!(rects.rotation, -FRAC_PI_2);
+
+        // Rotating around the centre of the destination rect must land the sprite on its trimmed footprint,
+        // which is 8x16 starting at (2, 0)
+        let center = rects.dest + rects.dest_size / 2.;
+        assert_eq!(center, vec2(6., 8.));
+        assert_eq!(center - vec2(rects.dest_size.y, rects.dest_size.x) / 2., vec2(2., 0.));
+    }
+
+    #[test]
+    fn test_mirrored_trimmed_sprite_is_offset_from_the_right() {
+        // The same trimmed sprite as above: 3 pixels of padding on the left and 1 on the right, which swap over
+        let sprite = sprite(Frame { x: 40, y: 10, w: 5, h: 6 }, false, Frame { x: 3, y: 2, w: 5, h: 6 }, Size { w: 9, h: 9 });
+        let rects = sprite_draw_rects(&sprite, vec2(100., 50.), vec2(2., 2.), true);
+
+        assert_eq!(rects.source, Rect::new(40., 10., 5., 6.));
+        assert_eq!(rects.dest, vec2(102., 54.));
+        assert_eq!(rects.dest_size, vec2(10., 12.));
+        assert!(rects.flip_x);
+        assert!(!rects.flip_y);
+    }
+
+    #[test]
+    fn test_mirrored_rotated_sprite_flips_vertically() {
+        let sprite = sprite(Frame { x: 10, y: 20, w: 4, h: 8 }, true, Frame { x: 1, y: 0, w: 4, h: 8 }, Size { w: 5, h: 8 });
+        let rects = sprite_draw_rects(&sprite, vec2(0., 0.), vec2(2., 2.), true);
+
+        assert!(!rects.flip_x);
+        assert!(rects.flip_y);
+        // Mirrored, the one pixel of trim is on the right, so the footprint now starts at (0, 0)
+        let center = rects.dest + rects.dest_size / 2.;
+        assert_eq!(center - vec2(rects.dest_size.y, rects.dest_size.x) / 2., vec2(0., 0.));
+    }
+}
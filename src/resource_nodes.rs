@@ -0,0 +1,300 @@
+use std::collections::HashMap;
+use legion::{Entity, IntoQuery, Read, World};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use crate::components::{RegrowingComponent, ResourceNodeComponent};
+use crate::map::{Biome, GameMap, CHUNK_SIZE};
+
+// Mixed into the map seed, so resource nodes don't follow the same random numbers as the rest of generation
+const RESOURCE_SEED_SALT: u64 = 0x5eed_4e50;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ResourceKind {
+    Tree,
+    Gold,
+    Stone,
+}
+
+impl ResourceKind {
+    pub const ALL: [ResourceKind; 3] = [ResourceKind::Tree, ResourceKind::Gold, ResourceKind::Stone];
+
+    // Id of the node's texture in the asset manifest
+    pub fn texture_id(&self) -> &'static str {
+        match self {
+            ResourceKind::Tree => "tree",
+            ResourceKind::Gold => "gold_vein",
+            ResourceKind::Stone => "stone_outcrop",
+        }
+    }
+
+    // How much a new node holds
+    pub fn capacity(&self) -> u32 {
+        match self {
+            ResourceKind::Tree => 50,
+            ResourceKind::Gold => 200,
+            ResourceKind::Stone => 150,
+        }
+    }
+
+    // How much grows back each second, up to the node's capacity. Trees grow back, and veins and outcrops don't.
+    pub fn regrowth(&self) -> Option<f32> {
+        match self {
+            ResourceKind::Tree => Some(0.5),
+            ResourceKind::Gold | ResourceKind::Stone => None,
+        }
+    }
+
+    // Chance of a node turning up on any one tile of a biome
+    pub fn chance(&self, biome: Biome) -> f64 {
+        match (self, biome) {
+            (ResourceKind::Tree, Biome::Forest) => 0.3,
+            (ResourceKind::Tree, Biome::Grassland) => 0.02,
+            (ResourceKind::Tree, Biome::Hills) => 0.02,
+            (ResourceKind::Gold, Biome::Hills) => 0.005,
+            (ResourceKind::Gold, Biome::Mountain) => 0.02,
+            (ResourceKind::Stone, Biome::Hills) => 0.03,
+            (ResourceKind::Stone, Biome::Mountain) => 0.06,
+            (ResourceKind::Stone, Biome::Sand) => 0.005,
+            _ => 0.,
+        }
+    }
+}
+
+// Scatter resource nodes over the map by biome, at most one to a tile, and only on open ground (not on roads, rivers
+// or anything else). The same map always gets the same nodes.
+pub fn place_resource_nodes(game_map: &GameMap) -> Vec<ResourceNodeComponent> {
+    let mut rng = StdRng::seed_from_u64(game_map.config.seed as u64 ^ RESOURCE_SEED_SALT);
+    let mut nodes = Vec::new();
+    for (tile_position, tile) in game_map.tiles() {
+        let roll: f64 = rng.gen();
+        if !tile.is_passable() || tile.feature.is_some() {
+            continue;
+        }
+        // Each kind takes its share of the roll in turn, so the chances of each stay as given
+        let mut threshold = 0.;
+        for kind in ResourceKind::ALL {
+            threshold += kind.chance(tile.biome);
+            if roll < threshold {
+                nodes.push(ResourceNodeComponent::new(kind, tile_position));
+                break;
+            }
+        }
+    }
+    nodes
+}
+
+// Where every resource node is, by the map chunk it's in, so finding the nodes in an area (like the one on screen)
+// only looks at the chunks overlapping it rather than every node on the map
+#[derive(Default)]
+pub struct ResourceNodeIndex {
+    chunks: HashMap<(i32, i32), ChunkNodes>,
+}
+
+// The tile and entity of each node in a chunk
+type ChunkNodes = Vec<((i32, i32), Entity)>;
+
+impl ResourceNodeIndex {
+    fn chunk((x, y): (i32, i32)) -> (i32, i32) {
+        (x.div_euclid(CHUNK_SIZE), y.div_euclid(CHUNK_SIZE))
+    }
+
+    pub fn insert(&mut self, tile: (i32, i32), entity: Entity) {
+        self.chunks.entry(Self::chunk(tile)).or_default().push((tile, entity));
+    }
+
+    pub fn remove(&mut self, tile: (i32, i32), entity: Entity) {
+        if let Some(nodes) = self.chunks.get_mut(&Self::chunk(tile)) {
+            nodes.retain(|&node| node != (tile, entity));
+        }
+    }
+
+    // The node on a tile, if there is one
+    pub fn at(&self, tile: (i32, i32)) -> Option<Entity> {
+        self.chunks.get(&Self::chunk(tile))?.iter().find(|(node_tile, _)| *node_tile == tile).map(|(_, entity)| *entity)
+    }
+
+    // Every node on a tile from min to max (inclusive)
+    pub fn nodes_in(&self, (min_x, min_y): (i32, i32), (max_x, max_y): (i32, i32)) -> impl Iterator<Item = Entity> + '_ {
+        let (first_chunk, last_chunk) = (Self::chunk((min_x, min_y)), Self::chunk((max_x, max_y)));
+        (first_chunk.1..=last_chunk.1)
+            .flat_map(move |chunk_y| (first_chunk.0..=last_chunk.0).map(move |chunk_x| (chunk_x, chunk_y)))
+            .filter_map(|chunk| self.chunks.get(&chunk))
+            .flatten()
+            .filter(move |((x, y), _)| (min_x..=max_x).contains(x) && (min_y..=max_y).contains(y))
+            .map(|(_, entity)| *entity)
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunks.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+// Place the map's resource nodes and add them to the world, returning the index of where they are
+pub fn spawn_resource_nodes(world: &mut World, game_map: &GameMap) -> ResourceNodeIndex {
+    let mut index = ResourceNodeIndex::default();
+    for node in place_resource_nodes(game_map) {
+        index.insert(node.tile, world.push((node,)));
+    }
+    index
+}
+
+// Take up to `amount` out of a node, returning how much was taken. A node worked out for good is removed, and one
+// that grows back is marked as regrowing until it's full again.
+pub fn harvest_resource_node(world: &mut World, index: &mut ResourceNodeIndex, entity: Entity, amount: u32) -> u32 {
+    let Some(mut entry) = world.entry(entity) else { return 0 };
+    let Ok(node) = entry.get_component_mut::<ResourceNodeComponent>() else { return 0 };
+    let taken = node.work(amount);
+    let node = *node;
+
+    if node.is_exhausted() {
+        index.remove(node.tile, entity);
+        world.remove(entity);
+    } else if taken > 0 && node.kind.regrowth().is_some() {
+        entry.add_component(RegrowingComponent);
+    }
+    taken
+}
+
+// The closest node of a kind to a tile that still has something left in it, with its entity
+pub fn nearest_resource_node(world: &World, kind: ResourceKind, (x, y): (i32, i32)) -> Option<(Entity, ResourceNodeComponent)> {
+    <(Entity, Read<ResourceNodeComponent>)>::query()
+        .iter(world)
+        .filter(|(_, node)| node.kind == kind && !node.is_depleted())
+        .min_by_key(|(_, node)| (node.tile.0 - x).pow(2) + (node.tile.1 - y).pow(2))
+        .map(|(entity, node)| (*entity, *node))
+}
+
+#[cfg(test)]
+mod tests {
+    use legion::EntityStore;
+    use crate::map::{MapGenConfig, Tile, Feature};
+    use super::*;
+
+    fn striped_map() -> GameMap {
+        // A stripe of each biome, 20 tiles wide
+        let mut game_map = GameMap::new(120, 50, MapGenConfig::default());
+        game_map.fill(|x, _| Tile::new(Biome::ALL[(x / 20) as usize], 0));
+        game_map
+    }
+
+    #[test]
+    fn test_nodes_follow_biome_rules() {
+        let game_map = striped_map();
+        let nodes = place_resource_nodes(&game_map);
+        let count = |kind: ResourceKind, biome: Biome| {
+            nodes.iter().filter(|node| node.kind == kind && game_map.tile(node.tile.0, node.tile.1).unwrap().biome == biome).count()
+        };
+
+        // Nothing anywhere it has no chance of being
+        for kind in ResourceKind::ALL {
+            for biome in Biome::ALL {
+                if kind.chance(biome) == 0. {
+                    assert_eq!(count(kind, biome), 0, "{:?} on {:?}", kind, biome);
+                }
+            }
+        }
+        // Forests are thick with trees, and mountains have more gold than hills
+        let stripe = 20. * 50.;
+        assert!((count(ResourceKind::Tree, Biome::Forest) as f64 / stripe - 0.3).abs() < 0.05);
+        assert!(count(ResourceKind::Gold, Biome::Mountain) > count(ResourceKind::Gold, Biome::Hills));
+
+        // At most one to a tile
+        let mut tiles: Vec<_> = nodes.iter().map(|node| node.tile).collect();
+        tiles.sort();
+        tiles.dedup();
+        assert_eq!(tiles.len(), nodes.len());
+    }
+
+    #[test]
+    fn test_nodes_stay_off_features() {
+        let mut game_map = GameMap::new(10, 10, MapGenConfig::default());
+        game_map.fill(|x, _| if x == 5 { Tile::new(Biome::Forest, 0).with_feature(Feature::Road) } else { Tile::new(Biome::Forest, 0) });
+        let nodes = place_resource_nodes(&game_map);
+        assert!(!nodes.is_empty());
+        assert!(nodes.iter().all(|node| node.tile.0 != 5));
+    }
+
+    #[test]
+    fn test_placement_is_deterministic() {
+        let game_map = striped_map();
+        assert_eq!(place_resource_nodes(&game_map), place_resource_nodes(&game_map));
+
+        let mut other_seed = GameMap::new(120, 50, MapGenConfig::with_seed(7));
+        other_seed.fill(|x, _| Tile::new(Biome::ALL[(x / 20) as usize], 0));
+        assert_ne!(place_resource_nodes(&game_map), place_resource_nodes(&other_seed));
+    }
+
+    #[test]
+    fn test_nearest_resource_node() {
+        let mut world = World::default();
+        world.push((ResourceNodeComponent::new(ResourceKind::Tree, (10, 10)),));
+        let near = world.push((ResourceNodeComponent::new(ResourceKind::Tree, (3, 2)),));
+        world.push((ResourceNodeComponent::new(ResourceKind::Stone, (0, 1)),));
+        let mut empty = ResourceNodeComponent::new(ResourceKind::Tree, (0, 0));
+        empty.work(u32::MAX);
+        world.push((empty,));
+
+        // Closest of the kind asked for, skipping anything already worked out
+        let (entity, node) = nearest_resource_node(&world, ResourceKind::Tree, (0, 0)).unwrap();
+        assert_eq!((entity, node.tile), (near, (3, 2)));
+        assert_eq!(nearest_resource_node(&world, ResourceKind::Stone, (9, 9)).map(|(_, node)| node.tile), Some((0, 1)));
+        assert!(nearest_resource_node(&world, ResourceKind::Gold, (0, 0)).is_none());
+    }
+
+    #[test]
+    fn test_spawn_resource_nodes() {
+        let mut world = World::default();
+        let index = spawn_resource_nodes(&mut world, &striped_map());
+        assert!(!index.is_empty());
+        assert_eq!(<Read<ResourceNodeComponent>>::query().iter(&world).count(), index.len());
+
+        // Every node can be found by its tile, and in any area covering it
+        for (entity, node) in <(Entity, Read<ResourceNodeComponent>)>::query().iter(&world) {
+            assert_eq!(index.at(node.tile), Some(*entity));
+            assert!(index.nodes_in((node.tile.0 - 1, node.tile.1), node.tile).any(|found| found == *entity));
+        }
+    }
+
+    #[test]
+    fn test_nodes_in_only_returns_the_area() {
+        let mut world = World::default();
+        let mut index = ResourceNodeIndex::default();
+        let tiles = [(0, 0), (31, 31), (32, 0), (40, 40), (100, 5)];
+        let entities: Vec<_> = tiles.iter().map(|&tile| world.push((ResourceNodeComponent::new(ResourceKind::Tree, tile),))).collect();
+        for (&tile, &entity) in tiles.iter().zip(&entities) {
+            index.insert(tile, entity);
+        }
+
+        assert_eq!(index.nodes_in((10, 0), (40, 35)).collect::<Vec<_>>(), vec![entities[1], entities[2]]);
+
+        index.remove((31, 31), entities[1]);
+        assert_eq!(index.at((31, 31)), None);
+        assert_eq!(index.len(), 4);
+    }
+
+    #[test]
+    fn test_harvest_resource_node() {
+        let mut world = World::default();
+        let mut index = ResourceNodeIndex::default();
+        let tree = world.push((ResourceNodeComponent::new(ResourceKind::Tree, (0, 0)),));
+        let gold = world.push((ResourceNodeComponent::new(ResourceKind::Gold, (1, 0)),));
+        index.insert((0, 0), tree);
+        index.insert((1, 0), gold);
+
+        // A tree starts growing back once it's worked
+        assert_eq!(harvest_resource_node(&mut world, &mut index, tree, 10), 10);
+        assert!(world.entry_ref(tree).unwrap().get_component::<RegrowingComponent>().is_ok());
+
+        // A vein that's been worked out is gone
+        assert_eq!(harvest_resource_node(&mut world, &mut index, gold, 10), 10);
+        assert!(world.entry_ref(gold).unwrap().get_component::<RegrowingComponent>().is_err());
+        assert_eq!(harvest_resource_node(&mut world, &mut index, gold, u32::MAX), ResourceKind::Gold.capacity() - 10);
+        assert!(world.entry_ref(gold).is_err());
+        assert_eq!(index.at((1, 0)), None);
+        assert_eq!(harvest_resource_node(&mut world, &mut index, gold, 10), 0);
+    }
+}
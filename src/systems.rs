@@ -1,114 +1,383 @@
-use benimator::State;
-use legion::{Entity, system};
+use legion::{Entity, IntoQuery, Read, system};
 use legion::systems::CommandBuffer;
+use legion::query::component;
+use legion::world::SubWorld;
 use macroquad::math::Vec2;
 use rand::Rng;
-use crate::components::{AnimatedComponent, EntityKind, DrawableComponent, MovementComponent};
-use crate::{MapInformation};
-use crate::animations::animation::{AnimationMap, AnimationStates};
+use crate::components::{AnimatedComponent, BuildingComponent, EntityKind, DrawableComponent, FacingComponent, FactionComponent, MovementComponent, MovementMode, PathComponent, RegrowingComponent, ResourceNodeComponent, SightComponent};
+use crate::{Faction, FrameTime, MapInformation};
+use crate::animations::animation::{AnimationMap, AnimationStates, ANIMATION_END_EVENT};
+use crate::animations::controller::{AnimationController, Facing};
+use crate::flow_field::FlowFieldCache;
+use crate::map::GameMap;
+use crate::pathfinding::{find_path, smooth_path};
+use crate::visibility::VisibilityMap;
+
+#[system(for_each)]
+pub fn update_animations(
+    animation: &mut AnimatedComponent,
+    #[resource] animation_mapping: &AnimationMap,
+    #[resource] frame_time: &FrameTime) {
+    animation.update(animation_mapping, frame_time.delta);
+}
 
 #[system(for_each)]
 pub fn apply_random_death(
     entity: &Entity,
-    animation: &mut AnimatedComponent,
+    controller: &mut AnimationController,
+    animation: &AnimatedComponent,
     creature_type: &EntityKind,
-    cmd: &mut CommandBuffer,
-    #[resource] animation_mapping: &AnimationMap) {
-    if !animation.animated_sprite_label.contains(AnimationStates::Die.to_str()) {
+    #[resource] animation_mapping: &AnimationMap,
+    cmd: &mut CommandBuffer) {
+    if controller.state() != AnimationStates::Die {
         // Randomly kill entities
         let mut rng = rand::thread_rng();
         let chance = 0.005;
         let random_number = rng.gen_range(0.0..1.0);
-        if random_number < (chance as f64 / 100.0) {
-            // Change the animation to the death animation
-            let new_animation = AnimationStates::Die.get_creature_animation(creature_type.kind);
-            animation.liege_animation = animation_mapping.animations.get(&new_animation).unwrap().clone();
-            animation.animated_sprite_label = new_animation;
-            animation.animation_state = State::new();
+        if random_number < (chance / 100.0) {
+            controller.request(AnimationStates::Die);
         }
-    } else if animation.animated_sprite_label.contains(AnimationStates::Die.to_str()) {
-        // If the entity is already dying, check if the animation has ended, and remove the entity when that occurs (after the last frame)
-        if animation.animation_state.is_ended() {
+    } else if animation.has_event(ANIMATION_END_EVENT) {
+        // The entity is dying, so remove it once the death animation has ended (after the last frame). Whatever was
+        // playing before can end on the same tick the death was requested, before the death animation has started.
+        let dying = controller.resolve_animation(animation_mapping, creature_type.kind)
+            .is_some_and(|(die_animation, _)| die_animation == animation.animation);
+        if dying {
             cmd.remove(*entity);
         }
     }
 }
 
+// Lift the fog of war around everything the player owns that can see, creatures and buildings alike
+#[system]
+#[read_component(DrawableComponent)]
+#[read_component(BuildingComponent)]
+#[read_component(SightComponent)]
+#[read_component(FactionComponent)]
+pub fn update_visibility(
+    world: &SubWorld,
+    #[resource] visibility: &mut VisibilityMap,
+    #[resource] map_info: &MapInformation) {
+    let mut creatures = <(Read<DrawableComponent>, Read<SightComponent>, Read<FactionComponent>)>::query();
+    let mut buildings = <(Read<BuildingComponent>, Read<SightComponent>, Read<FactionComponent>)>::query();
+
+    let creature_sight = creatures.iter(world)
+        .filter(|(_, _, owner)| owner.faction == Faction::Player)
+        .map(|(drawable, sight, _)| (map_info.world_to_tile(drawable.position), sight.radius));
+    let building_sight = buildings.iter(world)
+        .filter(|(_, _, owner)| owner.faction == Faction::Player)
+        .map(|(building, sight, _)| (building.centre(), sight.radius));
+    visibility.update(creature_sight.chain(building_sight));
+}
+
+// Worked nodes grow back, until they're full and stop regrowing
+#[system(for_each)]
+#[filter(component::<RegrowingComponent>())]
+pub fn update_resource_nodes(
+    entity: &Entity,
+    node: &mut ResourceNodeComponent,
+    #[resource] frame_time: &FrameTime,
+    cmd: &mut CommandBuffer) {
+    node.regrow(frame_time.delta.as_secs_f32());
+    if node.is_full() {
+        cmd.remove_component::<RegrowingComponent>(*entity);
+    }
+}
+
 #[system(for_each)]
 pub fn apply_random_movement(
     movement: &mut MovementComponent,
+    path: &mut PathComponent,
     drawable: &mut DrawableComponent,
-    animation: &mut AnimatedComponent,
-    creature_type: &mut EntityKind,
+    controller: &mut AnimationController,
     #[resource] map_info: &MapInformation,
-    #[resource] animation_mapping: &AnimationMap) {
-    if !animation.animated_sprite_label.contains(AnimationStates::Die.to_str()) {
+    #[resource] game_map: &GameMap,
+    #[resource] flow_fields: &mut FlowFieldCache) {
+    if controller.state() != AnimationStates::Die {
         let mut rng = rand::thread_rng();
 
         // Check if this entity has a direction. If not, randomly decide if one should be set, or if it will remain idle
         if movement.destination == Vec2::ZERO {
             let chance = 0.5;
             let random_number = rng.gen_range(0.0..1.0);
-            if random_number < (chance as f64 / 100.0) {
-                // Create a new destination, scaled from map coordinates to world coordinates
-                let new_dest = Vec2::new(
-                    (rng.gen_range(1..=60) as f32) * (map_info.tile_size * map_info.tile_scale.x),
-                    (rng.gen_range(1..=60) as f32) * (map_info.tile_size * map_info.tile_scale.y)
-                );
-
-                movement.destination = new_dest;
-            } else {
-                // Remain idle, update the animation to idle
-                if !animation.animated_sprite_label.contains(AnimationStates::IdleRight.to_str()) &&
-                    !animation.animated_sprite_label.contains(AnimationStates::IdleLeft.to_str())
-                {
-                    let new_animation = AnimationStates::IdleRight.get_creature_animation(creature_type.kind);
-                    animation.liege_animation = animation_mapping.animations.get(&new_animation).unwrap().clone();
-                    animation.animated_sprite_label = new_animation;
-                    animation.animation_state = State::new();
+            if random_number < (chance / 100.0) {
+                // Pick a new destination tile, and find a path there. Destinations that can't be reached are skipped,
+                // and the entity waits to pick another.
+                let destination = (rng.gen_range(1..=60), rng.gen_range(1..=60));
+                let start = map_info.world_to_tile(drawable.position);
+                if let Some(tiles) = find_path(game_map, start, destination) {
+                    // Waypoints are tile centres, starting with the centre of the tile the entity is on, so every leg
+                    // is a straight line the smoothing checked is walkable
+                    let waypoints: Vec<Vec2> = smooth_path(game_map, &tiles).into_iter()
+                        .map(|(x, y)| map_info.tile_center(x, y))
+                        .collect();
+                    movement.destination = *waypoints.last().unwrap();
+                    *path = PathComponent::new(waypoints);
                 }
+            } else {
+                // Remain idle
+                movement.velocity = Vec2::ZERO;
+                controller.request(AnimationStates::Idle);
             }
         } else {
-            // This entity has a destination set, so move it towards there
-            // First, get the direction
-            let direction = movement.destination - drawable.position;
-
-            // Check to see if the entity has arrived at (or near) the destination
-            if direction.length() < 1.0 {
-                // Arrived at destination, clear out current destination, and set the idle animation
+            // This entity has a path or a flow field to follow, so move it towards the next waypoint
+            let waypoint = match movement.mode {
+                MovementMode::Path => path.current(),
+                MovementMode::FlowField { goal } => flow_fields.get(game_map, goal).waypoint(map_info, drawable.position, movement.destination),
+            };
+            let Some(waypoint) = waypoint else {
+                // Arrived at destination (or it can't be reached any more), clear out current destination, and go idle
+                *path = PathComponent::default();
+                movement.mode = MovementMode::Path;
                 movement.destination = Vec2::ZERO;
+                movement.velocity = Vec2::ZERO;
+                controller.request(AnimationStates::Idle);
+                return;
+            };
+            let direction = waypoint - drawable.position;
 
-                let new_animation = AnimationStates::IdleRight.get_creature_animation(creature_type.kind);
-                animation.liege_animation = animation_mapping.animations.get(&new_animation).unwrap().clone();
-                animation.animated_sprite_label = new_animation;
-                animation.animation_state = State::new();
+            // Check to see if the entity has arrived at (or near) the waypoint, and head for the next one if so
+            if direction.length() < 1.0 {
+                drawable.position = waypoint;
+                if movement.mode == MovementMode::Path {
+                    path.advance();
+                }
             } else {
                 let normalized_direction = direction.normalize();
-                // Move towards the destination, setting an appropriate animation based on the direction of movement
-                drawable.position += normalized_direction * movement.speed;
-
-                // Calculate the correct animation to apply during movement
-                let mut new_animation = AnimationStates::IdleRight.get_creature_animation(creature_type.kind);
-                if normalized_direction[0] >= 0. && normalized_direction[1] >= 0. {
-                    // Heading right and down
-                    new_animation = AnimationStates::WalkRight.get_creature_animation(creature_type.kind);
-                } else if normalized_direction[0] <= 0. && normalized_direction[1] >= 0. {
-                    // Heading left and down
-                    new_animation = AnimationStates::WalkLeft.get_creature_animation(creature_type.kind);
-                } else if normalized_direction[0] >= 0. && normalized_direction[1] <= 0. {
-                    // Heading right and up
-                    new_animation = AnimationStates::WalkUpRight.get_creature_animation(creature_type.kind);
-                } else if normalized_direction[0] <= 0. && normalized_direction[1] <= 0. {
-                    // Heading left and up
-                    new_animation = AnimationStates::WalkUpLeft.get_creature_animation(creature_type.kind);
+                // Move towards the waypoint, at whatever speed the terrain allows (without overshooting it)
+                match terrain_step(game_map, map_info, drawable.position, normalized_direction * movement.speed) {
+                    Some(step) => {
+                        movement.velocity = step.clamp_length_max(direction.length());
+                        drawable.position += movement.velocity;
+                        controller.request(AnimationStates::Walk);
+                    }
+                    None => {
+                        // The way is blocked, so give up on this destination
+                        *path = PathComponent::default();
+                        movement.mode = MovementMode::Path;
+                        movement.destination = Vec2::ZERO;
+                        movement.velocity = Vec2::ZERO;
+                        controller.request(AnimationStates::Idle);
+                    }
                 }
+            }
+        }
+    }
+}
 
-                if animation.animated_sprite_label != new_animation {
-                    animation.liege_animation = animation_mapping.animations.get(&new_animation).unwrap().clone();
-                    animation.animated_sprite_label = new_animation;
-                    animation.animation_state = State::new();
-                }
+// How far an entity moving at `velocity` over open ground gets in one tick, slowed down by the tile it's standing on.
+// None if the step would take it onto a tile it can't enter. Entities stranded somewhere impassable (or off the map)
+// move at full speed, so they can always get back out.
+pub fn terrain_step(game_map: &GameMap, map_info: &MapInformation, position: Vec2, velocity: Vec2) -> Option<Vec2> {
+    let (x, y) = map_info.world_to_tile(position);
+    let Some(cost) = game_map.movement_cost(x, y) else { return Some(velocity) };
+
+    let step = velocity / cost;
+    let (next_x, next_y) = map_info.world_to_tile(position + step);
+    game_map.is_passable(next_x, next_y).then_some(step)
+}
+
+// Face the direction the entity is moving in. Entities that stop keep facing the way they were going.
+#[system(for_each)]
+pub fn update_facing(movement: &MovementComponent, facing: &mut FacingComponent) {
+    if let Some(new_facing) = Facing::from_direction(movement.velocity) {
+        facing.facing = new_facing;
+    }
+}
+
+// Apply transitions driven by the animation itself, and switch the animation over whenever the controller's state
+// or facing no longer matches what is playing
+#[system(for_each)]
+pub fn apply_animation_controller(
+    controller: &mut AnimationController,
+    facing: &FacingComponent,
+    animation: &mut AnimatedComponent,
+    creature_type: &EntityKind,
+    #[resource] animation_mapping: &AnimationMap) {
+    if animation.has_event(ANIMATION_END_EVENT) {
+        controller.animation_ended();
+    }
+
+    controller.face(facing.facing);
+    if controller.take_changed() {
+        if let Some((new_animation, flip_x)) = controller.resolve_animation(animation_mapping, creature_type.kind) {
+            if new_animation != animation.animation {
+                animation.play(new_animation);
             }
+            // Turning between two facings that share art only flips it, without restarting the animation
+            animation.flip_x = flip_x;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use benimator::{Animation, FrameRate};
+    use legion::{EntityStore, Resources, Schedule, World};
+    use crate::animations::animation::{Frame, LiegeAnimation, LiegeSprite, Size};
+    use crate::map::{Biome, MapGenConfig, Tile};
+    use crate::resource_nodes::ResourceKind;
+    use crate::assets::TextureId;
+    use crate::visibility::Visibility;
+    use super::*;
+
+    // A map of one animation per label, each `frame_count` frames long at 10 frames a second
+    fn animation_map(labels: &[&str], frame_count: usize) -> AnimationMap {
+        let sprite = LiegeSprite {
+            frame: Frame { x: 0, y: 0, w: 9, h: 9 },
+            rotated: false,
+            trimmed: false,
+            sprite_source_size: Frame { x: 0, y: 0, w: 9, h: 9 },
+            source_size: Size { w: 9, h: 9 },
+            duration: None,
+        };
+        let animation = LiegeAnimation {
+            frames: vec![sprite; frame_count],
+            animation: Animation::from_indices(0..frame_count, FrameRate::from_fps(10.)).once(),
+            events: Vec::new(),
+            shortest_frame: Duration::from_millis(100),
+        };
+        let mut animation_map = AnimationMap::default();
+        for label in labels {
+            animation_map.insert(label.to_string(), animation.clone());
+        }
+        animation_map
+    }
+
+    #[test]
+    fn test_death_waits_for_the_die_animation_to_end() {
+        let animation_map = animation_map(&["goblin_attack_right", "goblin_die"], 2);
+        let mut world = World::default();
+        let mut resources = Resources::default();
+        let mut schedule = Schedule::builder().add_system(apply_random_death_system()).build();
+
+        // Dying, but the attack that was playing ended on the tick the death was requested
+        let mut controller = AnimationController::new(AnimationStates::Attack, Facing::Right);
+        controller.request(AnimationStates::Die);
+        let mut attacking = AnimatedComponent::new(animation_map.id("goblin_attack_right").unwrap());
+        attacking.events.push(ANIMATION_END_EVENT);
+        let goblin = world.push((controller, attacking, EntityKind { kind: "goblin" }));
+        resources.insert(animation_map);
+        schedule.execute(&mut world, &mut resources);
+        assert!(world.entry(goblin).is_some());
+
+        // Once the die animation itself ends, the goblin is gone
+        let die_animation = resources.get::<AnimationMap>().unwrap().id("goblin_die").unwrap();
+        let mut entry = world.entry(goblin).unwrap();
+        let animated = entry.get_component_mut::<AnimatedComponent>().unwrap();
+        animated.play(die_animation);
+        animated.events.push(ANIMATION_END_EVENT);
+        schedule.execute(&mut world, &mut resources);
+        assert!(world.entry(goblin).is_none());
+    }
+
+    #[test]
+    fn test_turning_mid_attack_does_not_restart_it() {
+        let animation_map = animation_map(&["rogue_idle_right", "rogue_idle_left", "rogue_attack_right", "rogue_attack_left"], 4);
+        let attack_right = animation_map.id("rogue_attack_right").unwrap();
+        let mut world = World::default();
+        let mut resources = Resources::default();
+        let mut schedule = Schedule::builder().add_system(apply_animation_controller_system()).build();
+
+        // Halfway through an attack to the right
+        let mut controller = AnimationController::new(AnimationStates::Idle, Facing::Right);
+        controller.request(AnimationStates::Attack);
+        let mut animated = AnimatedComponent::new(attack_right);
+        animated.update(&animation_map, Duration::from_millis(250));
+        let rogue = world.push((controller, FacingComponent { facing: Facing::Left }, animated, EntityKind { kind: "rogue" }));
+        resources.insert(animation_map);
+
+        // Turning around carries on with the same attack from the same frame
+        schedule.execute(&mut world, &mut resources);
+        let entry = world.entry(rogue).unwrap();
+        let animated = entry.get_component::<AnimatedComponent>().unwrap();
+        assert_eq!(animated.animation, attack_right);
+        assert_eq!(animated.animation_state.frame_index(), 2);
+
+        // Once the attack is over, the rogue turns to face the way it's going
+        world.entry(rogue).unwrap().get_component_mut::<AnimatedComponent>().unwrap().events.push(ANIMATION_END_EVENT);
+        schedule.execute(&mut world, &mut resources);
+        let idle_left = resources.get::<AnimationMap>().unwrap().id("rogue_idle_left").unwrap();
+        assert_eq!(world.entry(rogue).unwrap().get_component::<AnimatedComponent>().unwrap().animation, idle_left);
+    }
+
+    #[test]
+    fn test_terrain_step() {
+        let map_info = MapInformation { width: 3, height: 1, tile_size: 10., tile_scale: Vec2::ONE };
+        let mut game_map = GameMap::new(3, 1, MapGenConfig::default());
+        game_map.fill(|x, _| Tile::new([Biome::Grassland, Biome::Forest, Biome::Water][x as usize], 0));
+        let velocity = Vec2::new(2., 0.);
+
+        assert_eq!(terrain_step(&game_map, &map_info, Vec2::new(5., 5.), velocity), Some(velocity));
+        // Forest halves the speed
+        assert_eq!(terrain_step(&game_map, &map_info, Vec2::new(15., 5.), velocity), Some(Vec2::new(1., 0.)));
+        // Stepping into the water, or off the map, is blocked
+        assert_eq!(terrain_step(&game_map, &map_info, Vec2::new(19.5, 5.), velocity), None);
+        assert_eq!(terrain_step(&game_map, &map_info, Vec2::new(1., 5.), -velocity), None);
+        // But something stuck in the water can walk out
+        assert_eq!(terrain_step(&game_map, &map_info, Vec2::new(25., 5.), -velocity), Some(-velocity));
+    }
+
+    #[test]
+    fn test_update_resource_nodes() {
+        let mut world = World::default();
+        let mut resources = Resources::default();
+        resources.insert(FrameTime { delta: Duration::from_secs(4) });
+        let mut schedule = Schedule::builder().add_system(update_resource_nodes_system()).build();
+
+        let mut felled = ResourceNodeComponent::new(ResourceKind::Tree, (0, 0));
+        felled.work(u32::MAX);
+        let felled = world.push((felled, RegrowingComponent));
+        let mut nearly_grown = ResourceNodeComponent::new(ResourceKind::Tree, (1, 0));
+        nearly_grown.work(1);
+        let nearly_grown = world.push((nearly_grown, RegrowingComponent));
+        // Not marked as regrowing, so it's left alone
+        let mut unmarked = ResourceNodeComponent::new(ResourceKind::Tree, (2, 0));
+        unmarked.work(u32::MAX);
+        let unmarked = world.push((unmarked,));
+        schedule.execute(&mut world, &mut resources);
+
+        let node = |entity: Entity| *world.entry_ref(entity).unwrap().get_component::<ResourceNodeComponent>().unwrap();
+        let regrowing = |entity: Entity| world.entry_ref(entity).unwrap().get_component::<RegrowingComponent>().is_ok();
+        // The felled tree is growing back, and the one that grew back in full stops being looked at
+        assert_eq!((node(felled).remaining, regrowing(felled)), (2, true));
+        assert_eq!((node(nearly_grown).remaining, regrowing(nearly_grown)), (ResourceKind::Tree.capacity(), false));
+        assert_eq!(node(unmarked).remaining, 0);
+    }
+
+    #[test]
+    fn test_update_visibility() {
+        let map_info = MapInformation { width: 40, height: 10, tile_size: 10., tile_scale: Vec2::ONE };
+        let mut world = World::default();
+        let mut resources = Resources::default();
+        resources.insert(VisibilityMap::new(40, 10));
+        resources.insert(map_info);
+        let mut schedule = Schedule::builder().add_system(update_visibility_system()).build();
+
+        let creature = |tile: (i32, i32), faction| (
+            DrawableComponent { position: Vec2::new(tile.0 as f32 * 10. + 5., tile.1 as f32 * 10. + 5.), texture: TextureId::new(0) },
+            SightComponent { radius: 2 },
+            FactionComponent { faction },
+        );
+        let scout = world.push(creature((2, 5), Faction::Player));
+        world.push(creature((20, 5), Faction::Enemy));
+        world.push((BuildingComponent { tile: (34, 4), size: (2, 2) }, SightComponent { radius: 1 }, FactionComponent { faction: Faction::Player }));
+        schedule.execute(&mut world, &mut resources);
+
+        // The player's scout and building see around themselves, and the enemy sees nothing for the player
+        {
+            let visibility = resources.get::<VisibilityMap>().unwrap();
+            assert!(visibility.is_visible(2, 5) && visibility.is_visible(4, 5));
+            assert!(visibility.is_visible(35, 5) && visibility.is_visible(36, 5));
+            assert_eq!(visibility.get(20, 5), Visibility::Unexplored);
         }
+
+        // Once the scout moves on, where it was stays explored
+        world.entry(scout).unwrap().get_component_mut::<DrawableComponent>().unwrap().position = Vec2::new(105., 55.);
+        schedule.execute(&mut world, &mut resources);
+        let visibility = resources.get::<VisibilityMap>().unwrap();
+        assert_eq!(visibility.get(2, 5), Visibility::Explored);
+        assert!(visibility.is_visible(10, 5));
     }
-}
\ No newline at end of file
+}
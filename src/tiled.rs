@@ -0,0 +1,589 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use crate::map::{Biome, Feature, GameMap, MapGenConfig, Tile};
+use crate::visibility::CASTLE_SIGHT_RADIUS;
+use crate::{CreatureType, Faction};
+
+// The top bits of a tile id in a Tiled layer say how the tile is flipped, which makes no difference to its terrain
+const TILED_FLIP_FLAGS: u32 = 0xf000_0000;
+// The most creatures one spawn point can spawn, so a typo in a count can't bury the map in them
+pub const MAX_SPAWN_COUNT: u32 = 100;
+// The furthest a building can see, in tiles
+pub const MAX_BUILDING_SIGHT: i32 = 50;
+
+// A hand made map, and everything placed on it
+pub struct Scenario {
+    pub game_map: GameMap,
+    pub objects: Vec<ScenarioObject>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScenarioObjectKind {
+    // Where creatures start, given by its `creature` (and optionally `count`) properties
+    Spawn,
+    Lair,
+    // Owned by its `faction`, and seeing `sight` tiles around itself, if it has those properties
+    Building,
+}
+
+// An object from one of the map's object layers, with its custom properties for whatever it spawns
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScenarioObject {
+    pub id: u32,
+    pub name: String,
+    pub kind: ScenarioObjectKind,
+    // The tile the object's top left corner is on, and how many tiles across and down it covers
+    pub tile: (i32, i32),
+    pub size: (i32, i32),
+    pub properties: BTreeMap<String, Value>,
+}
+
+impl ScenarioObject {
+    // The creature a spawn point spawns, and how many of them
+    pub fn spawns(&self) -> Option<(CreatureType, u32)> {
+        let creature = self.properties.get("creature")?.as_str().and_then(CreatureType::from_name)?;
+        let count = self.spawn_count().ok()?;
+        (self.kind == ScenarioObjectKind::Spawn).then_some((creature, count))
+    }
+
+    // Who a building belongs to and how far it can see. Without `faction` and `sight` properties, it's the player's
+    // and sees as far as the castle.
+    pub fn building(&self) -> Option<(Faction, i32)> {
+        let faction = self.building_faction().ok()?;
+        let sight = self.building_sight().ok()?;
+        (self.kind == ScenarioObjectKind::Building).then_some((faction, sight))
+    }
+
+    fn building_faction(&self) -> Result<Faction, String> {
+        let Some(value) = self.properties.get("faction") else { return Ok(Faction::Player) };
+        value.as_str().and_then(Faction::from_name).ok_or_else(|| format!(
+            "faction has to be one of {}, not {}",
+            Faction::ALL.map(|faction| faction.to_str()).join(", "), value,
+        ))
+    }
+
+    fn building_sight(&self) -> Result<i32, String> {
+        let Some(value) = self.properties.get("sight") else { return Ok(CASTLE_SIGHT_RADIUS) };
+        match value.as_u64() {
+            Some(sight) if sight <= MAX_BUILDING_SIGHT as u64 => Ok(sight as i32),
+            _ => Err(format!("sight has to be a whole number from 0 to {}, not {}", MAX_BUILDING_SIGHT, value)),
+        }
+    }
+
+    // The spawn point's `count` property, or 1 without one
+    fn spawn_count(&self) -> Result<u32, String> {
+        let Some(value) = self.properties.get("count") else { return Ok(1) };
+        match value.as_u64() {
+            Some(count) if (1..=MAX_SPAWN_COUNT as u64).contains(&count) => Ok(count as u32),
+            _ => Err(format!("count has to be a whole number from 1 to {}, not {}", MAX_SPAWN_COUNT, value)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum TiledError {
+    Io { path: String, source: std::io::Error },
+    Parse { path: String, source: serde_json::Error },
+    Unsupported { reason: String },
+    Tileset { tileset: String, reason: String },
+    Layer { layer: String, reason: String },
+    Object { layer: String, object: String, reason: String },
+}
+
+impl fmt::Display for TiledError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TiledError::Io { path, source } => write!(f, "failed to read {}: {}", path, source),
+            TiledError::Parse { path, source } => write!(f, "failed to parse {}: {}", path, source),
+            TiledError::Unsupported { reason } => write!(f, "unsupported map: {}", reason),
+            TiledError::Tileset { tileset, reason } => write!(f, "tileset '{}': {}", tileset, reason),
+            TiledError::Layer { layer, reason } => write!(f, "layer '{}': {}", layer, reason),
+            TiledError::Object { layer, object, reason } => write!(f, "layer '{}', object {}: {}", layer, object, reason),
+        }
+    }
+}
+
+impl std::error::Error for TiledError {}
+
+#[derive(Deserialize)]
+struct TiledMap {
+    width: i32,
+    height: i32,
+    tilewidth: f32,
+    tileheight: f32,
+    #[serde(default)]
+    orientation: String,
+    #[serde(default)]
+    infinite: bool,
+    layers: Vec<TiledLayer>,
+    #[serde(default)]
+    tilesets: Vec<TiledTilesetRef>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+enum TiledLayer {
+    #[serde(rename = "tilelayer")]
+    Tiles {
+        name: String,
+        width: i32,
+        height: i32,
+        data: Value,
+    },
+    #[serde(rename = "objectgroup")]
+    Objects {
+        name: String,
+        #[serde(default)]
+        objects: Vec<TiledObject>,
+    },
+    #[serde(rename = "group")]
+    Group {
+        #[serde(default)]
+        layers: Vec<TiledLayer>,
+    },
+    // Image layers, which are only decoration
+    #[serde(other)]
+    Other,
+}
+
+// A tileset in the map, either written out in it or in a file of its own
+#[derive(Deserialize)]
+struct TiledTilesetRef {
+    firstgid: u32,
+    source: Option<String>,
+    #[serde(flatten)]
+    tileset: TiledTileset,
+}
+
+#[derive(Deserialize)]
+struct TiledTileset {
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    tiles: Vec<TiledTile>,
+}
+
+#[derive(Deserialize)]
+struct TiledTile {
+    id: u32,
+    #[serde(default)]
+    properties: Vec<TiledProperty>,
+}
+
+#[derive(Deserialize)]
+struct TiledProperty {
+    name: String,
+    value: Value,
+}
+
+#[derive(Deserialize)]
+struct TiledObject {
+    id: u32,
+    #[serde(default)]
+    name: String,
+    // Called the type before Tiled 1.9, and the class since
+    #[serde(default, rename = "type")]
+    object_type: String,
+    #[serde(default)]
+    class: String,
+    x: f32,
+    y: f32,
+    #[serde(default)]
+    width: f32,
+    #[serde(default)]
+    height: f32,
+    // Tile objects are positioned by their bottom left corner
+    gid: Option<u32>,
+    #[serde(default)]
+    properties: Vec<TiledProperty>,
+}
+
+// What a tile in a Tiled tileset means to Liege, from the tile's custom properties
+#[derive(Clone, Copy)]
+struct TileDefinition {
+    biome: Option<Biome>,
+    variant: u8,
+    feature: Option<Feature>,
+}
+
+struct Tileset {
+    firstgid: u32,
+    name: String,
+    tiles: HashMap<u32, TileDefinition>,
+}
+
+// Load a map made in Tiled, saved as JSON (.tmj) with CSV tile layer data. Tiles get their terrain from their
+// tileset's custom properties: `biome` (one of the biome names), `variant` and `feature` (`road`, `river`, `lake` or
+// `bridge`, or `road` set to true as a shorthand). Tiles with only a feature can be painted on a layer above the
+// terrain. Objects are spawn points, lairs or buildings, by their class.
+pub fn load_tiled_map(file_path: &str) -> Result<Scenario, TiledError> {
+    let base_directory = Path::new(file_path).parent().unwrap_or(Path::new(""));
+    let tiled_map: TiledMap = read_json(file_path)?;
+    build_scenario(tiled_map, base_directory)
+}
+
+pub fn parse_tiled_map(json: &str, base_directory: &Path) -> Result<Scenario, TiledError> {
+    let tiled_map = serde_json::from_str(json).map_err(|source| TiledError::Parse { path: "map".to_string(), source })?;
+    build_scenario(tiled_map, base_directory)
+}
+
+fn read_json<T: DeserializeOwned>(file_path: &str) -> Result<T, TiledError> {
+    let file_content = fs::read_to_string(file_path).map_err(|source| TiledError::Io { path: file_path.to_string(), source })?;
+    serde_json::from_str(&file_content).map_err(|source| TiledError::Parse { path: file_path.to_string(), source })
+}
+
+fn build_scenario(tiled_map: TiledMap, base_directory: &Path) -> Result<Scenario, TiledError> {
+    if !tiled_map.orientation.is_empty() && tiled_map.orientation != "orthogonal" {
+        return Err(TiledError::Unsupported { reason: format!("{} maps can't be loaded, only orthogonal ones", tiled_map.orientation) });
+    }
+    if tiled_map.infinite {
+        return Err(TiledError::Unsupported { reason: "infinite maps can't be loaded".to_string() });
+    }
+    if tiled_map.width <= 0 || tiled_map.height <= 0 || tiled_map.tilewidth <= 0. || tiled_map.tileheight <= 0. {
+        return Err(TiledError::Unsupported { reason: format!("the map is {}x{} tiles", tiled_map.width, tiled_map.height) });
+    }
+
+    let mut tilesets = Vec::with_capacity(tiled_map.tilesets.len());
+    for tileset_ref in tiled_map.tilesets {
+        tilesets.push(load_tileset(tileset_ref, base_directory)?);
+    }
+    tilesets.sort_by_key(|tileset| tileset.firstgid);
+
+    let (width, height) = (tiled_map.width, tiled_map.height);
+    let dimensions = MapDimensions { width, height, tile_width: tiled_map.tilewidth, tile_height: tiled_map.tileheight };
+    let tile_count = (width as usize).checked_mul(height as usize)
+        .ok_or_else(|| TiledError::Unsupported { reason: format!("the map is {}x{} tiles", width, height) })?;
+    // Only allocated once a tile layer has shown it really has that many tiles, so a typo in the map's size is an error
+    // rather than an enormous allocation
+    let mut tiles: Vec<Option<Tile>> = Vec::new();
+    let mut objects = Vec::new();
+    let mut layers: Vec<TiledLayer> = tiled_map.layers.into_iter().rev().collect();
+    while let Some(layer) = layers.pop() {
+        match layer {
+            TiledLayer::Tiles { name, width: layer_width, height: layer_height, data } => {
+                let layer_error = |reason: String| TiledError::Layer { layer: name.clone(), reason };
+                if (layer_width, layer_height) != (width, height) {
+                    return Err(layer_error(format!("is {}x{}, but the map is {}x{}", layer_width, layer_height, width, height)));
+                }
+                let data: Vec<u32> = serde_json::from_value(data)
+                    .map_err(|_| layer_error("tile data has to be saved as CSV".to_string()))?;
+                if data.len() != tile_count {
+                    return Err(layer_error(format!("has {} tiles, but the map has {}", data.len(), tile_count)));
+                }
+                if tiles.is_empty() {
+                    tiles = vec![None; tile_count];
+                }
+
+                for (index, gid) in data.into_iter().enumerate() {
+                    let gid = gid & !TILED_FLIP_FLAGS;
+                    if gid == 0 {
+                        continue;
+                    }
+                    let position = (index as i32 % width, index as i32 / width);
+                    let definition = tile_definition(&tilesets, gid)
+                        .map_err(|reason| layer_error(format!("tile {:?}: {}", position, reason)))?;
+                    tiles[index] = match (definition.biome, tiles[index]) {
+                        // Terrain replaces whatever is below it, and features are added on top of it
+                        (Some(biome), _) => Some(Tile { feature: definition.feature, ..Tile::new(biome, definition.variant) }),
+                        (None, Some(below)) => Some(Tile { feature: definition.feature, ..below }),
+                        (None, None) => {
+                            let feature = definition.feature.map_or("feature", |feature| feature.texture_id());
+                            return Err(layer_error(format!("tile {:?} is a {}, but there is no terrain under it", position, feature)));
+                        }
+                    };
+                }
+            }
+            TiledLayer::Objects { name, objects: layer_objects } => {
+                for object in layer_objects {
+                    objects.push(scenario_object(object, &name, &dimensions)?);
+                }
+            }
+            TiledLayer::Group { layers: group_layers } => layers.extend(group_layers.into_iter().rev()),
+            TiledLayer::Other => {}
+        }
+    }
+
+    if tiles.is_empty() {
+        return Err(TiledError::Unsupported { reason: "it has no tile layers".to_string() });
+    }
+    if let Some(index) = tiles.iter().position(Option::is_none) {
+        return Err(TiledError::Unsupported {
+            reason: format!("tile {:?} has no terrain on any layer", (index as i32 % width, index as i32 / width)),
+        });
+    }
+
+    let mut game_map = GameMap::new(width, height, MapGenConfig::default());
+    game_map.fill(|x, y| tiles[(y * width + x) as usize].unwrap());
+    Ok(Scenario { game_map, objects })
+}
+
+fn load_tileset(tileset_ref: TiledTilesetRef, base_directory: &Path) -> Result<Tileset, TiledError> {
+    let tileset = match &tileset_ref.source {
+        Some(source) => read_json(&base_directory.join(source).to_string_lossy())?,
+        None => tileset_ref.tileset,
+    };
+
+    let mut tiles = HashMap::new();
+    for tile in tileset.tiles {
+        let tileset_error = |reason: String| TiledError::Tileset { tileset: tileset.name.clone(), reason: format!("tile {}: {}", tile.id, reason) };
+        let properties: HashMap<_, _> = tile.properties.iter().map(|property| (property.name.as_str(), &property.value)).collect();
+
+        let biome = match properties.get("biome") {
+            Some(value) => Some(value.as_str().and_then(Biome::from_name)
+                .ok_or_else(|| tileset_error(format!("{} is not a biome", value)))?),
+            None => None,
+        };
+        let variant = match properties.get("variant") {
+            Some(value) => value.as_u64().filter(|&variant| variant < biome.map_or(1, |biome| biome.variations()) as u64)
+                .ok_or_else(|| tileset_error(format!("{} is not a variant of {:?}", value, biome)))? as u8,
+            None => 0,
+        };
+        let feature = match properties.get("feature") {
+            Some(value) => Some(value.as_str().and_then(Feature::from_name)
+                .ok_or_else(|| tileset_error(format!("{} is not a feature", value)))?),
+            None if properties.get("road").and_then(|value| value.as_bool()) == Some(true) => Some(Feature::Road),
+            None => None,
+        };
+        if biome.is_some() || feature.is_some() {
+            tiles.insert(tile.id, TileDefinition { biome, variant, feature });
+        }
+    }
+
+    Ok(Tileset { firstgid: tileset_ref.firstgid, name: tileset.name, tiles })
+}
+
+fn tile_definition(tilesets: &[Tileset], gid: u32) -> Result<TileDefinition, String> {
+    let tileset = tilesets.iter().rev().find(|tileset| tileset.firstgid <= gid)
+        .ok_or_else(|| format!("tile id {} isn't in any tileset", gid))?;
+    let id = gid - tileset.firstgid;
+    tileset.tiles.get(&id).copied()
+        .ok_or_else(|| format!("tile {} of tileset '{}' has no biome or feature property", id, tileset.name))
+}
+
+// Size of the map, and of its tiles in Tiled's pixels
+struct MapDimensions {
+    width: i32,
+    height: i32,
+    tile_width: f32,
+    tile_height: f32,
+}
+
+fn scenario_object(object: TiledObject, layer: &str, dimensions: &MapDimensions) -> Result<ScenarioObject, TiledError> {
+    let label = if object.name.is_empty() { format!("{}", object.id) } else { format!("'{}' ({})", object.name, object.id) };
+    let object_error = |reason: String| TiledError::Object { layer: layer.to_string(), object: label.clone(), reason };
+
+    let class = if object.class.is_empty() { &object.object_type } else { &object.class };
+    let kind = match class.to_lowercase().as_str() {
+        "spawn" => ScenarioObjectKind::Spawn,
+        "lair" => ScenarioObjectKind::Lair,
+        "building" => ScenarioObjectKind::Building,
+        "" => return Err(object_error("has no class, it should be spawn, lair or building".to_string())),
+        other => return Err(object_error(format!("unknown class '{}', it should be spawn, lair or building", other))),
+    };
+
+    let top = if object.gid.is_some() { object.y - object.height } else { object.y };
+    let tile = ((object.x / dimensions.tile_width).floor() as i32, (top / dimensions.tile_height).floor() as i32);
+    if tile.0 < 0 || tile.1 < 0 || tile.0 >= dimensions.width || tile.1 >= dimensions.height {
+        return Err(object_error(format!("is off the map, at tile {:?}", tile)));
+    }
+    let size = (
+        ((object.width / dimensions.tile_width).ceil() as i32).max(1),
+        ((object.height / dimensions.tile_height).ceil() as i32).max(1),
+    );
+
+    let properties: BTreeMap<_, _> = object.properties.into_iter().map(|property| (property.name, property.value)).collect();
+    let scenario_object = ScenarioObject { id: object.id, name: object.name, kind, tile, size, properties };
+    if kind == ScenarioObjectKind::Building {
+        scenario_object.building_faction().map_err(object_error)?;
+        scenario_object.building_sight().map_err(object_error)?;
+    }
+    if kind == ScenarioObjectKind::Spawn {
+        scenario_object.spawn_count().map_err(object_error)?;
+        if scenario_object.spawns().is_none() {
+            return Err(object_error(format!(
+                "spawn points need a creature property naming a creature ({}), and an optional whole number count",
+                CreatureType::ALL.map(|creature_type| creature_type.to_str()).join(", "),
+            )));
+        }
+    }
+    Ok(scenario_object)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use super::*;
+
+    fn tileset() -> Value {
+        json!({
+            "firstgid": 1,
+            "name": "terrain",
+            "tilecount": 5,
+            "tiles": [
+                { "id": 0, "properties": [{ "name": "biome", "type": "string", "value": "grassland" }] },
+                { "id": 1, "properties": [
+                    { "name": "biome", "type": "string", "value": "forest" },
+                    { "name": "variant", "type": "int", "value": 2 }
+                ] },
+                { "id": 2, "properties": [{ "name": "biome", "type": "string", "value": "water" }] },
+                { "id": 3, "properties": [{ "name": "road", "type": "bool", "value": true }] },
+                { "id": 4, "properties": [{ "name": "feature", "type": "string", "value": "river" }] }
+            ]
+        })
+    }
+
+    fn tiled_map(layers: Value) -> Value {
+        json!({
+            "width": 3,
+            "height": 2,
+            "tilewidth": 16,
+            "tileheight": 16,
+            "orientation": "orthogonal",
+            "infinite": false,
+            "layers": layers,
+            "tilesets": [tileset()]
+        })
+    }
+
+    fn terrain_layer() -> Value {
+        json!({ "type": "tilelayer", "name": "terrain", "width": 3, "height": 2, "data": [1, 2, 3, 1, 1, 3] })
+    }
+
+    fn parse(map: Value) -> Result<Scenario, TiledError> {
+        parse_tiled_map(&map.to_string(), Path::new(""))
+    }
+
+    #[test]
+    fn test_tile_layers() {
+        let features = json!({ "type": "tilelayer", "name": "features", "width": 3, "height": 2, "data": [5, 0, 0, 4, 4, 0] });
+        let scenario = parse(tiled_map(json!([terrain_layer(), features]))).unwrap();
+        let game_map = &scenario.game_map;
+
+        assert_eq!((game_map.map_width, game_map.map_height), (3, 2));
+        assert_eq!(game_map.tile(0, 0), Some(Tile::new(Biome::Grassland, 0).with_feature(Feature::River)));
+        assert_eq!(game_map.tile(1, 0), Some(Tile::new(Biome::Forest, 2)));
+        assert_eq!(game_map.tile(2, 0), Some(Tile::new(Biome::Water, 0)));
+        // Features are painted over the terrain below them
+        assert_eq!(game_map.tile(0, 1), Some(Tile::new(Biome::Grassland, 0).with_feature(Feature::Road)));
+        assert_eq!(game_map.tile(1, 1), Some(Tile::new(Biome::Grassland, 0).with_feature(Feature::Road)));
+    }
+
+    #[test]
+    fn test_flipped_tiles_and_groups() {
+        // Flipped horizontally, inside a group layer
+        let terrain = json!({ "type": "tilelayer", "name": "terrain", "width": 3, "height": 2, "data": [0x8000_0002u32, 1, 1, 1, 1, 1] });
+        let scenario = parse(tiled_map(json!([{ "type": "group", "name": "ground", "layers": [terrain] }]))).unwrap();
+        assert_eq!(scenario.game_map.tile(0, 0), Some(Tile::new(Biome::Forest, 2)));
+    }
+
+    #[test]
+    fn test_objects() {
+        let objects = json!({
+            "type": "objectgroup",
+            "name": "scenario",
+            "objects": [
+                { "id": 1, "name": "Goblins", "class": "spawn", "x": 20, "y": 4, "width": 0, "height": 0, "point": true,
+                  "properties": [{ "name": "creature", "type": "string", "value": "goblin" }, { "name": "count", "type": "int", "value": 3 }] },
+                { "id": 2, "name": "Cave", "type": "lair", "x": 32, "y": 16, "width": 16, "height": 16,
+                  "properties": [{ "name": "monster", "type": "string", "value": "troll" }] },
+                { "id": 3, "name": "Castle", "class": "Building", "x": 0, "y": 32, "width": 32, "height": 16, "gid": 5 },
+                { "id": 4, "name": "Watchtower", "class": "building", "x": 32, "y": 0,
+                  "properties": [{ "name": "faction", "type": "string", "value": "enemy" }, { "name": "sight", "type": "int", "value": 20 }] }
+            ]
+        });
+        let scenario = parse(tiled_map(json!([terrain_layer(), objects]))).unwrap();
+
+        let [spawn, lair, building, watchtower] = &scenario.objects[..] else { panic!("{:?}", scenario.objects) };
+        assert_eq!((spawn.kind, spawn.tile), (ScenarioObjectKind::Spawn, (1, 0)));
+        assert_eq!(spawn.spawns(), Some((CreatureType::Goblin, 3)));
+        assert_eq!((lair.kind, lair.tile, lair.size), (ScenarioObjectKind::Lair, (2, 1), (1, 1)));
+        assert_eq!(lair.properties["monster"], json!("troll"));
+        // Tile objects hang up from their position
+        assert_eq!((building.kind, building.tile, building.size), (ScenarioObjectKind::Building, (0, 1), (2, 1)));
+        // Buildings are the player's, and see as far as the castle, unless they say otherwise
+        assert_eq!(building.building(), Some((Faction::Player, CASTLE_SIGHT_RADIUS)));
+        assert_eq!(watchtower.building(), Some((Faction::Enemy, 20)));
+        assert_eq!(spawn.building(), None);
+    }
+
+    #[test]
+    fn test_external_tileset() {
+        let temp = tempfile::tempdir().unwrap();
+        let directory = temp.path();
+        fs::write(directory.join("terrain.tsj"), tileset().to_string()).unwrap();
+        let mut map = tiled_map(json!([terrain_layer()]));
+        map["tilesets"] = json!([{ "firstgid": 1, "source": "terrain.tsj" }]);
+        fs::write(directory.join("scenario.tmj"), map.to_string()).unwrap();
+
+        let scenario = load_tiled_map(&directory.join("scenario.tmj").to_string_lossy()).unwrap();
+        assert_eq!(scenario.game_map.tile(1, 0), Some(Tile::new(Biome::Forest, 2)));
+    }
+
+    #[test]
+    fn test_errors_name_the_layer_or_object() {
+        let error = |map: Value| parse(map).err().map(|e| e.to_string()).unwrap_or_default();
+
+        let unknown_tile = json!({ "type": "tilelayer", "name": "cliffs", "width": 3, "height": 2, "data": [1, 1, 1, 1, 1, 9] });
+        assert_eq!(error(tiled_map(json!([unknown_tile]))), "layer 'cliffs': tile (2, 1): tile 8 of tileset 'terrain' has no biome or feature property");
+
+        let short = json!({ "type": "tilelayer", "name": "terrain", "width": 3, "height": 2, "data": [1, 1] });
+        assert!(error(tiled_map(json!([short]))).starts_with("layer 'terrain':"));
+
+        let base64 = json!({ "type": "tilelayer", "name": "terrain", "width": 3, "height": 2, "encoding": "base64", "data": "AQAAAA==" });
+        assert_eq!(error(tiled_map(json!([base64]))), "layer 'terrain': tile data has to be saved as CSV");
+
+        let road_only = json!({ "type": "tilelayer", "name": "roads", "width": 3, "height": 2, "data": [4, 1, 1, 1, 1, 1] });
+        assert!(error(tiled_map(json!([road_only]))).starts_with("layer 'roads': tile (0, 0)"));
+
+        let objects = |object: Value| json!([terrain_layer(), { "type": "objectgroup", "name": "spawns", "objects": [object] }]);
+        assert_eq!(
+            error(tiled_map(objects(json!({ "id": 7, "name": "Orcs", "class": "spawn", "x": 0, "y": 0, "properties": [{ "name": "creature", "value": "orc" }] })))),
+            "layer 'spawns', object 'Orcs' (7): spawn points need a creature property naming a creature (rogue, goblin), and an optional whole number count",
+        );
+        assert_eq!(
+            error(tiled_map(objects(json!({ "id": 8, "class": "tree", "x": 0, "y": 0 })))),
+            "layer 'spawns', object 8: unknown class 'tree', it should be spawn, lair or building",
+        );
+        assert!(error(tiled_map(objects(json!({ "id": 9, "class": "lair", "x": 100, "y": 0 })))).starts_with("layer 'spawns', object 9: is off the map"));
+
+        let spawn_count = |count: Value| objects(json!({ "id": 10, "class": "spawn", "x": 0, "y": 0,
+            "properties": [{ "name": "creature", "value": "goblin" }, { "name": "count", "value": count }] }));
+        assert_eq!(error(tiled_map(spawn_count(json!("3")))), "layer 'spawns', object 10: count has to be a whole number from 1 to 100, not \"3\"");
+        assert_eq!(error(tiled_map(spawn_count(json!(-2)))), "layer 'spawns', object 10: count has to be a whole number from 1 to 100, not -2");
+        assert_eq!(error(tiled_map(spawn_count(json!(0)))), "layer 'spawns', object 10: count has to be a whole number from 1 to 100, not 0");
+        assert_eq!(error(tiled_map(spawn_count(json!(5_000_000_000u64)))), "layer 'spawns', object 10: count has to be a whole number from 1 to 100, not 5000000000");
+        assert!(parse(tiled_map(spawn_count(json!(100)))).is_ok());
+
+        let building = |properties: Value| objects(json!({ "id": 11, "class": "building", "x": 0, "y": 0, "properties": properties }));
+        assert_eq!(error(tiled_map(building(json!([{ "name": "faction", "value": "goblins" }])))), "layer 'spawns', object 11: faction has to be one of player, enemy, not \"goblins\"");
+        assert_eq!(error(tiled_map(building(json!([{ "name": "sight", "value": -1 }])))), "layer 'spawns', object 11: sight has to be a whole number from 0 to 50, not -1");
+        assert_eq!(error(tiled_map(building(json!([{ "name": "sight", "value": 51 }])))), "layer 'spawns', object 11: sight has to be a whole number from 0 to 50, not 51");
+
+        let mut bad_tileset = tiled_map(json!([terrain_layer()]));
+        bad_tileset["tilesets"][0]["tiles"][0]["properties"][0]["value"] = json!("lava");
+        assert_eq!(error(bad_tileset), "tileset 'terrain': tile 0: \"lava\" is not a biome");
+
+        let mut infinite = tiled_map(json!([terrain_layer()]));
+        infinite["infinite"] = json!(true);
+        assert!(matches!(parse(infinite), Err(TiledError::Unsupported { .. })));
+
+        let mut huge = tiled_map(json!([terrain_layer()]));
+        huge["width"] = json!(2_000_000_000);
+        huge["height"] = json!(2_000_000_000);
+        huge["layers"][0]["width"] = json!(2_000_000_000);
+        huge["layers"][0]["height"] = json!(2_000_000_000);
+        assert_eq!(error(huge), "layer 'terrain': has 6 tiles, but the map has 4000000000000000000");
+        assert_eq!(error(tiled_map(json!([]))), "unsupported map: it has no tile layers");
+    }
+
+    #[test]
+    fn test_missing_terrain() {
+        let sparse = json!({ "type": "tilelayer", "name": "terrain", "width": 3, "height": 2, "data": [1, 1, 1, 1, 0, 1] });
+        assert_eq!(parse(tiled_map(json!([sparse]))).err().unwrap().to_string(), "unsupported map: tile (1, 1) has no terrain on any layer");
+    }
+}
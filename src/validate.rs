@@ -0,0 +1,210 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use crate::animations::animation::{AnimationMap, AnimationStates, LiegeAnimation};
+use crate::animations::controller::{AnimationController, Facing};
+use crate::assets::{load_available_assets, load_manifest};
+use crate::CreatureType;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Finding {
+    pub severity: Severity,
+    pub message: String,
+}
+
+#[derive(Debug, Default)]
+pub struct ValidationReport {
+    pub findings: Vec<Finding>,
+}
+
+impl ValidationReport {
+    fn error(&mut self, message: String) {
+        self.findings.push(Finding { severity: Severity::Error, message });
+    }
+
+    fn warning(&mut self, message: String) {
+        self.findings.push(Finding { severity: Severity::Warning, message });
+    }
+
+    pub fn count(&self, severity: Severity) -> usize {
+        self.findings.iter().filter(|finding| finding.severity == severity).count()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.count(Severity::Error) > 0
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for finding in &self.findings {
+            let severity = match finding.severity {
+                Severity::Warning => "warning",
+                Severity::Error => "error",
+            };
+            writeln!(f, "{}: {}", severity, finding.message)?;
+        }
+        write!(f, "{} error(s), {} warning(s)", self.count(Severity::Error), self.count(Severity::Warning))
+    }
+}
+
+// Load every asset in the manifest and check that the animations are usable by the game. Missing art is only a
+// warning, as the game runs with placeholders in its place. Assets that fail to load are reported, and the checks
+// still run over everything that did.
+pub fn validate_assets(manifest_path: &str) -> ValidationReport {
+    let mut report = ValidationReport::default();
+
+    let manifest = match load_manifest(manifest_path) {
+        Ok(manifest) => manifest,
+        Err(asset_report) => {
+            for error in asset_report.errors {
+                report.error(error.to_string());
+            }
+            return report;
+        }
+    };
+    let (assets, errors) = load_available_assets(&manifest);
+    for error in errors {
+        report.error(error.to_string());
+    }
+
+    for placeholder in &assets.placeholders {
+        report.warning(format!("{} is missing, and will be replaced with a placeholder", placeholder));
+    }
+
+    check_frame_sizes(&assets.animations, &mut report);
+    check_creature_states(&AnimationMap::new(assets.animations), &mut report);
+    report
+}
+
+// Every frame of an animation should be the same size before trimming, or the sprite jumps around as it plays
+pub fn check_frame_sizes(animations: &HashMap<String, LiegeAnimation>, report: &mut ValidationReport) {
+    let animations: BTreeMap<_, _> = animations.iter().collect();
+    for (label, animation) in animations {
+        let Some(first) = animation.frames.first() else { continue };
+        for (index, frame) in animation.frames.iter().enumerate().skip(1) {
+            if frame.source_size != first.source_size {
+                report.error(format!(
+                    "animation '{}': frame {} is {}x{}, but frame 0 is {}x{}",
+                    label, index, frame.source_size.w, frame.source_size.h, first.source_size.w, first.source_size.h,
+                ));
+            }
+        }
+    }
+}
+
+// Every creature needs an animation for every state the systems can put it in, in every direction it can face
+// (allowing for the fallbacks the controller makes, like mirroring the other side)
+pub fn check_creature_states(animation_map: &AnimationMap, report: &mut ValidationReport) {
+    for creature_type in CreatureType::ALL {
+        let creature = creature_type.to_str();
+        for state in AnimationStates::ALL {
+            let missing: Vec<_> = Facing::ALL.iter()
+                .filter(|facing| AnimationController::new(state, **facing).resolve_animation(animation_map, creature).is_none())
+                .map(|facing| &facing.to_str()[1..])
+                .collect();
+
+            let state_name = &state.to_str()[1..];
+            if missing.len() == Facing::ALL.len() {
+                report.error(format!("creature '{}' has no {} animation", creature, state_name));
+            } else if !missing.is_empty() {
+                report.error(format!("creature '{}' has no {} animation facing {}", creature, state_name, missing.join(", ")));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use benimator::{Animation, FrameRate};
+    use crate::animations::animation::{Frame, LiegeSprite, Size};
+    use super::*;
+
+    fn animation(sizes: &[(i32, i32)]) -> LiegeAnimation {
+        let frames = sizes.iter()
+            .map(|&(w, h)| LiegeSprite {
+                frame: Frame { x: 0, y: 0, w, h },
+                rotated: false,
+                trimmed: false,
+                sprite_source_size: Frame { x: 0, y: 0, w, h },
+                source_size: Size { w, h },
+                duration: None,
+            })
+            .collect();
+        LiegeAnimation { frames, animation: Animation::from_indices(0..sizes.len(), FrameRate::from_fps(1.)), events: Vec::new(), shortest_frame: Duration::from_secs(1) }
+    }
+
+    #[test]
+    fn test_inconsistent_frame_sizes() {
+        let animations = HashMap::from([
+            ("rogue_walk_right".to_string(), animation(&[(9, 9), (9, 9), (10, 9)])),
+            ("rogue_idle_right".to_string(), animation(&[(9, 9), (9, 9)])),
+        ]);
+        let mut report = ValidationReport::default();
+        check_frame_sizes(&animations, &mut report);
+
+        assert_eq!(report.findings, vec![Finding {
+            severity: Severity::Error,
+            message: "animation 'rogue_walk_right': frame 2 is 10x9, but frame 0 is 9x9".to_string(),
+        }]);
+    }
+
+    #[test]
+    fn test_missing_creature_states() {
+        let mut animations = HashMap::new();
+        for creature in ["rogue", "goblin"] {
+            for label in ["idle_right", "walk_right", "walk_up_left", "attack_right", "die"] {
+                animations.insert(format!("{}_{}", creature, label), animation(&[(9, 9)]));
+            }
+        }
+        animations.remove("goblin_die");
+        animations.remove("rogue_walk_right");
+
+        let mut report = ValidationReport::default();
+        check_creature_states(&AnimationMap::new(animations), &mut report);
+
+        // The rogue can still walk up, and mirror up left for up right
+        let messages: Vec<_> = report.findings.iter().map(|finding| finding.message.as_str()).collect();
+        assert_eq!(messages, vec![
+            "creature 'rogue' has no walk animation facing right, down_right, down, down_left, left",
+            "creature 'goblin' has no die animation",
+        ]);
+    }
+
+    #[test]
+    fn test_checks_what_loaded_when_some_animations_fail() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = |name: &str| temp.path().join(name).to_string_lossy().into_owned();
+        let frame = |x: i32, w: i32| format!(r#"{{"frame": {{"x": {x}, "y": 0, "w": {w}, "h": 9}}, "rotated": false, "trimmed": false,
+            "spriteSourceSize": {{"x": 0, "y": 0, "w": {w}, "h": 9}}, "sourceSize": {{"w": {w}, "h": 9}}}}"#);
+        std::fs::write(path("rogue.json"), format!(r#"{{"frames": {{"rogue_walk_right_1.png": {}, "rogue_walk_right_2.png": {}}}}}"#,
+            frame(0, 9), frame(9, 10))).unwrap();
+        std::fs::write(path("rogue_animations.json"), r#"{"creature": "rogue", "sprite_sheet": "rogue", "animations": [
+            {"name": "attack_right", "frame_prefix": "rogue_attack_right_", "frame_count": 2, "fps": 5.0},
+            {"name": "walk_right", "frame_prefix": "rogue_walk_right_", "frame_count": 2, "fps": 5.0},
+            {"name": "die", "frame_prefix": "rogue_die_", "frame_count": 1, "fps": 5.0}]}"#).unwrap();
+        std::fs::write(path("manifest.json"), format!(r#"{{"sprite_sheets": {{"rogue": {:?}}}, "animations": {{"rogue": {:?}}}}}"#,
+            path("rogue.json"), path("rogue_animations.json"))).unwrap();
+
+        let report = validate_assets(&path("manifest.json"));
+        let messages: Vec<_> = report.findings.iter().map(|finding| finding.message.as_str()).collect();
+
+        // Both broken animations are reported, and the walk that did load is still checked
+        assert!(messages.contains(&"animations 'rogue': animation 'rogue_attack_right' references missing frame(s) 'rogue_attack_right_1.png', 'rogue_attack_right_2.png'"), "{}", report);
+        assert!(messages.contains(&"animations 'rogue': animation 'rogue_die' references missing frame(s) 'rogue_die_1.png'"), "{}", report);
+        assert!(messages.contains(&"animation 'rogue_walk_right': frame 1 is 10x9, but frame 0 is 9x9"), "{}", report);
+        assert!(messages.contains(&"creature 'rogue' has no idle animation"), "{}", report);
+    }
+
+    #[test]
+    fn test_game_assets_are_valid() {
+        let report = validate_assets(crate::assets::ASSET_MANIFEST_PATH);
+        assert!(!report.has_errors(), "{}", report);
+    }
+}
@@ -0,0 +1,156 @@
+// How far player owned creatures and buildings can see, in tiles
+pub const CREATURE_SIGHT_RADIUS: i32 = 7;
+pub const CASTLE_SIGHT_RADIUS: i32 = 12;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Visibility {
+    // Never seen
+    #[default]
+    Unexplored,
+    // Seen before, but not by anything right now
+    Explored,
+    // In sight of something the player owns
+    Visible,
+}
+
+// What the player knows of each tile on the map, updated as their creatures and buildings look around
+pub struct VisibilityMap {
+    width: i32,
+    height: i32,
+    tiles: Vec<Visibility>,
+    // Indices of the tiles that are currently visible, so they can be put back to explored without a pass over the
+    // whole map
+    visible: Vec<usize>,
+    // How many tiles have ever been seen, which only goes up
+    explored: usize,
+}
+
+impl VisibilityMap {
+    pub fn new(width: i32, height: i32) -> Self {
+        Self {
+            width,
+            height,
+            tiles: vec![Visibility::Unexplored; (width.max(0) * height.max(0)) as usize],
+            visible: Vec::new(),
+            explored: 0,
+        }
+    }
+
+    // Anywhere off the map is unexplored
+    pub fn get(&self, x: i32, y: i32) -> Visibility {
+        self.index(x, y).map_or(Visibility::Unexplored, |index| self.tiles[index])
+    }
+
+    pub fn is_visible(&self, x: i32, y: i32) -> bool {
+        self.get(x, y) == Visibility::Visible
+    }
+
+    pub fn is_explored(&self, x: i32, y: i32) -> bool {
+        self.get(x, y) != Visibility::Unexplored
+    }
+
+    // How many tiles are explored or visible, so anything drawn from what's been explored can tell when it's out of
+    // date
+    pub fn explored_count(&self) -> usize {
+        self.explored
+    }
+
+    // Every tile in sight right now
+    pub fn visible_tiles(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        self.visible.iter().map(|&index| (index as i32 % self.width, index as i32 / self.width))
+    }
+
+    // Replace what's visible with what can be seen from each of the given tiles, out to its sight radius. Anything
+    // that drops out of sight stays explored.
+    pub fn update(&mut self, sight: impl IntoIterator<Item = ((i32, i32), i32)>) {
+        for index in self.visible.drain(..) {
+            self.tiles[index] = Visibility::Explored;
+        }
+        for (centre, radius) in sight {
+            self.reveal(centre, radius);
+        }
+    }
+
+    // Mark a tile as seen before without it being in sight now, e.g. when loading what a saved game had explored
+    pub fn explore(&mut self, x: i32, y: i32) {
+        if let Some(index) = self.index(x, y) {
+            if self.tiles[index] == Visibility::Unexplored {
+                self.tiles[index] = Visibility::Explored;
+                self.explored += 1;
+            }
+        }
+    }
+
+    // Make every tile within `radius` of `centre` visible
+    pub fn reveal(&mut self, (centre_x, centre_y): (i32, i32), radius: i32) {
+        for y in (centre_y - radius).max(0)..=(centre_y + radius).min(self.height - 1) {
+            for x in (centre_x - radius).max(0)..=(centre_x + radius).min(self.width - 1) {
+                if (x - centre_x).pow(2) + (y - centre_y).pow(2) > radius * radius {
+                    continue;
+                }
+                let index = (y * self.width + x) as usize;
+                if self.tiles[index] == Visibility::Unexplored {
+                    self.explored += 1;
+                }
+                if self.tiles[index] != Visibility::Visible {
+                    self.tiles[index] = Visibility::Visible;
+                    self.visible.push(index);
+                }
+            }
+        }
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        (x >= 0 && y >= 0 && x < self.width && y < self.height).then(|| (y * self.width + x) as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reveal_circle() {
+        let mut visibility = VisibilityMap::new(10, 10);
+        assert_eq!(visibility.get(5, 5), Visibility::Unexplored);
+
+        visibility.update([((5, 5), 2)]);
+        assert!(visibility.is_visible(5, 5));
+        assert!(visibility.is_visible(7, 5) && visibility.is_visible(5, 3));
+        // Round, not square
+        assert!(visibility.is_visible(6, 6));
+        assert!(!visibility.is_visible(7, 7));
+        assert_eq!(visibility.get(8, 5), Visibility::Unexplored);
+    }
+
+    #[test]
+    fn test_tiles_out_of_sight_stay_explored() {
+        let mut visibility = VisibilityMap::new(10, 10);
+        visibility.update([((1, 1), 1)]);
+        visibility.update([((8, 8), 1)]);
+
+        assert_eq!(visibility.get(1, 1), Visibility::Explored);
+        assert!(visibility.is_explored(1, 1));
+        assert_eq!(visibility.get(8, 8), Visibility::Visible);
+        assert_eq!(visibility.explored_count(), 10);
+
+        // Nothing looking, so nothing visible
+        visibility.update([]);
+        assert_eq!(visibility.get(8, 8), Visibility::Explored);
+        assert_eq!(visibility.get(5, 5), Visibility::Unexplored);
+        assert_eq!(visibility.explored_count(), 10);
+    }
+
+    #[test]
+    fn test_overlapping_sight_and_map_edges() {
+        let mut visibility = VisibilityMap::new(4, 4);
+        // Overlapping circles, and one hanging off the corner of the map
+        visibility.update([((0, 0), 3), ((1, 1), 3), ((-5, -5), 2)]);
+        assert!(visibility.is_visible(3, 3));
+        assert_eq!(visibility.get(-1, 0), Visibility::Unexplored);
+
+        visibility.update([((3, 3), 0)]);
+        assert!(visibility.is_visible(3, 3));
+        assert_eq!(visibility.get(0, 0), Visibility::Explored);
+    }
+}